@@ -1,10 +1,100 @@
 //! All of the custom errors that can be returned in a Result value in this package's functions
+//!
+//! Every public function of this crate that can fail returns a [PixelveilError]. Failures that are specific to hiding
+//! and recovering data (e.g. an image that is too small for a payload) are wrapped in
+//! [PixelveilError::Steganography], and errors of the `image` crate and of I/O are wrapped as well, so the original
+//! error is always available through [source](std::error::Error::source).
 
+use image::ImageError;
 use std::{
     error,
     fmt::{self, Display, Formatter},
+    io,
 };
 
+/// The error type of every public function in this crate
+#[derive(Debug)]
+pub enum PixelveilError {
+    /// Occurs when hiding or recovering data fails, the stored value describes why.
+    Steganography(SteganographyError),
+
+    /// Occurs when the `image` crate fails to decode or encode an image.
+    Image(ImageError),
+
+    /// Occurs when reading or writing a file fails.
+    Io(io::Error),
+
+    /// Occurs when an argument is out of range, or doesn't apply to the image it is used with.
+    ///
+    /// The stored value represents the explanation to why the argument is invalid.
+    InvalidParameter(String),
+
+    /// Occurs when two images that must be of the same dimensions aren't.
+    ///
+    /// The stored values are (in this order):
+    /// * The dimensions of the first image
+    /// * The dimensions of the second image
+    DimensionMismatch((u32, u32), (u32, u32)),
+
+    /// Occurs when an image's pixel format can't be used by an operation.
+    ///
+    /// The stored value describes the pixel format and the operation.
+    UnsupportedPixelFormat(String),
+}
+
+impl Display for PixelveilError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Steganography(e) => write!(f, "{e}"),
+            Self::Image(e) => write!(f, "Image error: {e}"),
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::InvalidParameter(reason) => write!(f, "Invalid parameter: {reason}"),
+            Self::DimensionMismatch(first, second) => {
+                write!(
+                    f,
+                    "The images don't have the same dimensions: {first:?} and {second:?}"
+                )
+            }
+            Self::UnsupportedPixelFormat(reason) => {
+                write!(f, "Unsupported pixel format: {reason}")
+            }
+        }
+    }
+}
+
+impl error::Error for PixelveilError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Steganography(e) => Some(e),
+            Self::Image(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SteganographyError> for PixelveilError {
+    fn from(error: SteganographyError) -> Self {
+        Self::Steganography(error)
+    }
+}
+
+impl From<ImageError> for PixelveilError {
+    fn from(error: ImageError) -> Self {
+        match error {
+            // the image crate wraps I/O errors, they are surfaced directly so they can be told apart
+            ImageError::IoError(e) => Self::Io(e),
+            e => Self::Image(e),
+        }
+    }
+}
+
+impl From<io::Error> for PixelveilError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
 /// All steganography-related errors
 #[derive(Debug)]
 pub enum SteganographyError {
@@ -24,6 +114,81 @@ pub enum SteganographyError {
     /// * The minimum number of accepted and unused bit planes that the image was expected to contain
     /// * The number of accepted and unused bit planes that was found
     InsufficientPlaneNumber(usize, usize),
+
+    /// Occurs when data that was just embedded into an image can't be extracted back from it.
+    ///
+    /// This error is only returned by embedding functions that verify their result. The image is restored to its
+    /// original state before this error is returned.
+    VerificationFailed,
+
+    /// Occurs when a payload that was split across multiple images can't be reassembled because parts are missing.
+    ///
+    /// The stored values are (in this order):
+    /// * The number of parts the payload was split into
+    /// * The number of images that were given
+    MissingPayloadParts(usize, usize),
+
+    /// Occurs when the parts of a payload that was split across multiple images don't belong together.
+    ///
+    /// This error will be propagated when parts have different payload IDs, disagree about the payload, or the same
+    /// part is given more than once.
+    ///
+    /// The stored value represents the explanation to why the parts are inconsistent.
+    InconsistentPayloadParts(String),
+
+    /// Occurs when a secret that was shared across multiple images can't be recovered because there are too few
+    /// shares.
+    ///
+    /// The stored values are (in this order):
+    /// * The number of distinct shares that are needed to recover the secret (the threshold)
+    /// * The number of distinct shares that were found
+    InsufficientShares(usize, usize),
+
+    /// Occurs when the tiles of an image can't hold a whole tiled payload.
+    ///
+    /// The stored value is the number of payload bytes that didn't fit.
+    InsufficientTileCapacity(usize),
+
+    /// Occurs when every tile of an image was read without finding the final part of a tiled payload.
+    ///
+    /// The stored value is the number of tiles that were read.
+    IncompleteTiledPayload(usize),
+
+    /// Occurs when a progress observer cancels an operation.
+    ///
+    /// Embedding functions restore the image to its original state before this error is returned.
+    Cancelled,
+
+    /// Occurs when the data iterator that was passed to an embedding function doesn't yield the declared number of
+    /// bytes.
+    ///
+    /// The iterator is not read past the first extra byte, so if it yields too many bytes `actual` is `declared + 1`.
+    /// The image is restored to its original state before this error is returned.
+    DataLengthMismatch {
+        /// The number of bytes the data was declared to have.
+        declared: usize,
+        /// The number of bytes the iterator yielded.
+        actual: usize,
+    },
+
+    /// Occurs when the original image is requested from a payload that wasn't embedded reversibly.
+    ///
+    /// Only payloads that were embedded with [embed_data_reversible](crate::bpcs::embed_data_reversible) store the
+    /// cover they replaced.
+    CoverNotStored,
+
+    /// Occurs when a payload that stores the cover it replaced would be updated.
+    ///
+    /// Replacing or appending to a payload that was embedded with
+    /// [embed_data_reversible](crate::bpcs::embed_data_reversible) would discard its stored cover, so the original
+    /// image could never be restored. Restore the cover with [restore_cover](crate::bpcs::restore_cover) and embed the
+    /// new payload into it instead.
+    CoverStored,
+
+    /// Occurs when the stored cover of a reversible payload is malformed.
+    ///
+    /// The stored value represents the explanation to why the stored cover is invalid.
+    InvalidCoverData(String),
 }
 
 impl Display for SteganographyError {
@@ -38,6 +203,63 @@ impl Display for SteganographyError {
             Self::InvalidIVData(reason) => {
                 write!(f, "The extracted IV data is invalid: {reason}")
             }
+            Self::VerificationFailed => {
+                write!(
+                    f,
+                    "The embedded data could not be extracted back from the image, the image was left unchanged"
+                )
+            }
+            Self::MissingPayloadParts(part_count, got) => {
+                write!(
+                    f,
+                    "The payload was split into {part_count} parts, but only {got} images were given"
+                )
+            }
+            Self::InconsistentPayloadParts(reason) => {
+                write!(f, "The payload parts don't belong together: {reason}")
+            }
+            Self::InsufficientShares(threshold, got) => {
+                write!(
+                    f,
+                    "Recovering the secret requires at least {threshold} distinct shares, got {got}"
+                )
+            }
+            Self::InsufficientTileCapacity(missing) => {
+                write!(
+                    f,
+                    "The tiles of the image can't hold the payload, {missing} bytes didn't fit"
+                )
+            }
+            Self::IncompleteTiledPayload(tile_count) => {
+                write!(
+                    f,
+                    "The final part of the tiled payload wasn't found in {tile_count} tiles"
+                )
+            }
+            Self::Cancelled => {
+                write!(f, "The operation was cancelled by its progress observer")
+            }
+            Self::DataLengthMismatch { declared, actual } => {
+                write!(
+                    f,
+                    "The data was declared to be {declared} bytes long, but the iterator yielded {actual}"
+                )
+            }
+            Self::CoverNotStored => {
+                write!(
+                    f,
+                    "The payload wasn't embedded reversibly, so the original image can't be restored"
+                )
+            }
+            Self::CoverStored => {
+                write!(
+                    f,
+                    "The payload stores the cover it replaced, updating it would discard the cover"
+                )
+            }
+            Self::InvalidCoverData(reason) => {
+                write!(f, "The stored cover is invalid: {reason}")
+            }
         }
     }
 }
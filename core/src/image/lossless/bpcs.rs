@@ -12,38 +12,143 @@
 //! * `rng_key` — This parameter is a parameter specific to this crate's implementation of BPCS, it controls the seed for
 //!   the pseudo-random selection of which parts of the image to change. This parameter is also a private component.
 //!
+//! # Supported Image Types
+//! Every function in this module accepts an `ImageBuffer` of any pixel type whose subpixel type implements
+//! [BitPlaneSubpixel]. That includes 8-bit images like `RgbImage` and `GrayImage`
+//! (8 bit planes per channel), and 16-bit images like `ImageBuffer<Rgb<u16>, Vec<u16>>` and
+//! `ImageBuffer<Luma<u16>, Vec<u16>>` (16 bit planes per channel). 16-bit images roughly double the amount of noise-like
+//! bit planes, so don't downsample high bit depth vessels before embedding.
+//!
+//! The functions are also generic over the `ImageBuffer` container, so images don't have to be owned `Vec`s. Frames
+//! that live in borrowed buffers (e.g. decoded video frames or memory-mapped files) can be wrapped without copying
+//! using [ImageBuffer::from_raw](https://docs.rs/image/latest/image/struct.ImageBuffer.html#method.from_raw):
+//! ```no_run
+//! # use image::{ImageBuffer, Rgb};
+//! # use pixelveil::bpcs::embed_data;
+//! # let (width, height) = (1920u32, 1080u32);
+//! let mut frame: Vec<u8> = vec![0u8; (width * height * 3) as usize]; // e.g. a decoded video frame
+//!
+//! let mut frame_image = ImageBuffer::<Rgb<u8>, &mut [u8]>::from_raw(width, height, frame.as_mut_slice()).unwrap();
+//! let data = [1u8, 2, 3];
+//! embed_data(&mut frame_image, &mut data.into_iter(), data.len(), 0.3, [0u8; 32])?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//! The buffer must be tightly packed (row stride equal to `width * channels`). Buffers with padded rows need to be
+//! split into rows or repacked first.
+//!
+//! # Configuration
+//! The parameters can also be collected into a validated [BpcsConfig], which adds channel and block policies and
+//! optional layers, and is used with [embed_data_with_config] and [extract_data_with_config]. Enabling the `serde`
+//! feature makes it serializable, so profiles can be loaded from configuration files.
+//!
+//! # Format Versions
+//! Every payload declares the version of the format it was embedded with, and [probe] reports it. Payloads are always
+//! embedded with the latest version, and extraction detects the version of a payload on its own:
+//! * Version `0` — Payloads that were embedded before the format was versioned. The planes were selected with `rand`'s
+//!   `StdRng`, whose algorithm isn't guaranteed to stay the same between `rand` releases.
+//! * Version `1` — The planes are selected with ChaCha20 and a shuffle that is implemented by this crate, so the same
+//!   key selects the same planes on every platform and with every version of the dependencies.
+//!
+//! # Block Encodings
+//! Every payload also declares the [BlockEncoding] of its message planes. By default a message plane is conjugated if
+//! it isn't complex enough, and [embed_data_minimum_change] (or a [BpcsConfig] with
+//! [BlockEncoding::MinimumChange]) writes every message plane in whichever keyed transform flips the fewest bits of
+//! the image. Extraction detects the encoding on its own.
+//!
+//! # Reversible Embedding
+//! [embed_data_reversible] embeds the losslessly compressed bit planes it replaces together with the data, so
+//! [restore_cover] can return a bit-exact copy of the original image along with the data. The compressed planes take
+//! part of the capacity, which [estimate_reversible_capacity] accounts for.
+//!
+//! # Low-Level Access
+//! The bit planes, their complexity and conjugation, and reading and writing them at given coordinates are available
+//! in the [raw] module, for experimenting with BPCS and building custom methods on top of it.
+//!
 //! For an in depth review of the principles of BPCS please read [this paper](https://www.researchgate.net/file.PostFileLoader.html?id=53b3b80cd5a3f216068b4643&assetKey=AS%3A273551540588545%401442231177391).
 //! As the link is old, it might not work anymore, if thats the case search for "Principle and Application of BPCS
 //! Steganography" by E Kawaguchi.
 
+pub(crate) mod alpha_recovery;
 pub(crate) mod bit_plane;
 pub(crate) mod bit_plane_iter;
 pub(crate) mod capacity;
+pub(crate) mod config;
+pub(crate) mod cover_compression;
+pub(crate) mod deniability;
+pub(crate) mod deterministic;
 pub(crate) mod dynamic_prefix;
+pub(crate) mod embed_report;
+pub(crate) mod embedding_plan;
 pub(crate) mod initialization_vector;
 pub(crate) mod message_plane_iter;
+pub(crate) mod payload_update;
+pub(crate) mod plane_encoding;
 pub(crate) mod plane_selection;
+pub(crate) mod probe;
+pub(crate) mod progress;
+pub mod raw;
+pub(crate) mod reversible;
+pub(crate) mod selection_rng;
+pub(crate) mod slots;
+pub(crate) mod split_payload;
+pub(crate) mod threshold_sharing;
+pub(crate) mod tiled;
+pub(crate) mod vessel_ranking;
+pub(crate) mod visualization;
 
 use crate::{
-    errors::SteganographyError,
+    errors::{PixelveilError, SteganographyError},
     image::lossless::bpcs::{
         bit_plane::{
-            BYTES_PER_PLANE, USIZE_PLANE_SIZE, get_planes_from_image_and_coords, write_plane_at,
+            BYTES_PER_PLANE, BitPlane, ImageCoding, get_planes_from_image_and_coords,
+            read_planes_from_image_and_coords, write_plane_at,
         },
-        dynamic_prefix::{num_of_prefixed_planes_for_n_bits, prefix_length},
+        capacity::image_data_capacity,
+        dynamic_prefix::validate_min_alpha,
         initialization_vector::{
-            MESSAGE_LENGTH_IV_BIT_NUMBER, MESSAGE_REMNANT_IV_BIT_NUMBER,
-            build_conjugation_map_planes, build_iv_planes,
-            extract_conj_map_data_from_conj_map_planes, extract_iv_data_from_iv_planes,
+            FORMAT_VERSION, IVData, build_conjugation_map_planes, build_iv_planes,
+            calculate_message_plane_length_and_remnant, extract_conj_map_data_from_conj_map_planes,
+            extract_iv_data_from_iv_planes,
         },
         message_plane_iter::MessagePlanesIter,
-        plane_selection::{PlaneSelector, count_accepted_planes},
+        plane_encoding::PlaneEncoding,
+        plane_selection::{EmbeddingLayout, PlaneCoords, PlaneSelector},
+        progress::{PROGRESS_INTERVAL, ReportProgress, no_progress},
+        reversible::split_reversible_container,
+    },
+    utils::image_utils::{
+        BitPlaneSubpixel, OwnedImageBuffer, image_to_binary_code, image_to_gray_code,
     },
-    utils::image_utils::{image_to_binary_code, image_to_gray_code},
 };
-use image::RgbImage;
+pub use alpha_recovery::extract_data_with_unknown_alpha;
+pub use config::{
+    BlockPolicy, BpcsConfig, BpcsConfigBuilder, ChannelPolicy, embed_data_with_config,
+    extract_data_with_config,
+};
+pub use deniability::{embed_data_with_decoy, extract_hidden_data};
+pub use deterministic::embed_data_deterministic;
+pub use embed_report::EmbedReport;
+pub use embedding_plan::{EmbeddingPlan, PlannedPlane, PlannedPlaneKind};
+use image::{ImageBuffer, Pixel};
 use itertools::Itertools;
-use std::iter::zip;
+pub use payload_update::{PayloadUpdateReport, append_data, replace_data};
+pub use plane_encoding::{BlockEncoding, embed_data_minimum_change};
+pub use probe::{PayloadInfo, probe};
+pub use progress::{
+    BpcsStage, ProgressObserver, embed_data_with_progress, extract_data_with_progress,
+};
+use rand::{Rng, rng};
+pub use reversible::{embed_data_reversible, estimate_reversible_capacity, restore_cover};
+pub use slots::{SlotPayload, embed_slots, extract_slot};
+pub use split_payload::{embed_data_across_images, extract_data_across_images};
+use std::{
+    iter::zip,
+    ops::{Deref, DerefMut},
+};
+pub use threshold_sharing::{embed_secret_shares, recover_secret_from_shares};
+pub use tiled::{TiledEmbedder, TiledExtractor, embed_data_tiled, extract_data_tiled};
+pub use vessel_ranking::{VesselScore, rank_vessels};
+pub use visualization::{render_accepted_plane_map, render_complexity_heatmap};
 
 /// Embed data into an image using BPCS
 ///
@@ -68,7 +173,8 @@ use std::iter::zip;
 ///
 /// # Arguments
 /// The function takes in five arguments:
-/// * `source_image: &mut RgbImage` — A mutable reference to the source image.
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image (e.g. an `RgbImage`, a 16-bit
+///   `ImageBuffer<Rgb<u16>, Vec<u16>>` or an `ImageBuffer` over a borrowed `&mut [u8]` buffer).
 /// * `data: &mut impl Iterator<Item = u8>` — An iterator that yields bytes (u8s), this is the data that is going to be
 ///   embedded. This was chosen to be an iterator to mitigate the memory usage of large amounts of data.
 /// * `data_length: usize` — The length of the data iterator, in bytes (the number of u8s). Must be the exact length
@@ -78,91 +184,662 @@ use std::iter::zip;
 ///
 /// # Errors
 /// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
 /// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
-/// inputted data.
+///   inputted data.
+/// * `SteganographyError::DataLengthMismatch` — If the `data` iterator doesn't yield exactly `data_length` bytes.
 ///
 /// # Returns
-/// Returns `Result<(), Box<dyn std::error::Error>>`, the source image will be modified instead of returning a new one.
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
 ///
 /// # Notes
 /// For the best security, please use unique and original images and rng keys for each embedding operation as repeated
-/// usage of these can lead to many attacks.
-pub fn embed_data(
-    source_image: &mut RgbImage,
+/// usage of these can lead to many attacks. The filler bits are random, so embedding the same data twice gives
+/// different images, use [embed_data_deterministic] if the output has to be reproducible.
+///
+/// The `data` iterator is never read past `data_length` bytes and one extra byte. The original content of the selected
+/// planes is kept in memory while writing, so the image can be restored if the iterator turns out to be shorter or
+/// longer.
+pub fn embed_data<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
     data: &mut impl Iterator<Item = u8>,
     data_length: usize,
     min_alpha: f64,
     rng_key: [u8; 32],
-) -> Result<(), SteganographyError> {
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
     image_to_gray_code(source_image);
 
-    // calculate all the necessary values for the initialization vectors and such
-    let message_plane_length = (data_length as f64 / BYTES_PER_PLANE as f64).ceil() as usize;
-    let mut remnant_bit_number = (data_length * 8) % (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE);
-    remnant_bit_number = if remnant_bit_number == 0 {
-        USIZE_PLANE_SIZE * USIZE_PLANE_SIZE
-    } else {
-        remnant_bit_number
+    let embedded = embed_data_into_gray_coded_image(
+        source_image,
+        data,
+        data_length,
+        min_alpha,
+        rng_key,
+        &mut rng(),
+    );
+
+    // the image is converted back even if the embedding failed, planes are only written after all of them were
+    // successfully selected, so a failed embedding leaves the image unchanged
+    image_to_binary_code(source_image);
+
+    embedded?;
+    Ok(())
+}
+
+/// Embed data whose length is known from its iterator into an image using BPCS
+///
+/// This is [embed_data] for data that comes from an `ExactSizeIterator` (e.g. a `Vec<u8>`, an array or a slice
+/// iterator), the length is taken from the iterator instead of being passed separately.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_exact_size;
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let data = vec![6u8, 9, 193, 7, 1, 7];
+///
+/// embed_data_exact_size(&mut vessel_image, data, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: impl IntoIterator<Item = u8, IntoIter: ExactSizeIterator>` — The data that is going to be embedded.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+/// * `SteganographyError::DataLengthMismatch` — If the iterator doesn't yield as many bytes as it reports.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// The data is embedded exactly like [embed_data] embeds it, and is extracted with [extract_data].
+pub fn embed_data_exact_size<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: impl IntoIterator<Item = u8, IntoIter: ExactSizeIterator>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    let mut data = data.into_iter();
+    let data_length = data.len();
+    embed_data(source_image, &mut data, data_length, min_alpha, rng_key)
+}
+
+/// Embed data into a new copy of an image using BPCS, and report how the image was changed
+///
+/// This is the non-destructive version of [embed_data]. The source image is never modified, the data is embedded into
+/// a copy of it which is returned together with an [EmbedReport].
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_with_report;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+/// let data: [u8; _] = [6, 9, 193, 7, 1, 7];
+///
+/// let (stego_image, report) = embed_data_with_report(
+///     &vessel_image,
+///     &mut data.into_iter(),
+///     data.len(),
+///     0.3,
+///     [0u8; 32],
+/// ).unwrap();
+///
+/// println!("PSNR: {} dB, {} bits flipped", report.psnr, report.bits_flipped);
+/// ```
+///
+/// # Arguments
+/// The function takes in five arguments:
+/// * `source_image: &ImageBuffer<P, C>` — A reference to the source image, it is left untouched.
+/// * `data: &mut impl Iterator<Item = u8>` — An iterator that yields the bytes that are going to be embedded.
+/// * `data_length: usize` — The length of the data iterator, in bytes (the number of u8s). Must be the exact length
+///   of the `data` iterator.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+/// * `SteganographyError::DataLengthMismatch` — If the `data` iterator doesn't yield exactly `data_length` bytes.
+///
+/// # Returns
+/// Returns `Result<(OwnedImageBuffer<P>, EmbedReport), PixelveilError>`. If `Ok(...)` is returned,
+/// the contained values are the new image that has the data embedded in it, and a report of the changes that were
+/// made to it.
+///
+/// # Notes
+/// This function holds two copies of the image in memory while building the report, if memory is a concern and a
+/// report isn't needed, use [embed_data] instead.
+pub fn embed_data_with_report<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(OwnedImageBuffer<P>, EmbedReport), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let mut new_image: OwnedImageBuffer<P> = ImageBuffer::from_raw(
+        source_image.width(),
+        source_image.height(),
+        source_image.as_raw().to_vec(),
+    )
+    .unwrap();
+
+    image_to_gray_code(&mut new_image);
+    let layout = embed_data_into_gray_coded_image(
+        &mut new_image,
+        data,
+        data_length,
+        min_alpha,
+        rng_key,
+        &mut rng(),
+    )?;
+    image_to_binary_code(&mut new_image);
+
+    let report = EmbedReport::new(source_image, &new_image, &layout);
+
+    Ok((new_image, report))
+}
+
+/// Embed data into an image using BPCS, and verify that it can be extracted back
+///
+/// Works exactly like [embed_data], but after embedding the data is extracted back from the resulting image (with
+/// the same parameters) and compared with the data that was embedded. If they don't match, the image is restored to
+/// its original state and an error is returned, instead of handing back an image that can't be decoded.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_and_verify;
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let data: [u8; _] = [6, 9, 193, 7, 1, 7];
+///
+/// embed_data_and_verify(
+///     &mut vessel_image,
+///     &mut data.into_iter(),
+///     data.len(),
+///     0.3,
+///     [0u8; 32],
+/// ).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in five arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &mut impl Iterator<Item = u8>` — An iterator that yields the bytes that are going to be embedded.
+/// * `data_length: usize` — The length of the data iterator, in bytes (the number of u8s). Must be the exact length
+///   of the `data` iterator.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+/// * `SteganographyError::DataLengthMismatch` — If the `data` iterator doesn't yield exactly `data_length` bytes.
+/// * `SteganographyError::VerificationFailed` — If the data that was extracted back from the image doesn't match the
+///   data that was embedded.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// The embedded data is kept in memory until the verification is done, and the image is read twice. Use this function
+/// when the embedded image is the only copy of the data that is going to be kept (e.g. if the original data is deleted
+/// after embedding).
+pub fn embed_data_and_verify<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    image_to_gray_code(source_image);
+
+    let layout = match select_embedding_layout(
+        source_image,
+        data_length,
+        min_alpha,
+        rng_key,
+        BlockEncoding::Conjugation,
+    ) {
+        Ok(layout) => layout,
+        Err(e) => {
+            image_to_binary_code(source_image);
+            return Err(e.into());
+        }
+    };
+
+    embed_layout_verified(
+        source_image,
+        &layout,
+        data,
+        data_length,
+        min_alpha,
+        &mut rng(),
+        Some(|image: &ImageBuffer<P, C>| extract_data(image, min_alpha, rng_key)),
+    )
+}
+
+/// Plan a BPCS embedding without changing the image
+///
+/// Runs the same plane selection as [embed_data] with the same parameters, and returns every bit plane that would be
+/// overwritten, without writing anything. This can be used to review where an image is going to change before
+/// committing a payload.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::plan_embedding;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let plan = plan_embedding(&vessel_image, 1000, 0.3, [0u8; 32]).unwrap();
+///
+/// println!("{} planes would be changed", plan.planes.len());
+/// plan.mask.save("mask.png").unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &ImageBuffer<P, C>` — A reference to the source image, it is left untouched.
+/// * `data_length: usize` — The length of the data that would be embedded, in bytes.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store data of
+///   the given length.
+///
+/// # Returns
+/// Returns `Result<EmbeddingPlan, PixelveilError>`. If `Ok(...)` is returned, the contained value lists every bit
+/// plane that would be changed, and a mask image that highlights the changed blocks by bit index.
+///
+/// # Notes
+/// The plan is exact, embedding data of the same length with the same parameters into the same image will change
+/// exactly the listed planes. The content that will be written into them is random-looking, so some of the listed
+/// planes might keep some of their original bits.
+pub fn plan_embedding<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<EmbeddingPlan, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let (message_plane_length, _) = calculate_message_plane_length_and_remnant(data_length);
+
+    let mut plane_selector =
+        PlaneSelector::new(source_image, min_alpha, rng_key).reading_binary_coded_image();
+    let layout = EmbeddingLayout::select(&mut plane_selector, min_alpha, message_plane_length)?;
+
+    Ok(EmbeddingPlan::new(
+        &layout,
+        source_image.width(),
+        source_image.height(),
+        P::Subpixel::BIT_DEPTH,
+    ))
+}
+
+fn embed_data_into_gray_coded_image<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    filler_rng: &mut impl Rng,
+) -> Result<EmbeddingLayout, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    embed_data_into_gray_coded_image_with_encoding(
+        source_image,
+        data,
+        data_length,
+        min_alpha,
+        rng_key,
+        BlockEncoding::Conjugation,
+        filler_rng,
+    )
+}
+
+fn embed_data_into_gray_coded_image_with_encoding<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    block_encoding: BlockEncoding,
+    filler_rng: &mut impl Rng,
+) -> Result<EmbeddingLayout, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    let layout = select_embedding_layout(
+        source_image,
+        data_length,
+        min_alpha,
+        rng_key,
+        block_encoding,
+    )?;
+    write_data_into_gray_coded_image_with_progress(
+        source_image,
+        &layout,
+        data,
+        data_length,
+        min_alpha,
+        filler_rng,
+        &no_progress,
+    )?;
+    Ok(layout)
+}
+
+// Writes the data into the planes of a layout that was selected on the Gray coded image, and converts the image back
+//  to binary code. If verify is given, it extracts the data back from the final image exactly like a recipient would,
+//  and the image is restored if the data doesn't match. The image is left unchanged on any error.
+pub(crate) fn embed_layout_verified<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    layout: &EmbeddingLayout,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    filler_rng: &mut impl Rng,
+    verify: Option<impl Fn(&ImageBuffer<P, C>) -> Result<Vec<u8>, PixelveilError>>,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    // keep the original planes and the embedded data, so the image can be restored if the verification fails
+    let verifying = verify.is_some();
+    let original_planes = verifying.then(|| {
+        get_planes_from_image_and_coords(source_image, layout.all_plane_coords().copied().collect())
+    });
+    let mut embedded_data: Vec<u8> = Vec::with_capacity(if verifying { data_length } else { 0 });
+
+    let written = {
+        let mut recorded_data = data.inspect(|byte| {
+            if verifying {
+                embedded_data.push(*byte);
+            }
+        });
+        write_data_into_gray_coded_image_with_progress(
+            source_image,
+            layout,
+            &mut recorded_data,
+            data_length,
+            min_alpha,
+            filler_rng,
+            &no_progress,
+        )
     };
 
-    // crate conjugation map
-    let mut conjugation_map: Vec<bool> = Vec::with_capacity(message_plane_length);
+    image_to_binary_code(source_image);
+    written?;
 
-    // patch the data iterator and conjugation map into the message plane iter
-    let message_plane_iter = MessagePlanesIter::new(data, &mut conjugation_map);
+    let (Some(verify), Some(original_planes)) = (verify, original_planes) else {
+        return Ok(());
+    };
+    if verify(source_image).is_ok_and(|extracted_data| extracted_data == embedded_data) {
+        return Ok(());
+    }
 
-    // collect the accepted planes and put them in a PRNG selector
-    let mut plane_selector = PlaneSelector::new(&source_image, min_alpha, rng_key);
+    image_to_gray_code(source_image);
+    for (coords, plane) in zip(layout.all_plane_coords(), original_planes) {
+        write_plane_at(source_image, plane, *coords);
+    }
+    image_to_binary_code(source_image);
 
-    // select all planes
-    let iv_plane_coords = plane_selector.select_iv_planes(min_alpha)?;
-    let conj_map_plane_coords =
-        plane_selector.select_conjugation_map_planes(min_alpha, message_plane_length)?;
-    let message_plane_coords = plane_selector.select_message_planes(message_plane_length)?;
+    Err(SteganographyError::VerificationFailed.into())
+}
+
+fn select_embedding_layout<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    block_encoding: BlockEncoding,
+) -> Result<EmbeddingLayout, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let (message_plane_length, _) = calculate_message_plane_length_and_remnant(data_length);
+
+    // collect the accepted planes and put them in a PRNG selector, then select all planes
+    let mut plane_selector = PlaneSelector::new(source_image, min_alpha, rng_key);
+    EmbeddingLayout::select_with_encoding(
+        &mut plane_selector,
+        min_alpha,
+        message_plane_length,
+        block_encoding,
+    )
+}
+
+// writes data that is known to be exactly data_length bytes long, e.g. data from a slice
+fn write_data_into_gray_coded_image<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    layout: &EmbeddingLayout,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    filler_rng: &mut impl Rng,
+) where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    write_data_into_gray_coded_image_with_progress(
+        source_image,
+        layout,
+        data,
+        data_length,
+        min_alpha,
+        filler_rng,
+        &no_progress,
+    )
+    .expect("Writing data of the declared length without a progress observer can't fail.");
+}
+
+// Writes the data like write_data_into_gray_coded_image, and reports every written plane. If the progress reporter
+//  cancels, or the data iterator doesn't yield exactly data_length bytes, the planes that were already written are
+//  restored, so the image is left unchanged.
+fn write_data_into_gray_coded_image_with_progress<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    layout: &EmbeddingLayout,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    filler_rng: &mut impl Rng,
+    progress: &ReportProgress,
+) -> Result<(), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    // keep the original planes so the image can be restored if the writing fails
+    let original_planes = get_planes_from_image_and_coords(
+        source_image,
+        layout.all_plane_coords().copied().collect(),
+    );
+
+    let written = write_layout_planes(
+        source_image,
+        layout,
+        data,
+        data_length,
+        min_alpha,
+        filler_rng,
+        progress,
+    );
+    if written.is_err() {
+        for (coords, plane) in zip(layout.all_plane_coords(), original_planes) {
+            write_plane_at(source_image, plane, *coords);
+        }
+    }
+    written
+}
+
+// writes the planes of the layout in order, and stops at the first plane that fails
+fn write_layout_planes<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    layout: &EmbeddingLayout,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    filler_rng: &mut impl Rng,
+    progress: &ReportProgress,
+) -> Result<(), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    // calculate all the necessary values for the initialization vectors and such
+    let (message_plane_length, remnant_bit_number) =
+        calculate_message_plane_length_and_remnant(data_length);
+
+    let total_planes = layout.all_plane_coords().count();
+    let mut written_planes = 0usize;
+    let mut write_plane = |source_image: &mut ImageBuffer<P, C>, plane, coords| {
+        write_plane_at(source_image, plane, coords);
+        written_planes += 1;
+        if written_planes.is_multiple_of(PROGRESS_INTERVAL) || written_planes == total_planes {
+            progress(BpcsStage::WritingPlanes, written_planes, total_planes)?;
+        }
+        Ok::<(), SteganographyError>(())
+    };
+
+    // crate conjugation map, it holds the map bits of every message plane's encoding
+    let mut conjugation_map: Vec<bool> =
+        Vec::with_capacity(message_plane_length * layout.encoding.map_bits_per_plane());
 
     // embed IV
-    let iv_planes = build_iv_planes(min_alpha, message_plane_length, remnant_bit_number);
-    assert_eq!(iv_plane_coords.len(), iv_planes.len());
-    let iv_pairs = zip(iv_plane_coords, iv_planes);
+    let iv_planes = build_iv_planes(
+        min_alpha,
+        message_plane_length,
+        remnant_bit_number,
+        layout.encoding.block_encoding(),
+        layout.stores_cover,
+        filler_rng,
+    );
+    let iv_planes = match &layout.header_mask {
+        Some(header_mask) => header_mask.mask_planes(iv_planes, 0),
+        None => iv_planes,
+    };
+    assert_eq!(layout.iv_plane_coords.len(), iv_planes.len());
+    let iv_pairs = zip(&layout.iv_plane_coords, iv_planes);
 
     for (coords, plane) in iv_pairs {
-        write_plane_at(source_image, plane, coords);
+        write_plane(source_image, plane, *coords)?;
     }
 
-    // embed message (and by that we construct the conjugation map)
-    assert_eq!(message_plane_coords.len(), message_plane_length);
-    let message_pairs = zip(message_plane_coords, message_plane_iter);
-    for (coords, plane) in message_pairs {
-        write_plane_at(source_image, plane, coords);
+    // the data is never read past the declared length, and the bytes that were read are counted so a shorter iterator
+    //  is caught before its random padding is mistaken for data
+    let mut bytes_read = 0usize;
+    {
+        let mut declared_data = data.by_ref().take(data_length).inspect(|_| bytes_read += 1);
+
+        // patch the data iterator into the message plane iter
+        let message_plane_iter = MessagePlanesIter::new(&mut declared_data, filler_rng);
+
+        // encode and embed message (and by that we construct the conjugation map)
+        assert_eq!(layout.message_plane_coords.len(), message_plane_length);
+        let message_pairs = zip(&layout.message_plane_coords, message_plane_iter);
+        for (coords, data_plane) in message_pairs {
+            let plane = layout.encoding.encode(
+                data_plane,
+                min_alpha,
+                || get_planes_from_image_and_coords(source_image, vec![*coords]).remove(0),
+                &mut conjugation_map,
+            );
+            write_plane(source_image, plane, *coords)?;
+        }
+    }
+    if bytes_read == data_length && data.next().is_some() {
+        bytes_read += 1;
+    }
+    if bytes_read != data_length {
+        return Err(SteganographyError::DataLengthMismatch {
+            declared: data_length,
+            actual: bytes_read,
+        });
     }
 
     // embed conjugation map
-    let conj_map_planes = build_conjugation_map_planes(conjugation_map, min_alpha);
+    let conj_map_planes = build_conjugation_map_planes(conjugation_map, min_alpha, filler_rng);
+    let conj_map_planes = match &layout.header_mask {
+        Some(header_mask) => header_mask.mask_planes(conj_map_planes, layout.iv_plane_coords.len()),
+        None => conj_map_planes,
+    };
 
-    assert_eq!(conj_map_plane_coords.len(), conj_map_planes.len());
-    let conj_map_pairs = zip(conj_map_plane_coords, conj_map_planes);
+    assert_eq!(layout.conj_map_plane_coords.len(), conj_map_planes.len());
+    let conj_map_pairs = zip(&layout.conj_map_plane_coords, conj_map_planes);
     for (coords, plane) in conj_map_pairs {
-        write_plane_at(source_image, plane, coords);
+        write_plane(source_image, plane, *coords)?;
     }
 
-    image_to_binary_code(source_image);
-
     Ok(())
 }
 
 /// Extract data from an image using BPCS
 ///
+/// The image is only borrowed. The bit planes are converted to Gray Code on the fly as they are read, so the image is
+/// never copied or converted as a whole.
+///
 /// # Example
 /// ```no_run
 /// use pixelveil::{bpcs::extract_data, image_utils::open_rgbimage_from_path};
 /// use image::RgbImage;
 ///
-/// let mut vessel_image = RgbImage::new(512, 512);
+/// let vessel_image = RgbImage::new(512, 512);
 /// let min_alpha = 0.3f64;
 /// let rng_key = [0u8; 32];
 ///
 /// let extracted_data = extract_data(
-///     vessel_image,
+///     &vessel_image,
 ///     min_alpha,
 ///     rng_key,
 /// ).unwrap();
@@ -170,63 +847,217 @@ pub fn embed_data(
 ///
 /// # Arguments
 /// The function takes in three arguments:
-/// * `mut source_image: RgbImage` — The image to extract data from.
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from (e.g. an `RgbImage`, a 16-bit
+///   `ImageBuffer<Rgb<u16>, Vec<u16>>` or an `ImageBuffer` over a borrowed `&[u8]` buffer), it is left untouched.
 /// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
 /// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
 ///
 /// # Errors
 /// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
 /// * `SteganographyError::InvalidIVData` — if the IV in the image contains invalid data. The most likely causes of this
-/// are trying to extract data from an image that doesn't have data hidden in it or incorrect parameters.
+///   are trying to extract data from an image that doesn't have data hidden in it or incorrect parameters.
+/// * `SteganographyError::InvalidCoverData` — If the payload was embedded with [embed_data_reversible] and its
+///   container is malformed.
 ///
 /// # Returns
-/// Returns `Result<i32, Box<Vec<u8> std::error::Error>>`. If `Ok(...)` is returned, the contained value is a vector of
-/// the extracted data bytes.
-pub fn extract_data(
-    mut source_image: RgbImage,
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is a vector of the
+/// extracted data bytes.
+///
+/// # Notes
+/// The accepted bit planes of the least significant bit indexes are still scanned to select the planes, but only the
+/// bit indexes that the embedding reached are visited.
+pub fn extract_data<P, C>(
+    source_image: &ImageBuffer<P, C>,
     min_alpha: f64,
     rng_key: [u8; 32],
-) -> Result<Vec<u8>, SteganographyError> {
-    image_to_gray_code(&mut source_image);
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
 
-    let mut selector = PlaneSelector::new(&source_image, min_alpha, rng_key);
+    let selector =
+        PlaneSelector::new(source_image, min_alpha, rng_key).reading_binary_coded_image();
+    Ok(extract_data_with_selector(
+        source_image,
+        selector,
+        min_alpha,
+    )?)
+}
 
-    let iv_planes =
-        get_planes_from_image_and_coords(&source_image, selector.select_iv_planes(min_alpha)?);
+fn extract_data_with_selector<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    mut selector: PlaneSelector<P, C>,
+    min_alpha: f64,
+) -> Result<Vec<u8>, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let (layout, message_remnant_length) =
+        read_embedding_layout(source_image, &mut selector, min_alpha)?;
+    let (conjugation_map, message_planes) =
+        read_message_planes(source_image, &layout, min_alpha, selector.coding())?;
+
+    let data = decode_message_planes(
+        &layout.encoding,
+        conjugation_map,
+        message_planes,
+        message_remnant_length,
+    );
+    match layout.stores_cover {
+        true => Ok(split_reversible_container(data)?.0),
+        false => Ok(data),
+    }
+}
 
-    let (message_plane_length, message_remnant_length) =
-        extract_iv_data_from_iv_planes(iv_planes, min_alpha)?;
+// reads the conjugation map and the (still encoded) message planes of an embedding
+fn read_message_planes<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    layout: &EmbeddingLayout,
+    min_alpha: f64,
+    coding: ImageCoding,
+) -> Result<(Vec<bool>, Vec<BitPlane>), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let message_plane_length = layout.message_plane_coords.len();
 
-    let conjugation_map_planes = get_planes_from_image_and_coords(
-        &source_image,
-        selector.select_conjugation_map_planes(min_alpha, message_plane_length)?,
+    let conjugation_map_planes = read_planes_from_image_and_coords(
+        source_image,
+        layout.conj_map_plane_coords.clone(),
+        coding,
     );
+    let conjugation_map_planes = match &layout.header_mask {
+        Some(header_mask) => {
+            header_mask.unmask_planes(conjugation_map_planes, layout.iv_plane_coords.len())
+        }
+        None => conjugation_map_planes,
+    };
 
     let conjugation_map = extract_conj_map_data_from_conj_map_planes(
         conjugation_map_planes,
         min_alpha,
-        message_plane_length,
+        message_plane_length * layout.encoding.map_bits_per_plane(),
     )?;
 
-    let message_planes = get_planes_from_image_and_coords(
-        &source_image,
-        selector.select_message_planes(message_plane_length)?,
+    let message_planes = read_planes_from_image_and_coords(
+        source_image,
+        layout.message_plane_coords.clone(),
+        coding,
     );
 
-    assert_eq!(conjugation_map.len(), message_planes.len());
+    Ok((conjugation_map, message_planes))
+}
 
+fn decode_message_planes(
+    encoding: &PlaneEncoding,
+    conjugation_map: Vec<bool>,
+    message_planes: Vec<BitPlane>,
+    message_remnant_length: usize,
+) -> Vec<u8> {
+    let message_plane_length = message_planes.len();
     let mut data: Vec<u8> = Vec::with_capacity(message_plane_length * BYTES_PER_PLANE);
 
-    for (is_conjugated, mut plane) in zip(conjugation_map, message_planes) {
-        if is_conjugated {
-            plane.conjugate();
+    let map_bits = conjugation_map.chunks_exact(encoding.map_bits_per_plane());
+    assert_eq!(map_bits.len(), message_plane_length);
+    for (plane_map_bits, plane) in zip(map_bits, message_planes) {
+        data.extend(encoding.decode(plane, plane_map_bits).export_to_u8s());
+    }
+
+    // an empty message still declares a full remnant, so the final length is capped by the extracted data length
+    let final_length = (message_plane_length.saturating_sub(1) * BYTES_PER_PLANE
+        + message_remnant_length / 8)
+        .min(data.len());
+
+    data.drain(0..final_length).collect_vec()
+}
+
+// Selects and reads the IV planes with the plane selection of the latest format version, and falls back to the older
+//  versions if the IV isn't valid with it. The selector is left at the format version of the IV, returns the IV plane
+//  coords and the IV data.
+fn select_and_read_iv<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    selector: &mut PlaneSelector<P, C>,
+    min_alpha: f64,
+) -> Result<(Vec<PlaneCoords>, IVData), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let mut first_error: Option<SteganographyError> = None;
+    for format_version in (0..=FORMAT_VERSION).rev() {
+        selector.restart_with_format_version(format_version);
+        let iv_plane_coords = selector.select_iv_planes(min_alpha)?;
+        let iv_planes = read_planes_from_image_and_coords(
+            source_image,
+            iv_plane_coords.clone(),
+            selector.coding(),
+        );
+        let iv_planes = match selector.header_mask() {
+            Some(header_mask) => header_mask.unmask_planes(iv_planes, 0),
+            None => iv_planes,
+        };
+
+        match extract_iv_data_from_iv_planes(iv_planes, min_alpha) {
+            // an IV is only valid if it was selected the way its format version selects planes
+            Ok(iv_data) if iv_data.format_version == format_version => {
+                return Ok((iv_plane_coords, iv_data));
+            }
+            Ok(iv_data) => {
+                let iv_format_version = iv_data.format_version;
+                first_error.get_or_insert(SteganographyError::InvalidIVData(format!(
+                    "The IV declares format version {iv_format_version}, but was selected with version {format_version}"
+                )));
+            }
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
         }
-        data.extend(plane.export_to_u8s());
     }
+    Err(first_error.unwrap())
+}
+
+// reads the IV of an embedding and selects the rest of its planes, returns the layout and the message remnant length
+fn read_embedding_layout<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    selector: &mut PlaneSelector<P, C>,
+    min_alpha: f64,
+) -> Result<(EmbeddingLayout, usize), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let (iv_plane_coords, iv_data) = select_and_read_iv(source_image, selector, min_alpha)?;
 
-    let final_length = ((message_plane_length - 1) * BYTES_PER_PLANE) + message_remnant_length / 8;
+    // the IV is read from the image, so the length of the map it declares can overflow
+    let conjugation_map_length = iv_data
+        .message_plane_length
+        .checked_mul(iv_data.block_encoding.map_bits_per_plane())
+        .ok_or_else(|| {
+            SteganographyError::InvalidIVData(format!(
+                "The IV declares {} message planes, which is more than a conjugation map can hold",
+                iv_data.message_plane_length
+            ))
+        })?;
+    let conj_map_plane_coords =
+        selector.select_conjugation_map_planes(min_alpha, conjugation_map_length)?;
+    let message_plane_coords = selector.select_message_planes(iv_data.message_plane_length)?;
 
-    Ok(data.drain(0..final_length).collect_vec())
+    Ok((
+        EmbeddingLayout {
+            iv_plane_coords,
+            conj_map_plane_coords,
+            message_plane_coords,
+            encoding: PlaneEncoding::new(iv_data.block_encoding, selector.randomization_seed()),
+            stores_cover: iv_data.stores_cover,
+            header_mask: selector.header_mask().cloned(),
+        },
+        iv_data.message_remnant_length,
+    ))
 }
 
 /// Estimates the maximum payload capacity for an image that can be embedded using BPCS
@@ -245,7 +1076,8 @@ pub fn extract_data(
 ///
 /// # Arguments
 /// The `estimate_maximum_capacity` function takes in:
-/// * `source_image: &RgbImage` — The source image to analyze for BPCS embedding capacity.
+/// * `source_image: &ImageBuffer<P, C>` — The source image to analyze for BPCS embedding capacity (e.g.
+///   an `RgbImage` or an `ImageBuffer` over a borrowed `&[u8]` buffer).
 /// * `min_alpha: f64` — The BPCS complexity threshold (0.0–0.5).  
 ///
 /// # Errors
@@ -253,21 +1085,21 @@ pub fn extract_data(
 ///
 /// # Returns
 /// Returns a `u64` indicating the maximum number of payload bytes that can be embedded into `source_image` using BPCS,
-/// after subtracting all internal overhead such as prefix data and IV planes.
+/// after subtracting all internal overhead such as prefix data, IV planes and the conjugation map.
 ///
 /// # Notes
 /// * A higher `min_alpha` typically reduces capacity because fewer bit-planes qualify as sufficiently complex.
 /// * The result is deterministic for a given image and threshold.
-pub fn estimate_maximum_capacity(source_image: &RgbImage, min_alpha: f64) -> u64 {
-    let accepted_plane_number = count_accepted_planes(source_image, min_alpha);
-    let prefix_length = prefix_length(min_alpha);
-    let iv_planes_num =
-        (num_of_prefixed_planes_for_n_bits(MESSAGE_LENGTH_IV_BIT_NUMBER, prefix_length)
-            + num_of_prefixed_planes_for_n_bits(MESSAGE_REMNANT_IV_BIT_NUMBER, prefix_length))
-            as u64;
-
-    ((accepted_plane_number - 2 - iv_planes_num) as f64
-        / (1.0 + (1 / ((USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) - prefix_length)) as f64))
-        .floor() as u64
-        * (BYTES_PER_PLANE as u64)
+/// * The capacity is exact, a payload of this length always fits into the image and a longer one never does.
+/// * If `min_alpha` isn't between 0.0 and 0.5 nothing can be embedded with it, so 0 is returned.
+pub fn estimate_maximum_capacity<P, C>(source_image: &ImageBuffer<P, C>, min_alpha: f64) -> u64
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    if validate_min_alpha(min_alpha).is_err() {
+        return 0;
+    }
+
+    image_data_capacity(source_image, min_alpha) as u64
 }
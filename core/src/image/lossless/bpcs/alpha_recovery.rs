@@ -0,0 +1,125 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        bit_plane::{BitPlane, ImageCoding},
+        decode_message_planes,
+        dynamic_prefix::validate_min_alpha,
+        plane_encoding::PlaneEncoding,
+        plane_selection::{PlaneComplexities, PlaneSelector},
+        read_embedding_layout, read_message_planes,
+        reversible::split_reversible_container,
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel};
+use std::{iter::zip, ops::Deref};
+
+// Conjugated message planes are conjugated whenever their complexity is below 0.5, so every embedded message plane
+//  has a complexity of at least 0.5, and every conjugated one has a complexity above 0.5. Planes that were read with an
+//  incorrect min_alpha are cover planes, which break this quickly. Minimum change planes can't be checked this way,
+//  so they rely on the header alone.
+fn message_planes_are_consistent(
+    encoding: &PlaneEncoding,
+    conjugation_map: &[bool],
+    message_planes: &[BitPlane],
+) -> bool {
+    zip(
+        conjugation_map.chunks_exact(encoding.map_bits_per_plane()),
+        message_planes,
+    )
+    .all(|(map_bits, plane)| encoding.is_consistent(plane, map_bits))
+}
+
+/// Extract data from an image using BPCS, when the `min_alpha` it was embedded with is unknown
+///
+/// Every candidate `min_alpha` is tried in order with the key. A candidate is accepted if the header it reads is sane
+/// and the message planes it reads are consistent with the conjugation map. The complexity of every bit plane is only
+/// calculated once, and is shared by all of the candidates.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::extract_data_with_unknown_alpha;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// // try every threshold between 0.0 and 0.5 with a precision of two decimal places
+/// let candidates = (0..=50).map(|i| i as f64 / 100.0);
+/// let (min_alpha, data) = extract_data_with_unknown_alpha(&vessel_image, [0u8; 32], candidates).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `rng_key: [u8; 32]` — The randomization key.
+/// * `candidate_alphas: impl IntoIterator<Item = f64>` — The `min_alpha` values to try, in the order to try them in.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If one of the candidates that are tried isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If none of the candidates could extract a payload. The most likely causes of
+///   this are an incorrect key, or candidates that don't include the `min_alpha` the data was embedded with.
+///
+/// # Returns
+/// Returns `Result<(f64, Vec<u8>), PixelveilError>`. If `Ok(...)` is returned, the contained values are the
+/// candidate that extracted the payload and the extracted data.
+///
+/// # Notes
+/// * Different `min_alpha` values can accept the exact same bit planes and use the same prefix length. These values
+///   extract the same data, so the returned value isn't necessarily the exact value that was used for embedding, only
+///   an equivalent one.
+/// * Payloads that were embedded with [BlockEncoding::MinimumChange](crate::bpcs::BlockEncoding::MinimumChange) can
+///   only be checked by their header, since their message planes don't have to be more complex than `min_alpha`.
+/// * The bit planes are converted to Gray Code on the fly, the image isn't copied.
+pub fn extract_data_with_unknown_alpha<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    rng_key: [u8; 32],
+    candidate_alphas: impl IntoIterator<Item = f64>,
+) -> Result<(f64, Vec<u8>), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let complexities = PlaneComplexities::new(source_image, ImageCoding::BinaryCode);
+
+    for min_alpha in candidate_alphas {
+        validate_min_alpha(min_alpha)?;
+        let mut selector =
+            PlaneSelector::new(source_image, min_alpha, rng_key).with_complexities(&complexities);
+
+        let Ok((layout, message_remnant_length)) =
+            read_embedding_layout(source_image, &mut selector, min_alpha)
+        else {
+            continue;
+        };
+        let Ok((conjugation_map, message_planes)) =
+            read_message_planes(source_image, &layout, min_alpha, selector.coding())
+        else {
+            continue;
+        };
+        if !message_planes_are_consistent(&layout.encoding, &conjugation_map, &message_planes) {
+            continue;
+        }
+
+        let data = decode_message_planes(
+            &layout.encoding,
+            conjugation_map,
+            message_planes,
+            message_remnant_length,
+        );
+        let data = match layout.stores_cover {
+            true => match split_reversible_container(data) {
+                Ok((data, _)) => data,
+                Err(_) => continue,
+            },
+            false => data,
+        };
+
+        return Ok((min_alpha, data));
+    }
+
+    Err(SteganographyError::InvalidIVData(String::from(
+        "None of the candidate min_alpha values could extract a payload with the given key",
+    ))
+    .into())
+}
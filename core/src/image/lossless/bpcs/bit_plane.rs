@@ -1,48 +1,123 @@
-use crate::utils::bit_operations_utils::{bits_to_u8, get_bit_from_u8};
-use image::{GenericImageView, Rgb, RgbImage, SubImage};
-
-pub(crate) const PLANE_SIZE: u32 = 8;
+use crate::{
+    errors::PixelveilError,
+    utils::{
+        bit_operations_utils::{bits_to_u8, get_bit_from_unsigned_int, set_bit_in_unsigned_int},
+        image_utils::BitPlaneSubpixel,
+    },
+};
+use image::{GenericImageView, ImageBuffer, Pixel, SubImage};
+use std::ops::{Deref, DerefMut};
+
+/// The width and height of a bit plane, in pixels
+pub const PLANE_SIZE: u32 = 8;
 pub(crate) const USIZE_PLANE_SIZE: usize = PLANE_SIZE as usize;
 pub(crate) const BYTES_PER_PLANE: usize = (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) / 8;
+/// How the values of an image that planes are read from are stored
+///
+/// Planes are always read as Gray Code, so the planes of a binary coded image are converted on the fly, without
+/// converting the whole image first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageCoding {
+    /// The image was already converted to Gray Code, e.g. with
+    /// [image_to_gray_code](crate::image_utils::image_to_gray_code).
+    GrayCode,
+    /// The image is stored as is, which is how every decoded image is stored.
+    BinaryCode,
+}
+
+// rejects channels and bit indices that the pixel type of an image doesn't have
+pub(crate) fn check_channel_and_bit_index<P>(
+    channel: u8,
+    bit_index: u8,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+{
+    if channel >= P::CHANNEL_COUNT {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "channel {channel} is out of bounds for a pixel with {} channels",
+            P::CHANNEL_COUNT
+        )));
+    }
+    check_bit_index::<P>(bit_index)
+}
+
+// rejects bit indices that the subpixel type of an image doesn't have
+pub(crate) fn check_bit_index<P>(bit_index: u8) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+{
+    if bit_index >= P::Subpixel::BIT_DEPTH {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "bit index {bit_index} is out of bounds for a subpixel with {} bits",
+            P::Subpixel::BIT_DEPTH
+        )));
+    }
+    Ok(())
+}
+
 const MAX_BIT_CHANGES: usize =
     ((USIZE_PLANE_SIZE - 1) * USIZE_PLANE_SIZE) + ((USIZE_PLANE_SIZE - 1) * USIZE_PLANE_SIZE);
 
 pub(crate) fn checkerboard() -> [[bool; USIZE_PLANE_SIZE]; USIZE_PLANE_SIZE] {
     let mut board = [[false; USIZE_PLANE_SIZE]; USIZE_PLANE_SIZE];
 
-    for y in 0..USIZE_PLANE_SIZE {
-        for x in 0..USIZE_PLANE_SIZE {
-            board[y][x] = (x + y) % 2 != 0;
+    for (y, row) in board.iter_mut().enumerate() {
+        for (x, square) in row.iter_mut().enumerate() {
+            *square = (x + y) % 2 != 0;
         }
     }
 
     board
 }
 
-pub(crate) fn get_planes_from_image_and_coords(
-    source_image: &RgbImage,
+pub(crate) fn get_planes_from_image_and_coords<P, C>(
+    source_image: &ImageBuffer<P, C>,
     coords: Vec<(u32, u32, u8, u8)>,
-) -> Vec<BitPlane> {
+) -> Vec<BitPlane>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    read_planes_from_image_and_coords(source_image, coords, ImageCoding::GrayCode)
+}
+
+pub(crate) fn read_planes_from_image_and_coords<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    coords: Vec<(u32, u32, u8, u8)>,
+    coding: ImageCoding,
+) -> Vec<BitPlane>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
     let mut planes: Vec<BitPlane> = Vec::with_capacity(coords.len());
-    let mut sub_image = source_image.view(0, 0, PLANE_SIZE, PLANE_SIZE);
+    // the view is resized to every plane before it is read, it starts at the size of the image if the image is
+    //  smaller than a plane (in which case there are no coords)
+    let (width, height) = source_image.dimensions();
+    let mut sub_image = source_image.view(0, 0, width.min(PLANE_SIZE), height.min(PLANE_SIZE));
     for (x, y, channel, bit_index) in coords {
         sub_image.change_bounds(x, y, PLANE_SIZE, PLANE_SIZE);
-        planes.push(BitPlane::from_sub_image(sub_image, channel, bit_index));
+        planes.push(BitPlane::read_from_sub_image(
+            sub_image, channel, bit_index, coding,
+        ));
     }
     planes
 }
 
-pub(crate) fn write_plane_at(image: &mut RgbImage, plane: BitPlane, coords: (u32, u32, u8, u8)) {
+pub(crate) fn write_plane_at<P, C>(
+    image: &mut ImageBuffer<P, C>,
+    plane: BitPlane,
+    coords: (u32, u32, u8, u8),
+) where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
     let sub_image = image.view(coords.0, coords.1, PLANE_SIZE, PLANE_SIZE);
-    let mut pixels: Vec<(u32, u32, Rgb<u8>)> =
-        Vec::with_capacity(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE);
+    let mut pixels: Vec<(u32, u32, P)> = Vec::with_capacity(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE);
     for (x, y, mut p) in sub_image.pixels() {
-        let mask = (1 << (7 - coords.3)) as u8;
-        if plane.bits[x as usize][y as usize] {
-            p.0[coords.2 as usize] |= mask;
-        } else {
-            p.0[coords.2 as usize] &= !mask;
-        }
+        let channel = &mut p.channels_mut()[coords.2 as usize];
+        *channel = set_bit_in_unsigned_int(*channel, coords.3, plane.bits[x as usize][y as usize]);
         pixels.push((x, y, p));
     }
     for (x, y, p) in pixels {
@@ -50,85 +125,184 @@ pub(crate) fn write_plane_at(image: &mut RgbImage, plane: BitPlane, coords: (u32
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct BitPlane {
+/// An 8x8 block of bits, taken from a single bit index of a single channel of an image
+///
+/// The bits are addressed by `(x, y)`, relative to the top left corner of the block.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BitPlane {
     pub(crate) bits: [[bool; USIZE_PLANE_SIZE]; USIZE_PLANE_SIZE],
 }
 
 impl BitPlane {
-    pub(crate) fn new() -> Self {
+    /// Creates a plane whose bits are all unset.
+    pub fn new() -> Self {
         BitPlane {
             bits: [[false; USIZE_PLANE_SIZE]; USIZE_PLANE_SIZE],
         }
     }
 
-    pub(crate) fn from_sub_image(
-        sub_image: SubImage<&RgbImage>,
+    pub(crate) fn from_sub_image<P, C>(
+        sub_image: SubImage<&ImageBuffer<P, C>>,
         channel: u8,
         bit_index: u8,
-    ) -> Self {
+    ) -> Self
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
         assert!(
             sub_image.width() == PLANE_SIZE && sub_image.height() == PLANE_SIZE,
             "Supplied SubImage incorrect dimensions to block of dimensions {PLANE_SIZE},{PLANE_SIZE}."
         );
         let mut p = BitPlane::new();
         for (x, y, pixel) in sub_image.pixels() {
-            p.set_bit(
-                (x as usize, y as usize),
-                get_bit_from_u8(pixel.0[channel as usize], bit_index),
-            );
+            p.bits[x as usize][y as usize] =
+                get_bit_from_unsigned_int(pixel.channels()[channel as usize], bit_index);
+        }
+        p
+    }
+
+    // reads a Gray Code plane from a sub image that is stored in the given coding
+    pub(crate) fn read_from_sub_image<P, C>(
+        sub_image: SubImage<&ImageBuffer<P, C>>,
+        channel: u8,
+        bit_index: u8,
+        coding: ImageCoding,
+    ) -> Self
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        match coding {
+            ImageCoding::GrayCode => BitPlane::from_sub_image(sub_image, channel, bit_index),
+            ImageCoding::BinaryCode => {
+                BitPlane::from_binary_coded_sub_image(sub_image, channel, bit_index)
+            }
+        }
+    }
+
+    pub(crate) fn from_binary_coded_sub_image<P, C>(
+        sub_image: SubImage<&ImageBuffer<P, C>>,
+        channel: u8,
+        bit_index: u8,
+    ) -> Self
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        assert!(
+            sub_image.width() == PLANE_SIZE && sub_image.height() == PLANE_SIZE,
+            "Supplied SubImage incorrect dimensions to block of dimensions {PLANE_SIZE},{PLANE_SIZE}."
+        );
+        let mut p = BitPlane::new();
+        for (x, y, pixel) in sub_image.pixels() {
+            let value = pixel.channels()[channel as usize];
+            // the Gray Code of a number is num ^ (num >> 1), so every Gray Code bit is the binary bit XORed with the
+            //  next more significant binary bit
+            let more_significant_bit =
+                bit_index > 0 && get_bit_from_unsigned_int(value, bit_index - 1);
+            p.bits[x as usize][y as usize] =
+                get_bit_from_unsigned_int(value, bit_index) ^ more_significant_bit;
         }
         p
     }
 
-    pub(crate) fn from_bits(bit_array: [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE]) -> Self {
+    /// Creates a plane from 64 bits. The bits are ordered by `x` and then by `y`, so the bit at `(x, y)` is
+    /// `bit_array[x * 8 + y]`. This is the same order [to_bits](BitPlane::to_bits) returns them in.
+    pub fn from_bits(bit_array: [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE]) -> Self {
         let mut p = BitPlane::new();
         for i in 0..USIZE_PLANE_SIZE {
             for j in 0..USIZE_PLANE_SIZE {
-                p.set_bit((i, j), bit_array[(i * USIZE_PLANE_SIZE) + j]);
+                p.bits[i][j] = bit_array[(i * USIZE_PLANE_SIZE) + j];
             }
         }
         p
     }
 
-    pub(crate) fn export_to_bools(self) -> [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE] {
-        let bits_flattened: [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE] = self
-            .bits
-            .into_iter()
-            .flatten()
-            .collect::<Vec<bool>>()
-            .try_into()
-            .unwrap();
+    /// Returns the 64 bits of the plane, in the order [from_bits](BitPlane::from_bits) takes them in.
+    pub fn to_bits(&self) -> [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE] {
+        let mut bits_flattened = [false; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE];
+        for (flattened, bit) in bits_flattened.iter_mut().zip(self.bits.iter().flatten()) {
+            *flattened = *bit;
+        }
         bits_flattened
     }
 
+    pub(crate) fn export_to_bools(self) -> [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE] {
+        self.to_bits()
+    }
+
     pub(crate) fn export_to_u8s(self) -> [u8; BYTES_PER_PLANE] {
         let bits_flattened: [bool; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE] = self.export_to_bools();
         let mut bytes = [0u8; BYTES_PER_PLANE];
-        for i in 0..BYTES_PER_PLANE {
-            bytes[i] = bits_to_u8(bits_flattened[i * 8..(i + 1) * 8].try_into().unwrap())
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = bits_to_u8(bits_flattened[i * 8..(i + 1) * 8].try_into().unwrap())
         }
         bytes
     }
 
-    pub(crate) fn set_bit(&mut self, coords: (usize, usize), val: bool) {
-        assert!(
-            coords.0 < USIZE_PLANE_SIZE && coords.1 < USIZE_PLANE_SIZE,
-            "Specified coords are out of bounds: coords: {coords:?}"
-        );
+    fn check_coords(coords: (usize, usize)) -> Result<(), PixelveilError> {
+        if coords.0 >= USIZE_PLANE_SIZE || coords.1 >= USIZE_PLANE_SIZE {
+            return Err(PixelveilError::InvalidParameter(format!(
+                "Specified coords are out of bounds: coords: {coords:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the bit at `(x, y)`.
+    ///
+    /// # Errors
+    /// Returns `PixelveilError::InvalidParameter` if `x` or `y` are 8 or more.
+    pub fn bit(&self, coords: (usize, usize)) -> Result<bool, PixelveilError> {
+        BitPlane::check_coords(coords)?;
+        Ok(self.bits[coords.0][coords.1])
+    }
+
+    /// Sets the bit at `(x, y)`.
+    ///
+    /// # Errors
+    /// Returns `PixelveilError::InvalidParameter` if `x` or `y` are 8 or more, the plane is left unchanged.
+    pub fn set_bit(&mut self, coords: (usize, usize), val: bool) -> Result<(), PixelveilError> {
+        BitPlane::check_coords(coords)?;
         self.bits[coords.0][coords.1] = val;
+        Ok(())
     }
 
-    pub(crate) fn conjugate(&mut self) {
+    /// Conjugates the plane, by XORing it with a checkerboard pattern. A plane with a complexity of `alpha` has a
+    /// complexity of `1 - alpha` after it is conjugated, and conjugating it twice restores it.
+    pub fn conjugate(&mut self) {
         let checkerboard = checkerboard();
-        for x in 0..USIZE_PLANE_SIZE {
-            for y in 0..USIZE_PLANE_SIZE {
-                self.bits[x][y] ^= checkerboard[x][y];
+        for (row, checkerboard_row) in self.bits.iter_mut().zip(checkerboard) {
+            for (bit, square) in row.iter_mut().zip(checkerboard_row) {
+                *bit ^= square;
             }
         }
     }
 
-    pub(crate) fn alpha(&self) -> f64 {
+    // XORs the plane with another plane, bit by bit
+    pub(crate) fn xor(&mut self, other: &BitPlane) {
+        for (row, other_row) in self.bits.iter_mut().zip(other.bits) {
+            for (bit, other_bit) in row.iter_mut().zip(other_row) {
+                *bit ^= other_bit;
+            }
+        }
+    }
+
+    // the number of bits that differ between the planes
+    pub(crate) fn hamming_distance(&self, other: &BitPlane) -> usize {
+        self.bits
+            .iter()
+            .flatten()
+            .zip(other.bits.iter().flatten())
+            .filter(|(bit, other_bit)| bit != other_bit)
+            .count()
+    }
+
+    /// Returns the complexity of the plane, the number of adjacent bits that differ divided by the maximal number of
+    /// such changes. The complexity ranges from 0.0 (a plane of a single color) to 1.0 (a checkerboard), and a plane
+    /// is used by BPCS if its complexity is at least `min_alpha`.
+    pub fn alpha(&self) -> f64 {
         let mut changes: usize = 0;
         for x in 1..USIZE_PLANE_SIZE {
             for y in 0..USIZE_PLANE_SIZE {
@@ -151,7 +325,10 @@ impl BitPlane {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::image::lossless::bpcs::dynamic_prefix::get_n_random_bools;
+    use crate::{
+        image::lossless::bpcs::dynamic_prefix::get_n_random_bools,
+        utils::image_utils::image_to_gray_code,
+    };
     use image::{GenericImageView, open};
 
     #[test]
@@ -163,7 +340,7 @@ mod tests {
     #[test]
     fn test_set_bit() {
         let mut b = BitPlane::new();
-        b.set_bit((0, 0), true);
+        b.set_bit((0, 0), true).unwrap();
         assert_eq!(
             b.bits,
             [
@@ -180,10 +357,18 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Specified coords are out of bounds: coords: (6, 9)")]
     fn test_set_out_of_bounds() {
         let mut b = BitPlane::new();
-        b.set_bit((6, 9), false);
+        assert!(matches!(
+            b.set_bit((6, 9), true),
+            Err(PixelveilError::InvalidParameter(reason))
+                if reason == "Specified coords are out of bounds: coords: (6, 9)"
+        ));
+        assert!(matches!(
+            b.bit((8, 0)),
+            Err(PixelveilError::InvalidParameter(_))
+        ));
+        assert_eq!(b, BitPlane::new());
     }
 
     #[test]
@@ -192,7 +377,7 @@ mod tests {
         expected[0][0] = true;
 
         let mut p = BitPlane::new();
-        p.set_bit((0, 0), true);
+        p.set_bit((0, 0), true).unwrap();
 
         p.conjugate();
 
@@ -226,6 +411,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_from_binary_coded_sub_image() -> Result<(), Box<dyn std::error::Error>> {
+        let img = image::open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+        let mut gray_coded_img = img.clone();
+        image_to_gray_code(&mut gray_coded_img);
+
+        for (channel, bit_index) in iproduct!(0..3u8, 0..8u8) {
+            let on_the_fly = BitPlane::from_binary_coded_sub_image(
+                img.view(8, 16, PLANE_SIZE, PLANE_SIZE),
+                channel,
+                bit_index,
+            );
+            let converted = BitPlane::from_sub_image(
+                gray_coded_img.view(8, 16, PLANE_SIZE, PLANE_SIZE),
+                channel,
+                bit_index,
+            );
+            assert_eq!(on_the_fly.bits, converted.bits);
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_from_bits() {
         let mut bits = [false; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE];
@@ -264,15 +471,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_write_plane_at_16_bit() {
+        let mut source_image =
+            ImageBuffer::<image::Luma<u16>, Vec<u16>>::new(PLANE_SIZE, PLANE_SIZE);
+        let plane = BitPlane::from_bits(
+            get_n_random_bools(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE, &mut rand::rng())
+                .try_into()
+                .unwrap(),
+        );
+        let expected_bits = plane.bits;
+        write_plane_at(&mut source_image, plane, (0, 0, 0, 13));
+        let new_bits =
+            BitPlane::from_sub_image(source_image.view(0, 0, PLANE_SIZE, PLANE_SIZE), 0, 13).bits;
+        assert_eq!(expected_bits, new_bits);
+        for pixel in source_image.pixels() {
+            assert_eq!(pixel.0[0] & !0b100, 0);
+        }
+    }
+
     #[test]
     fn test_write_plane_at() -> Result<(), Box<dyn std::error::Error>> {
         let mut source_image = open("tests/assets/test_write_plane_at.png")?.to_rgb8();
         let plane = BitPlane::from_bits(
-            get_n_random_bools(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE)
+            get_n_random_bools(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE, &mut rand::rng())
                 .try_into()
                 .unwrap(),
         );
-        let expected_bits = plane.bits.clone();
+        let expected_bits = plane.bits;
         write_plane_at(&mut source_image, plane, (0, 0, 1, 3));
         let new_bits =
             BitPlane::from_sub_image(source_image.view(0, 0, PLANE_SIZE, PLANE_SIZE), 1, 3).bits;
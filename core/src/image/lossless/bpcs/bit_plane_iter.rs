@@ -1,27 +1,57 @@
-use crate::image::lossless::bpcs::bit_plane::{BitPlane, PLANE_SIZE};
-use image::{GenericImageView, RgbImage, SubImage};
+use crate::{
+    image::lossless::bpcs::bit_plane::{BitPlane, ImageCoding, PLANE_SIZE},
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{GenericImageView, ImageBuffer, Pixel, SubImage};
+use std::ops::Deref;
 
-pub(crate) struct BitIndexedBitPlaneIter<'a> {
-    pub(crate) current_sub_image: SubImage<&'a RgbImage>,
-    pub(crate) cursor: Box<dyn Iterator<Item = (u32, u32, u8)>>, // plane_x_index, plane_y_index, channel, bit_index
+pub(crate) struct BitIndexedBitPlaneIter<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    pub(crate) current_sub_image: SubImage<&'a ImageBuffer<P, C>>,
+    pub(crate) cursor: Box<dyn Iterator<Item = (u32, u32, u8)>>, // plane_x_index, plane_y_index, channel
     pub(crate) bit_index: u8,
+    pub(crate) coding: ImageCoding,
 }
 
-impl<'a> BitIndexedBitPlaneIter<'a> {
-    pub(crate) fn new(source_image: &'a RgbImage, bit_index: u8) -> Self {
+impl<'a, P, C> BitIndexedBitPlaneIter<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    pub(crate) fn with_coding(
+        source_image: &'a ImageBuffer<P, C>,
+        bit_index: u8,
+        coding: ImageCoding,
+    ) -> Self {
+        // an image that is narrower or shorter than a plane has no planes, the cursor is empty and the view (which
+        //  can't be larger than the image) is never read
+        let (width, height) = source_image.dimensions();
         BitIndexedBitPlaneIter {
-            current_sub_image: source_image.view(0, 0, PLANE_SIZE, PLANE_SIZE),
+            current_sub_image: source_image.view(
+                0,
+                0,
+                width.min(PLANE_SIZE),
+                height.min(PLANE_SIZE),
+            ),
             cursor: Box::new(iproduct!(
                 0..source_image.width() / PLANE_SIZE,
                 0..source_image.height() / PLANE_SIZE,
-                0..3u8,
+                0..P::CHANNEL_COUNT,
             )),
             bit_index,
+            coding,
         }
     }
 }
 
-impl<'a> Iterator for BitIndexedBitPlaneIter<'a> {
+impl<P, C> Iterator for BitIndexedBitPlaneIter<'_, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
     type Item = ((u32, u32, u8, u8), BitPlane);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -29,7 +59,12 @@ impl<'a> Iterator for BitIndexedBitPlaneIter<'a> {
         let (x_coord, y_coord) = (plane_x * PLANE_SIZE, plane_y * PLANE_SIZE);
         self.current_sub_image
             .change_bounds(x_coord, y_coord, PLANE_SIZE, PLANE_SIZE);
-        let p = BitPlane::from_sub_image(self.current_sub_image, channel, self.bit_index);
+        let p = BitPlane::read_from_sub_image(
+            self.current_sub_image,
+            channel,
+            self.bit_index,
+            self.coding,
+        );
         Some(((x_coord, y_coord, channel, self.bit_index), p))
     }
 }
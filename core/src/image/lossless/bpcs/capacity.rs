@@ -1,12 +1,14 @@
-#![allow(dead_code)] // this module might be useful later, so I'm keeping it in for now.
-
 use crate::{
-    errors::SteganographyError,
     image::lossless::bpcs::{
+        bit_plane::{BYTES_PER_PLANE, ImageCoding},
         dynamic_prefix::{num_of_prefixed_planes_for_n_bits, prefix_length},
         initialization_vector::calculate_iv_plane_number,
+        plane_selection::count_accepted_planes_with_coding,
     },
+    utils::image_utils::BitPlaneSubpixel,
 };
+use image::{ImageBuffer, Pixel};
+use std::ops::Deref;
 
 pub(crate) fn calculate_num_of_embedding_planes(
     min_alpha: f64,
@@ -19,19 +21,53 @@ pub(crate) fn calculate_num_of_embedding_planes(
     iv_plane_num + conj_map_plane_num + message_plane_length
 }
 
-pub(crate) fn check_capacity(
-    min_alpha: f64,
-    message_plane_length: usize,
-    accepted_planes_num: usize,
-) -> Result<(), SteganographyError> {
-    let required_min_accepted_num =
-        calculate_num_of_embedding_planes(min_alpha, message_plane_length);
-    if required_min_accepted_num <= accepted_planes_num {
-        Ok(())
-    } else {
-        Err(SteganographyError::InsufficientPlaneNumber(
-            required_min_accepted_num,
-            accepted_planes_num,
-        ))
+// the largest number of message planes that fit into the accepted planes together with their IV and conjugation map
+pub(crate) fn max_message_plane_length(min_alpha: f64, accepted_planes_num: usize) -> usize {
+    if calculate_num_of_embedding_planes(min_alpha, 0) > accepted_planes_num {
+        return 0;
+    }
+
+    // the number of planes that are needed only grows with the message plane length, so it can be binary searched
+    let (mut fitting, mut too_long) = (0usize, accepted_planes_num + 1);
+    while too_long - fitting > 1 {
+        let middle = fitting + (too_long - fitting) / 2;
+        if calculate_num_of_embedding_planes(min_alpha, middle) <= accepted_planes_num {
+            fitting = middle;
+        } else {
+            too_long = middle;
+        }
+    }
+    fitting
+}
+
+// The exact number of payload bytes a binary coded image can hold. The accepted planes are counted in Gray Code, like
+//  the embedder selects them, and embedding never changes which planes are accepted.
+pub(crate) fn image_data_capacity<P, C>(source_image: &ImageBuffer<P, C>, min_alpha: f64) -> usize
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let accepted_planes_num =
+        count_accepted_planes_with_coding(source_image, min_alpha, ImageCoding::BinaryCode)
+            as usize;
+    max_message_plane_length(min_alpha, accepted_planes_num) * BYTES_PER_PLANE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_message_plane_length() {
+        let min_alpha = 0.3;
+        for accepted_planes_num in [0usize, 5, 40, 1_000, 123_457] {
+            let length = max_message_plane_length(min_alpha, accepted_planes_num);
+            if length > 0 {
+                assert!(
+                    calculate_num_of_embedding_planes(min_alpha, length) <= accepted_planes_num
+                );
+            }
+            assert!(calculate_num_of_embedding_planes(min_alpha, length + 1) > accepted_planes_num);
+        }
     }
 }
@@ -0,0 +1,529 @@
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{
+        bit_plane::PLANE_SIZE,
+        deterministic::deterministic_filler_rng,
+        dynamic_prefix::validate_min_alpha,
+        embed_layout_verified, extract_data_with_selector,
+        initialization_vector::calculate_message_plane_length_and_remnant,
+        plane_encoding::BlockEncoding,
+        plane_selection::{EmbeddingLayout, PlaneCoords, PlaneSelector},
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::{RngCore, rng};
+use std::ops::{Deref, DerefMut};
+
+const DEFAULT_MIN_ALPHA: f64 = 0.3;
+
+/// Which channels of an image BPCS may change
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelPolicy {
+    /// Every channel may be changed.
+    #[default]
+    All,
+    /// Only the listed channels may be changed (e.g. R,G,B = 0,1,2).
+    Only(Vec<u8>),
+}
+
+/// Which blocks of an image BPCS may change
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockPolicy {
+    /// Every block may be changed.
+    #[default]
+    All,
+    /// Only the blocks that lie entirely inside the region may be changed.
+    Region {
+        /// The x coordinate of the top left pixel of the region.
+        x: u32,
+        /// The y coordinate of the top left pixel of the region.
+        y: u32,
+        /// The width of the region, in pixels.
+        width: u32,
+        /// The height of the region, in pixels.
+        height: u32,
+    },
+}
+
+/// A validated set of BPCS parameters, used by [embed_data_with_config] and [extract_data_with_config]
+///
+/// A configuration can only be created through [BpcsConfigBuilder], which checks every value. With the `serde` feature
+/// enabled it can be serialized and deserialized, and deserializing it runs the same checks.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{BpcsConfig, ChannelPolicy};
+///
+/// let config = BpcsConfig::builder()
+///     .min_alpha(0.35)
+///     .rng_key([7u8; 32])
+///     .channels(ChannelPolicy::Only(vec![1, 2]))
+///     .verify(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "BpcsConfigBuilder", into = "BpcsConfigBuilder")
+)]
+pub struct BpcsConfig {
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    channels: ChannelPolicy,
+    blocks: BlockPolicy,
+    block_encoding: BlockEncoding,
+    deterministic: bool,
+    verify: bool,
+}
+
+/// Builds a [BpcsConfig]
+///
+/// Every value except the key has a default, see the methods for the defaults. With the `serde` feature enabled the
+/// builder is what a configuration is stored as, every field is optional and missing fields take their defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct BpcsConfigBuilder {
+    min_alpha: Option<f64>,
+    rng_key: Option<[u8; 32]>,
+    channels: ChannelPolicy,
+    blocks: BlockPolicy,
+    block_encoding: BlockEncoding,
+    deterministic: bool,
+    verify: bool,
+}
+
+impl BpcsConfig {
+    /// Creates a builder for a configuration.
+    pub fn builder() -> BpcsConfigBuilder {
+        BpcsConfigBuilder::default()
+    }
+
+    /// The BPCS minimum complexity coefficient.
+    pub fn min_alpha(&self) -> f64 {
+        self.min_alpha
+    }
+
+    /// The randomization key, used for pseudo-random selection of where to change the source image.
+    pub fn rng_key(&self) -> [u8; 32] {
+        self.rng_key
+    }
+
+    /// The channels that may be changed.
+    pub fn channels(&self) -> &ChannelPolicy {
+        &self.channels
+    }
+
+    /// The blocks that may be changed.
+    pub fn blocks(&self) -> &BlockPolicy {
+        &self.blocks
+    }
+
+    /// How the message planes are encoded.
+    pub fn block_encoding(&self) -> BlockEncoding {
+        self.block_encoding
+    }
+
+    /// Whether the filler bits are derived from the inputs, like
+    /// [embed_data_deterministic](crate::bpcs::embed_data_deterministic) does.
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Whether every embedding is extracted back and checked, like
+    /// [embed_data_and_verify](crate::bpcs::embed_data_and_verify) does.
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    // the channel policy can only be checked against the pixel type of the image it is used with
+    fn check_image<P>(&self) -> Result<(), PixelveilError>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+    {
+        if let ChannelPolicy::Only(channels) = &self.channels
+            && let Some(channel) = channels
+                .iter()
+                .find(|channel| **channel >= P::CHANNEL_COUNT)
+        {
+            return Err(PixelveilError::InvalidParameter(format!(
+                "channel {channel} is out of bounds for a pixel with {} channels",
+                P::CHANNEL_COUNT
+            )));
+        }
+        Ok(())
+    }
+
+    fn allows_plane(&self, coords: &PlaneCoords) -> bool {
+        let (plane_x, plane_y, channel, _) = *coords;
+        let channel_allowed = match &self.channels {
+            ChannelPolicy::All => true,
+            ChannelPolicy::Only(channels) => channels.contains(&channel),
+        };
+        let block_allowed = match self.blocks {
+            BlockPolicy::All => true,
+            BlockPolicy::Region {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                plane_x >= x
+                    && plane_y >= y
+                    && (plane_x - x) as u64 + PLANE_SIZE as u64 <= width as u64
+                    && (plane_y - y) as u64 + PLANE_SIZE as u64 <= height as u64
+            }
+        };
+        channel_allowed && block_allowed
+    }
+
+    fn selector<'a, P, C>(&'a self, source_image: &'a ImageBuffer<P, C>) -> PlaneSelector<'a, P, C>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        PlaneSelector::new(source_image, self.min_alpha, self.rng_key)
+            .with_plane_filter(|coords| self.allows_plane(coords))
+    }
+}
+
+impl BpcsConfigBuilder {
+    /// Sets the BPCS minimum complexity coefficient, it must be between 0.0 and 0.5. Defaults to 0.3.
+    pub fn min_alpha(self, min_alpha: f64) -> Self {
+        BpcsConfigBuilder {
+            min_alpha: Some(min_alpha),
+            ..self
+        }
+    }
+
+    /// Sets the randomization key. There is no default, a key must be set.
+    pub fn rng_key(self, rng_key: [u8; 32]) -> Self {
+        BpcsConfigBuilder {
+            rng_key: Some(rng_key),
+            ..self
+        }
+    }
+
+    /// Sets the channels that may be changed. Defaults to [ChannelPolicy::All].
+    pub fn channels(self, channels: ChannelPolicy) -> Self {
+        BpcsConfigBuilder { channels, ..self }
+    }
+
+    /// Sets the blocks that may be changed. Defaults to [BlockPolicy::All].
+    pub fn blocks(self, blocks: BlockPolicy) -> Self {
+        BpcsConfigBuilder { blocks, ..self }
+    }
+
+    /// Sets how the message planes are encoded, see [BlockEncoding]. Defaults to [BlockEncoding::Conjugation].
+    pub fn block_encoding(self, block_encoding: BlockEncoding) -> Self {
+        BpcsConfigBuilder {
+            block_encoding,
+            ..self
+        }
+    }
+
+    /// Sets whether the filler bits are derived from the inputs, so the same inputs always produce the same image.
+    /// Defaults to `false`.
+    pub fn deterministic(self, deterministic: bool) -> Self {
+        BpcsConfigBuilder {
+            deterministic,
+            ..self
+        }
+    }
+
+    /// Sets whether every embedding is extracted back and checked. Defaults to `false`.
+    pub fn verify(self, verify: bool) -> Self {
+        BpcsConfigBuilder { verify, ..self }
+    }
+
+    /// Validates the values and builds the configuration
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5, if no key was set, if the
+    ///   channel policy lists no channels or the same channel twice, or if the block policy's region can't hold a
+    ///   single block.
+    ///
+    /// # Returns
+    /// Returns `Result<BpcsConfig, PixelveilError>`. If `Ok(...)` is returned, the contained value is the validated
+    /// configuration.
+    ///
+    /// # Notes
+    /// The channels are checked against the pixel type when the configuration is used, since the builder doesn't know
+    /// it.
+    pub fn build(self) -> Result<BpcsConfig, PixelveilError> {
+        let min_alpha = self.min_alpha.unwrap_or(DEFAULT_MIN_ALPHA);
+        validate_min_alpha(min_alpha)?;
+
+        let Some(rng_key) = self.rng_key else {
+            return Err(PixelveilError::InvalidParameter(
+                "an rng_key must be set".to_string(),
+            ));
+        };
+
+        if let ChannelPolicy::Only(channels) = &self.channels {
+            if channels.is_empty() {
+                return Err(PixelveilError::InvalidParameter(
+                    "the channel policy doesn't allow any channel".to_string(),
+                ));
+            }
+            if channels
+                .iter()
+                .enumerate()
+                .any(|(i, channel)| channels[..i].contains(channel))
+            {
+                return Err(PixelveilError::InvalidParameter(format!(
+                    "the channel policy lists a channel more than once: {channels:?}"
+                )));
+            }
+        }
+
+        if let BlockPolicy::Region { width, height, .. } = self.blocks
+            && (width < PLANE_SIZE || height < PLANE_SIZE)
+        {
+            return Err(PixelveilError::InvalidParameter(format!(
+                "the block policy's region of {width}x{height} pixels can't hold a single {PLANE_SIZE}x{PLANE_SIZE} block"
+            )));
+        }
+
+        Ok(BpcsConfig {
+            min_alpha,
+            rng_key,
+            channels: self.channels,
+            blocks: self.blocks,
+            block_encoding: self.block_encoding,
+            deterministic: self.deterministic,
+            verify: self.verify,
+        })
+    }
+}
+
+impl TryFrom<BpcsConfigBuilder> for BpcsConfig {
+    type Error = PixelveilError;
+
+    fn try_from(builder: BpcsConfigBuilder) -> Result<Self, Self::Error> {
+        builder.build()
+    }
+}
+
+impl From<BpcsConfig> for BpcsConfigBuilder {
+    fn from(config: BpcsConfig) -> Self {
+        BpcsConfigBuilder {
+            min_alpha: Some(config.min_alpha),
+            rng_key: Some(config.rng_key),
+            channels: config.channels,
+            blocks: config.blocks,
+            block_encoding: config.block_encoding,
+            deterministic: config.deterministic,
+            verify: config.verify,
+        }
+    }
+}
+
+/// Embed data into an image using BPCS, with the parameters of a [BpcsConfig]
+///
+/// Only planes that the configuration's channel and block policies allow are changed, and the message planes are
+/// encoded with the configuration's block encoding. If the configuration is deterministic the filler bits are derived
+/// from the inputs, and if it verifies, the data is extracted back and checked before returning.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{BpcsConfig, embed_data_with_config};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let config = BpcsConfig::builder().rng_key([7u8; 32]).build().unwrap();
+///
+/// embed_data_with_config(&mut vessel_image, b"configured", &config).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &[u8]` — The data to embed.
+/// * `config: &BpcsConfig` — The parameters of the embedding.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the channel policy lists a channel that the image doesn't have.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the allowed planes of the image can't store the data.
+/// * `SteganographyError::VerificationFailed` — If the configuration verifies, and the data couldn't be extracted back.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// The data must be extracted with [extract_data_with_config] and a configuration with the same key, `min_alpha`,
+/// channel policy and block policy. Data that was embedded with the default policies can also be extracted with
+/// [extract_data](crate::bpcs::extract_data).
+pub fn embed_data_with_config<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    config: &BpcsConfig,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    config.check_image::<P>()?;
+
+    image_to_gray_code(source_image);
+
+    let (message_plane_length, _) = calculate_message_plane_length_and_remnant(data.len());
+    let mut selector = config.selector(source_image);
+    let selected = EmbeddingLayout::select_with_encoding(
+        &mut selector,
+        config.min_alpha,
+        message_plane_length,
+        config.block_encoding,
+    );
+    drop(selector);
+    let layout = match selected {
+        Ok(layout) => layout,
+        Err(e) => {
+            image_to_binary_code(source_image);
+            return Err(e.into());
+        }
+    };
+
+    let mut filler_rng: Box<dyn RngCore> = match config.deterministic {
+        true => Box::new(deterministic_filler_rng(
+            config.rng_key,
+            config.min_alpha,
+            data,
+        )),
+        false => Box::new(rng()),
+    };
+    embed_layout_verified(
+        source_image,
+        &layout,
+        &mut data.iter().copied(),
+        data.len(),
+        config.min_alpha,
+        &mut filler_rng,
+        config
+            .verify
+            .then_some(|image: &ImageBuffer<P, C>| extract_data_with_config(image, config)),
+    )
+}
+
+/// Extract data from an image using BPCS, with the parameters of a [BpcsConfig]
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{BpcsConfig, extract_data_with_config};
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+/// let config = BpcsConfig::builder().rng_key([7u8; 32]).build().unwrap();
+///
+/// let extracted_data = extract_data_with_config(&vessel_image, &config).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in two arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `config: &BpcsConfig` — The parameters the data was embedded with.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the channel policy lists a channel that the image doesn't have.
+/// * `SteganographyError::InvalidIVData` — if the IV in the image contains invalid data. The most likely causes of this
+///   are trying to extract data from an image that doesn't have data hidden in it or a different configuration.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is a vector of the
+/// extracted data bytes.
+pub fn extract_data_with_config<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    config: &BpcsConfig,
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    config.check_image::<P>()?;
+
+    let selector = config.selector(source_image).reading_binary_coded_image();
+    Ok(extract_data_with_selector(
+        source_image,
+        selector,
+        config.min_alpha,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_validation() {
+        let builder = BpcsConfig::builder().rng_key([1u8; 32]);
+        assert_eq!(
+            builder.clone().build().unwrap().min_alpha(),
+            DEFAULT_MIN_ALPHA
+        );
+        assert!(builder.clone().min_alpha(0.0).build().is_ok());
+        assert!(builder.clone().min_alpha(0.5).build().is_ok());
+
+        for min_alpha in [-0.1, 0.51, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                builder.clone().min_alpha(min_alpha).build(),
+                Err(PixelveilError::InvalidParameter(_))
+            ));
+        }
+        assert!(BpcsConfig::builder().build().is_err());
+        assert!(
+            builder
+                .clone()
+                .channels(ChannelPolicy::Only(vec![]))
+                .build()
+                .is_err()
+        );
+        assert!(
+            builder
+                .clone()
+                .channels(ChannelPolicy::Only(vec![0, 2, 0]))
+                .build()
+                .is_err()
+        );
+        let thin_region = BlockPolicy::Region {
+            x: 0,
+            y: 0,
+            width: 7,
+            height: 64,
+        };
+        assert!(builder.clone().blocks(thin_region).build().is_err());
+    }
+
+    #[test]
+    fn test_allows_plane() {
+        let config = BpcsConfig::builder()
+            .rng_key([1u8; 32])
+            .channels(ChannelPolicy::Only(vec![2]))
+            .blocks(BlockPolicy::Region {
+                x: 8,
+                y: 8,
+                width: 20,
+                height: 16,
+            })
+            .build()
+            .unwrap();
+
+        assert!(config.allows_plane(&(8, 8, 2, 7)));
+        assert!(config.allows_plane(&(16, 16, 2, 0)));
+        assert!(!config.allows_plane(&(8, 8, 1, 7)));
+        assert!(!config.allows_plane(&(0, 8, 2, 7)));
+        assert!(!config.allows_plane(&(24, 8, 2, 7))); // doesn't fit in the region's width
+        assert!(!config.allows_plane(&(8, 24, 2, 7)));
+    }
+}
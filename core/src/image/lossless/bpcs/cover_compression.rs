@@ -0,0 +1,217 @@
+use crate::image::lossless::bpcs::bit_plane::{BitPlane, USIZE_PLANE_SIZE};
+
+// Lossless compression of bit planes, used to store the cover planes that a reversible embedding replaces. Every bit is
+//  coded with an adaptive binary range coder (the one LZMA uses), and its probability is predicted from the bits to
+//  its left and above it that were already coded.
+
+const PROBABILITY_BITS: u32 = 11;
+const PROBABILITY_ONE: u16 = 1 << PROBABILITY_BITS;
+const ADAPTATION_SHIFT: u16 = 5;
+const TOP_VALUE: u32 = 1 << 24;
+// the encoder flushes this many bytes, and the decoder reads as many before decoding the first bit
+const FLUSH_BYTES: usize = 5;
+
+// the context of a bit is its left, upper left, upper and upper right neighbours, and the bit two places to its left
+const CONTEXT_BITS: usize = 5;
+
+struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    output: Vec<u8>,
+}
+
+impl RangeEncoder {
+    fn new() -> Self {
+        RangeEncoder {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            output: Vec::new(),
+        }
+    }
+
+    fn encode(&mut self, bit: bool, probability: &mut u16) {
+        let bound = (self.range >> PROBABILITY_BITS) * (*probability as u32);
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION_SHIFT;
+        } else {
+            self.range = bound;
+            *probability += (PROBABILITY_ONE - *probability) >> ADAPTATION_SHIFT;
+        }
+        while self.range < TOP_VALUE {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    // moves the top byte of low to the output, a carry is propagated into the bytes that are still cached
+    fn shift_low(&mut self) {
+        if (self.low as u32) < 0xFF000000 || (self.low >> 32) != 0 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            loop {
+                self.output.push(byte.wrapping_add(carry));
+                byte = 0xFF;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = ((self.low as u32) << 8) as u64;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..FLUSH_BYTES {
+            self.shift_low();
+        }
+        self.output
+    }
+}
+
+struct RangeDecoder<'a> {
+    code: u32,
+    range: u32,
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        let mut decoder = RangeDecoder {
+            code: 0,
+            range: u32::MAX,
+            input,
+            position: 0,
+        };
+        for _ in 0..FLUSH_BYTES {
+            decoder.code = (decoder.code << 8) | decoder.next_byte() as u32;
+        }
+        decoder
+    }
+
+    // the decoder never reads past the bytes the encoder wrote, so missing bytes only occur in truncated input
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.input.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        byte
+    }
+
+    fn decode(&mut self, probability: &mut u16) -> bool {
+        let bound = (self.range >> PROBABILITY_BITS) * (*probability as u32);
+        let bit = if self.code < bound {
+            self.range = bound;
+            *probability += (PROBABILITY_ONE - *probability) >> ADAPTATION_SHIFT;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION_SHIFT;
+            true
+        };
+        while self.range < TOP_VALUE {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next_byte() as u32;
+        }
+        bit
+    }
+}
+
+fn context_of(plane: &BitPlane, x: usize, y: usize) -> usize {
+    let bit = |x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) if x < USIZE_PLANE_SIZE => plane.bits[x][y] as usize,
+        _ => 0,
+    };
+    bit(x.checked_sub(1), Some(y))
+        | bit(x.checked_sub(1), y.checked_sub(1)) << 1
+        | bit(Some(x), y.checked_sub(1)) << 2
+        | bit(Some(x + 1), y.checked_sub(1)) << 3
+        | bit(x.checked_sub(2), Some(y)) << 4
+}
+
+fn initial_probabilities() -> [u16; 1 << CONTEXT_BITS] {
+    [PROBABILITY_ONE / 2; 1 << CONTEXT_BITS]
+}
+
+pub(crate) fn compress_planes(planes: &[BitPlane]) -> Vec<u8> {
+    let mut probabilities = initial_probabilities();
+    let mut encoder = RangeEncoder::new();
+    for plane in planes {
+        for y in 0..USIZE_PLANE_SIZE {
+            for x in 0..USIZE_PLANE_SIZE {
+                let context = context_of(plane, x, y);
+                encoder.encode(plane.bits[x][y], &mut probabilities[context]);
+            }
+        }
+    }
+    encoder.finish()
+}
+
+// decodes plane_count planes from the start of compressed, the bytes after the compressed planes are never read
+pub(crate) fn decompress_planes(compressed: &[u8], plane_count: usize) -> Vec<BitPlane> {
+    let mut probabilities = initial_probabilities();
+    let mut decoder = RangeDecoder::new(compressed);
+    let mut planes: Vec<BitPlane> = Vec::with_capacity(plane_count);
+    for _ in 0..plane_count {
+        let mut plane = BitPlane::new();
+        for y in 0..USIZE_PLANE_SIZE {
+            for x in 0..USIZE_PLANE_SIZE {
+                let context = context_of(&plane, x, y);
+                plane.bits[x][y] = decoder.decode(&mut probabilities[context]);
+            }
+        }
+        planes.push(plane);
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::lossless::bpcs::message_plane_iter::random_message_plane;
+
+    #[test]
+    fn test_compression_is_lossless() {
+        let mut planes: Vec<BitPlane> = (0..200)
+            .map(|_| random_message_plane(&mut rand::rng()))
+            .collect();
+        planes.push(BitPlane::new());
+        planes.push(BitPlane::from_bits(
+            [true; USIZE_PLANE_SIZE * USIZE_PLANE_SIZE],
+        ));
+
+        let mut compressed = compress_planes(&planes);
+        assert_eq!(decompress_planes(&compressed, planes.len()), planes);
+
+        // the bytes after the compressed planes are never read
+        compressed.extend([0xAB; 16]);
+        assert_eq!(decompress_planes(&compressed, planes.len()), planes);
+
+        assert_eq!(decompress_planes(&compress_planes(&[]), 0), Vec::new());
+    }
+
+    #[test]
+    fn test_structured_planes_are_compressed() {
+        // complex planes whose bits follow from their neighbours, like the stripes and dithering of rendered images
+        let planes: Vec<BitPlane> = (0..500usize)
+            .map(|i| {
+                let mut plane = BitPlane::new();
+                for (x, y) in iproduct!(0..USIZE_PLANE_SIZE, 0..USIZE_PLANE_SIZE) {
+                    plane.bits[x][y] = ((x + i) ^ y) & 1 == 1 || (x + y + i) % 4 == 0;
+                }
+                plane
+            })
+            .collect();
+
+        let compressed = compress_planes(&planes);
+        assert!(compressed.len() * 3 < planes.len() * USIZE_PLANE_SIZE);
+        assert_eq!(decompress_planes(&compressed, planes.len()), planes);
+    }
+}
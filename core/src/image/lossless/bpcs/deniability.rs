@@ -0,0 +1,365 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        bit_plane::{ImageCoding, write_plane_at},
+        dynamic_prefix::validate_min_alpha,
+        extract_data_with_selector,
+        initialization_vector::calculate_message_plane_length_and_remnant,
+        message_plane_iter::random_message_plane,
+        plane_encoding::BlockEncoding,
+        plane_selection::{
+            EmbeddingLayout, PlaneCoords, PlaneSelector, collect_accepted_planes_at_bit_index,
+        },
+        read_embedding_layout, select_embedding_layout, write_data_into_gray_coded_image,
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::{Rng, rng, seq::SliceRandom};
+use std::{
+    collections::HashSet,
+    ops::{Deref, DerefMut},
+};
+
+fn hidden_selector<'a, P, C>(
+    source_image: &'a ImageBuffer<P, C>,
+    min_alpha: f64,
+    hidden_rng_key: [u8; 32],
+    decoy_layout: &EmbeddingLayout,
+) -> PlaneSelector<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let decoy_planes: HashSet<PlaneCoords> = decoy_layout.all_plane_coords().copied().collect();
+    // the header is masked so its planes look like the message and filler planes, an unmasked header is the only part
+    //  of the hidden payload that the complexity of its planes would give away
+    PlaneSelector::new(source_image, min_alpha, hidden_rng_key)
+        .with_plane_filter(move |coords| !decoy_planes.contains(coords))
+        .with_masked_header()
+}
+
+// selects the decoy and hidden layouts, and every other accepted plane in the bit indexes they use
+fn select_deniable_layouts<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    decoy_data_length: usize,
+    decoy_rng_key: [u8; 32],
+    hidden_data_length: usize,
+    hidden_rng_key: [u8; 32],
+    min_alpha: f64,
+) -> Result<(EmbeddingLayout, EmbeddingLayout, Vec<PlaneCoords>), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    // the decoy is selected exactly like a regular embedding, so it can be extracted with extract_data
+    let decoy_layout = select_embedding_layout(
+        source_image,
+        decoy_data_length,
+        min_alpha,
+        decoy_rng_key,
+        BlockEncoding::Conjugation,
+    )?;
+
+    let (hidden_message_plane_length, _) =
+        calculate_message_plane_length_and_remnant(hidden_data_length);
+    let mut selector = hidden_selector(source_image, min_alpha, hidden_rng_key, &decoy_layout);
+    let hidden_layout =
+        EmbeddingLayout::select(&mut selector, min_alpha, hidden_message_plane_length)?;
+
+    let used_planes: HashSet<&PlaneCoords> = decoy_layout
+        .all_plane_coords()
+        .chain(hidden_layout.all_plane_coords())
+        .collect();
+
+    // planes are selected from the least significant bit index up, so every bit index from the most significant used
+    //  one down is (partially) used
+    let most_significant_bit_index = used_planes
+        .iter()
+        .map(|(_, _, _, bit_index)| *bit_index)
+        .min()
+        .unwrap_or(P::Subpixel::BIT_DEPTH);
+
+    let mut unused_plane_coords: Vec<PlaneCoords> = Vec::new();
+    for bit_index in most_significant_bit_index..P::Subpixel::BIT_DEPTH {
+        unused_plane_coords.extend(
+            collect_accepted_planes_at_bit_index(
+                source_image,
+                min_alpha,
+                bit_index,
+                ImageCoding::GrayCode,
+            )
+            .into_iter()
+            .filter(|coords| !used_planes.contains(coords)),
+        );
+    }
+
+    Ok((decoy_layout, hidden_layout, unused_plane_coords))
+}
+
+// Chooses the unused planes that are filled with random data. The payloads use every accepted plane below their most
+//  significant bit index, so the unused planes are all in that bit index, and filling all of them would leave it
+//  fully used, which a regular embedding never does. A random share of them is filled instead, so the used planes end
+//  where those of a regular embedding of an unknown length would.
+fn choose_filler_planes(
+    mut unused_plane_coords: Vec<PlaneCoords>,
+    rng: &mut impl Rng,
+) -> Vec<PlaneCoords> {
+    let filler_plane_number = rng.random_range(0..=unused_plane_coords.len());
+    unused_plane_coords.shuffle(rng);
+    unused_plane_coords.truncate(filler_plane_number);
+    unused_plane_coords
+}
+
+/// Embed a decoy payload and a hidden payload into an image using BPCS, for plausible deniability
+///
+/// The decoy payload is embedded exactly like [embed_data](crate::bpcs::embed_data) would embed it, so it can be
+/// extracted with [extract_data](crate::bpcs::extract_data) and the decoy key alone. The hidden payload is embedded
+/// into accepted bit planes that the decoy doesn't use, and a random share of the accepted bit planes that are left in
+/// the most significant bit index the payloads reach is overwritten with random data. The header of the hidden payload
+/// is masked with its key, so all of its planes have the same complexity distribution as the random data. Someone who
+/// holds only the decoy key sees the decoy and random data, and nothing in the image proves that a second payload
+/// exists.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_with_decoy;
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let decoy_data = b"shopping list: milk, eggs";
+/// let hidden_data = b"the real message";
+///
+/// embed_data_with_decoy(&mut vessel_image, decoy_data, [1u8; 32], hidden_data, [2u8; 32], 0.3).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in six arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `decoy_data: &[u8]` — The innocuous payload, the one that is revealed if the user is coerced.
+/// * `decoy_rng_key: [u8; 32]` — The key of the decoy payload.
+/// * `hidden_data: &[u8]` — The real payload.
+/// * `hidden_rng_key: [u8; 32]` — The key of the real payload.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient, shared by both payloads.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `decoy_rng_key` and `hidden_rng_key` are equal, or if `min_alpha` isn't
+///   between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store both
+///   payloads.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * The hidden payload should be encrypted before embedding. Encrypted data is indistinguishable from the random data
+///   in the unused planes, plain text is not.
+/// * Filling the unused planes changes more of the image than embedding the decoy alone would.
+/// * The random data hides which of the written planes hold the hidden payload, not that planes were written. Like
+///   any BPCS payload, they can be told apart from the cover by someone who can detect a regular embedding.
+/// * Use [extract_hidden_data] to extract the hidden payload, it needs both keys.
+pub fn embed_data_with_decoy<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    decoy_data: &[u8],
+    decoy_rng_key: [u8; 32],
+    hidden_data: &[u8],
+    hidden_rng_key: [u8; 32],
+    min_alpha: f64,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    if decoy_rng_key == hidden_rng_key {
+        return Err(PixelveilError::InvalidParameter(
+            "the decoy and hidden keys must be different".to_string(),
+        ));
+    }
+
+    image_to_gray_code(source_image);
+
+    // select everything before writing anything, so a payload that doesn't fit leaves the image unchanged
+    let (decoy_layout, hidden_layout, unused_plane_coords) = match select_deniable_layouts(
+        source_image,
+        decoy_data.len(),
+        decoy_rng_key,
+        hidden_data.len(),
+        hidden_rng_key,
+        min_alpha,
+    ) {
+        Ok(layouts) => layouts,
+        Err(e) => {
+            image_to_binary_code(source_image);
+            return Err(e.into());
+        }
+    };
+
+    write_data_into_gray_coded_image(
+        source_image,
+        &decoy_layout,
+        &mut decoy_data.iter().copied(),
+        decoy_data.len(),
+        min_alpha,
+        &mut rng(),
+    );
+    write_data_into_gray_coded_image(
+        source_image,
+        &hidden_layout,
+        &mut hidden_data.iter().copied(),
+        hidden_data.len(),
+        min_alpha,
+        &mut rng(),
+    );
+    for coords in choose_filler_planes(unused_plane_coords, &mut rng()) {
+        write_plane_at(source_image, random_message_plane(&mut rng()), coords);
+    }
+
+    image_to_binary_code(source_image);
+
+    Ok(())
+}
+
+/// Extract the hidden payload from an image that was embedded using [embed_data_with_decoy]
+///
+/// The decoy payload is extracted with [extract_data](crate::bpcs::extract_data) and the decoy key, like any other
+/// payload.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::extract_hidden_data;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let hidden_data = extract_hidden_data(&vessel_image, 0.3, [1u8; 32], [2u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `decoy_rng_key: [u8; 32]` — The key of the decoy payload, it is needed to locate the planes the decoy uses.
+/// * `hidden_rng_key: [u8; 32]` — The key of the hidden payload.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If the IV of the decoy or of the hidden payload contains invalid data. The
+///   most likely causes of this are incorrect keys or `min_alpha`.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the hidden payload.
+pub fn extract_hidden_data<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    decoy_rng_key: [u8; 32],
+    hidden_rng_key: [u8; 32],
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let mut decoy_selector =
+        PlaneSelector::new(source_image, min_alpha, decoy_rng_key).reading_binary_coded_image();
+    let (decoy_layout, _) = read_embedding_layout(source_image, &mut decoy_selector, min_alpha)?;
+
+    let selector = hidden_selector(source_image, min_alpha, hidden_rng_key, &decoy_layout)
+        .reading_binary_coded_image();
+    Ok(extract_data_with_selector(
+        source_image,
+        selector,
+        min_alpha,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::lossless::bpcs::bit_plane::{BitPlane, read_planes_from_image_and_coords};
+    use image::open;
+    use std::iter::zip;
+
+    #[test]
+    fn test_hidden_header_planes_look_like_filler_planes() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let min_alpha = 0.3;
+        let (decoy_rng_key, hidden_rng_key) = ([1u8; 32], [2u8; 32]);
+        let source_image = open("tests/assets/test_circular_bpcs_vessel.png")?.to_rgb8();
+        let decoy_data = b"shopping list: milk, eggs";
+        // random, like the encrypted data the hidden payload should be
+        let hidden_data: Vec<u8> = (0..20_000).map(|_| rand::random()).collect();
+
+        let mut gray_coded_image = source_image.clone();
+        image_to_gray_code(&mut gray_coded_image);
+        let (_, hidden_layout, unused_plane_coords) = select_deniable_layouts(
+            &gray_coded_image,
+            decoy_data.len(),
+            decoy_rng_key,
+            hidden_data.len(),
+            hidden_rng_key,
+            min_alpha,
+        )?;
+        let unused_planes = read_planes_from_image_and_coords(
+            &source_image,
+            unused_plane_coords.clone(),
+            ImageCoding::BinaryCode,
+        );
+
+        // only a random share of the unused planes is filled, so the planes of a few embeddings are pooled
+        let mut header_alphas: Vec<f64> = Vec::new();
+        let mut filler_alphas: Vec<f64> = Vec::new();
+        for _ in 0..2 {
+            let mut vessel_image = source_image.clone();
+            embed_data_with_decoy(
+                &mut vessel_image,
+                decoy_data,
+                decoy_rng_key,
+                &hidden_data,
+                hidden_rng_key,
+                min_alpha,
+            )?;
+
+            let header_planes = read_planes_from_image_and_coords(
+                &vessel_image,
+                hidden_layout.header_plane_coords().copied().collect(),
+                ImageCoding::BinaryCode,
+            );
+            header_alphas.extend(header_planes.iter().map(BitPlane::alpha));
+
+            // the filled planes are the unused planes that were changed
+            let embedded_planes = read_planes_from_image_and_coords(
+                &vessel_image,
+                unused_plane_coords.clone(),
+                ImageCoding::BinaryCode,
+            );
+            filler_alphas.extend(
+                zip(&unused_planes, embedded_planes)
+                    .filter(|(unused_plane, embedded_plane)| *unused_plane != embedded_plane)
+                    .map(|(_, embedded_plane)| embedded_plane.alpha()),
+            );
+        }
+        assert!(filler_alphas.len() >= 100);
+
+        // an unmasked header has planes below 0.5, which no filler plane has
+        let mean = |alphas: &[f64]| alphas.iter().sum::<f64>() / alphas.len() as f64;
+        let share_of_simple = |alphas: &[f64]| {
+            alphas.iter().filter(|alpha| **alpha < 0.55).count() as f64 / alphas.len() as f64
+        };
+        assert!(
+            header_alphas
+                .iter()
+                .chain(&filler_alphas)
+                .all(|alpha| *alpha >= 0.5)
+        );
+        assert!((mean(&header_alphas) - mean(&filler_alphas)).abs() < 0.01);
+        assert!((share_of_simple(&header_alphas) - share_of_simple(&filler_alphas)).abs() < 0.15);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{dynamic_prefix::validate_min_alpha, embed_data_into_gray_coded_image},
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::ops::{Deref, DerefMut};
+
+const FILLER_SEED_DOMAIN: &[u8] = b"pixelveil/bpcs/filler";
+
+// Derives the filler rng from everything that determines the embedding, so the same inputs always produce the same
+//  filler and prefix bits, and changing any of them changes all of the filler.
+pub(crate) fn deterministic_filler_rng(
+    rng_key: [u8; 32],
+    min_alpha: f64,
+    data: &[u8],
+) -> ChaCha20Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(FILLER_SEED_DOMAIN);
+    hasher.update(rng_key);
+    hasher.update(min_alpha.to_be_bytes());
+    hasher.update((data.len() as u64).to_be_bytes());
+    hasher.update(data);
+    ChaCha20Rng::from_seed(hasher.finalize().into())
+}
+
+/// Embed data into an image using BPCS, such that the same inputs always produce the same image
+///
+/// [embed_data](crate::bpcs::embed_data) fills the unused bits of the last message plane and the complexity prefixes
+/// of the header planes with random bits, so embedding the same data twice produces different images. This function
+/// draws all of these bits from a ChaCha20 stream that is seeded with a hash of the key, `min_alpha` and the data
+/// instead. The result can be extracted with [extract_data](crate::bpcs::extract_data) like any other payload.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_deterministic;
+/// use image::RgbImage;
+///
+/// let mut first_image = RgbImage::new(512, 512);
+/// let mut second_image = first_image.clone();
+/// let data = b"a golden file";
+///
+/// embed_data_deterministic(&mut first_image, data, 0.3, [0u8; 32]).unwrap();
+/// embed_data_deterministic(&mut second_image, data, 0.3, [0u8; 32]).unwrap();
+/// assert_eq!(first_image, second_image);
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &[u8]` — The data to embed.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image
+///   and for seeding the filler bits.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * The whole payload is needed up front to seed the filler, so this function takes a slice instead of an iterator.
+/// * Embedding the same data with the same key into the same image always gives the same result, which is what makes
+///   golden files work, but it also lets anyone holding two such images tell that they carry the same payload. Use
+///   [embed_data](crate::bpcs::embed_data) when that matters.
+pub fn embed_data_deterministic<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let mut filler_rng = deterministic_filler_rng(rng_key, min_alpha, data);
+
+    image_to_gray_code(source_image);
+
+    let embedded = embed_data_into_gray_coded_image(
+        source_image,
+        &mut data.iter().copied(),
+        data.len(),
+        min_alpha,
+        rng_key,
+        &mut filler_rng,
+    );
+
+    // planes are only written after all of them were successfully selected, so a failed embedding leaves the image
+    //  unchanged
+    image_to_binary_code(source_image);
+
+    embedded?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    #[test]
+    fn test_filler_rng_depends_on_every_input() {
+        let data = [1u8, 2, 3];
+        let reference = deterministic_filler_rng([0u8; 32], 0.3, &data).next_u64();
+
+        assert_eq!(
+            deterministic_filler_rng([0u8; 32], 0.3, &data).next_u64(),
+            reference
+        );
+        assert_ne!(
+            deterministic_filler_rng([1u8; 32], 0.3, &data).next_u64(),
+            reference
+        );
+        assert_ne!(
+            deterministic_filler_rng([0u8; 32], 0.31, &data).next_u64(),
+            reference
+        );
+        assert_ne!(
+            deterministic_filler_rng([0u8; 32], 0.3, &[1u8, 2, 4]).next_u64(),
+            reference
+        );
+    }
+}
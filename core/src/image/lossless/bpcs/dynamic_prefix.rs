@@ -1,16 +1,42 @@
-use crate::image::lossless::bpcs::bit_plane::{BitPlane, PLANE_SIZE, USIZE_PLANE_SIZE};
-use rand::Rng;
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::bit_plane::{BitPlane, PLANE_SIZE, USIZE_PLANE_SIZE},
+    utils::bit_operations_utils::unsigned_int_to_bits,
+};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+const HEADER_MASK_SEED_DOMAIN: &[u8] = b"pixelveil/bpcs/header-mask";
+
+// the bit of the random prefix that tells masked planes that were conjugated apart, the checkerboard flips it
+const CONJUGATION_MARKER_BIT: usize = 1;
 
 pub(crate) fn num_of_prefixed_planes_for_n_bits(n: usize, prefix_length: usize) -> usize {
     (n as f64 / ((USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) - prefix_length) as f64).ceil() as usize
 }
 
-pub(crate) fn prefix_length(min_alpha: f64) -> usize {
+const MAX_MIN_ALPHA: f64 = 0.5;
+
+// a min_alpha of about 0.68 or more makes the prefix longer than a plane, and NaN makes every plane unacceptable, so
+// every public function that takes a min_alpha checks it with this first
+pub(crate) fn validate_min_alpha(min_alpha: f64) -> Result<(), PixelveilError> {
+    if !(0.0..=MAX_MIN_ALPHA).contains(&min_alpha) {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "min_alpha must be between 0.0 and {MAX_MIN_ALPHA}, got {min_alpha}"
+        )));
+    }
+    Ok(())
+}
+
+/// The number of random bits at the start of every plane that is built by
+/// [encode_prefixed_planes](crate::bpcs::raw::encode_prefixed_planes), for the given `min_alpha`
+pub fn prefix_length(min_alpha: f64) -> usize {
     ((PLANE_SIZE * PLANE_SIZE) as f64 * ((1.4 * min_alpha) + 0.05)).ceil() as usize
 }
 
-pub(crate) fn get_n_random_bools(n: usize) -> Vec<bool> {
-    let mut rng = rand::rng();
+// the filler randomness is drawn from the given rng, which is the thread rng unless the embedding is deterministic
+pub(crate) fn get_n_random_bools(n: usize, rng: &mut impl Rng) -> Vec<bool> {
     let mut bools = Vec::with_capacity(n);
     for _ in 0..n {
         bools.push(rng.random_bool(0.5));
@@ -18,13 +44,17 @@ pub(crate) fn get_n_random_bools(n: usize) -> Vec<bool> {
     bools
 }
 
-pub(crate) fn fill_to_prefixed_plane_size(bits: &mut Vec<bool>, min_alpha: f64) {
+pub(crate) fn fill_to_prefixed_plane_size(
+    bits: &mut Vec<bool>,
+    min_alpha: f64,
+    rng: &mut impl Rng,
+) {
     let data_bits_per_plane = (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) - prefix_length(min_alpha);
     let to_be_filled_length = data_bits_per_plane - (bits.len() % data_bits_per_plane);
-    if to_be_filled_length == data_bits_per_plane && bits.len() != 0 {
+    if to_be_filled_length == data_bits_per_plane && !bits.is_empty() {
         return;
     }
-    let filler_bits = get_n_random_bools(to_be_filled_length);
+    let filler_bits = get_n_random_bools(to_be_filled_length, rng);
     bits.extend(filler_bits);
 }
 
@@ -32,6 +62,7 @@ pub(crate) fn get_next_prefixed_plane(
     bits: &mut Vec<bool>,
     min_alpha: f64,
     prefix_length: usize,
+    rng: &mut impl Rng,
 ) -> BitPlane {
     assert!(
         bits.len() >= (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) - prefix_length,
@@ -41,7 +72,7 @@ pub(crate) fn get_next_prefixed_plane(
         .drain(0..((USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) - prefix_length))
         .collect();
     loop {
-        let prefix_bits = get_n_random_bools(prefix_length);
+        let prefix_bits = get_n_random_bools(prefix_length, rng);
         let plane_bits = [prefix_bits.as_slice(), plane_data.as_slice()].concat();
         let plane = BitPlane::from_bits(plane_bits.try_into().unwrap());
         if plane.alpha() >= min_alpha {
@@ -50,12 +81,21 @@ pub(crate) fn get_next_prefixed_plane(
     }
 }
 
-pub(crate) fn get_prefixed_planes(mut bits: Vec<bool>, min_alpha: f64) -> Vec<BitPlane> {
-    fill_to_prefixed_plane_size(&mut bits, min_alpha);
+pub(crate) fn get_prefixed_planes(
+    mut bits: Vec<bool>,
+    min_alpha: f64,
+    rng: &mut impl Rng,
+) -> Vec<BitPlane> {
+    fill_to_prefixed_plane_size(&mut bits, min_alpha, rng);
     let prefix_length = prefix_length(min_alpha);
     let mut planes: Vec<BitPlane> = Vec::new();
     while !bits.is_empty() {
-        planes.push(get_next_prefixed_plane(&mut bits, min_alpha, prefix_length));
+        planes.push(get_next_prefixed_plane(
+            &mut bits,
+            min_alpha,
+            prefix_length,
+            rng,
+        ));
     }
     planes
 }
@@ -70,6 +110,74 @@ pub(crate) fn data_bits_from_prefixed_planes(planes: Vec<BitPlane>, min_alpha: f
     data
 }
 
+// Keyed masks that make the prefixed header planes of an embedding look like its message planes. A masked plane is the
+//  prefixed plane XORed with the mask of its index, and conjugated if it isn't complex enough, so without the key it
+//  is distributed exactly like a conjugated message plane of random data. The marker bit of the prefix is cleared
+//  before masking, so after unmasking it is only set if the plane was conjugated.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HeaderMask {
+    seed: [u8; 32],
+}
+
+impl HeaderMask {
+    pub(crate) fn new(rng_key: [u8; 32]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(HEADER_MASK_SEED_DOMAIN);
+        hasher.update(rng_key);
+        HeaderMask {
+            seed: hasher.finalize().into(),
+        }
+    }
+
+    // every plane index has its own 64 bits of the stream, so a plane can be unmasked without the ones before it
+    fn mask(&self, plane_index: usize) -> BitPlane {
+        let mut mask_rng = ChaCha20Rng::from_seed(self.seed);
+        mask_rng.set_word_pos(plane_index as u128 * 2);
+        BitPlane::from_bits(
+            unsigned_int_to_bits(mask_rng.next_u64())
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    // masks the header planes that start at first_plane_index (in the order the header planes are selected)
+    pub(crate) fn mask_planes(
+        &self,
+        planes: Vec<BitPlane>,
+        first_plane_index: usize,
+    ) -> Vec<BitPlane> {
+        let mut masked_planes = Vec::with_capacity(planes.len());
+        for (plane_index, plane) in (first_plane_index..).zip(planes) {
+            let mut bits = plane.export_to_bools();
+            bits[CONJUGATION_MARKER_BIT] = false;
+            let mut plane = BitPlane::from_bits(bits);
+            plane.xor(&self.mask(plane_index));
+            if plane.alpha() < 0.5 {
+                plane.conjugate();
+            }
+            masked_planes.push(plane);
+        }
+        masked_planes
+    }
+
+    // reverses mask_planes
+    pub(crate) fn unmask_planes(
+        &self,
+        planes: Vec<BitPlane>,
+        first_plane_index: usize,
+    ) -> Vec<BitPlane> {
+        let mut unmasked_planes = Vec::with_capacity(planes.len());
+        for (plane_index, mut plane) in (first_plane_index..).zip(planes) {
+            plane.xor(&self.mask(plane_index));
+            if plane.to_bits()[CONJUGATION_MARKER_BIT] {
+                plane.conjugate();
+            }
+            unmasked_planes.push(plane);
+        }
+        unmasked_planes
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,14 +185,15 @@ mod tests {
     #[test]
     fn test_fill_to_prefixed_plane_size() {
         let mut bits = vec![false; 0];
-        fill_to_prefixed_plane_size(&mut bits, 0.3);
-        get_next_prefixed_plane(&mut bits, 0.3, prefix_length(0.3)); // now this is supposed to work
+        fill_to_prefixed_plane_size(&mut bits, 0.3, &mut rand::rng());
+        get_next_prefixed_plane(&mut bits, 0.3, prefix_length(0.3), &mut rand::rng()); // now this is supposed to work
     }
 
     #[test]
     fn test_get_next_prefixed_plane() {
         let mut bits = vec![false; 40];
-        let next_plane = get_next_prefixed_plane(&mut bits, 0.3, prefix_length(0.3));
+        let next_plane =
+            get_next_prefixed_plane(&mut bits, 0.3, prefix_length(0.3), &mut rand::rng());
         assert!(next_plane.alpha() >= 0.3);
         assert_eq!(bits, [false; 7]);
     }
@@ -92,12 +201,29 @@ mod tests {
     #[test]
     fn test_circular_plane_prefixing_and_data_extraction() {
         let min_alpha: f64 = 0.3;
-        let bits = get_n_random_bools(99);
-        let planes = get_prefixed_planes(bits.clone(), min_alpha);
+        let bits = get_n_random_bools(99, &mut rand::rng());
+        let planes = get_prefixed_planes(bits.clone(), min_alpha, &mut rand::rng());
         let data = data_bits_from_prefixed_planes(planes, min_alpha);
         assert_eq!(data, bits);
     }
 
+    #[test]
+    fn test_header_mask_is_reversible() {
+        let min_alpha: f64 = 0.3;
+        let bits = get_n_random_bools(330, &mut rand::rng());
+        let planes = get_prefixed_planes(bits.clone(), min_alpha, &mut rand::rng());
+
+        let header_mask = HeaderMask::new([3u8; 32]);
+        let masked_planes = header_mask.mask_planes(planes, 2);
+        assert!(masked_planes.iter().all(|plane| plane.alpha() >= 0.5));
+
+        let unmasked_planes = header_mask.unmask_planes(masked_planes, 2);
+        assert_eq!(
+            data_bits_from_prefixed_planes(unmasked_planes, min_alpha),
+            bits
+        );
+    }
+
     #[test]
     fn test_num_of_prefixed_planes_for_n_bits() {
         assert_eq!(num_of_prefixed_planes_for_n_bits(128, 0), 2);
@@ -0,0 +1,109 @@
+use crate::{
+    image::lossless::bpcs::{bit_plane::BYTES_PER_PLANE, plane_selection::EmbeddingLayout},
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel, Primitive};
+use num::{PrimInt, ToPrimitive};
+use std::{collections::HashMap, ops::Deref};
+
+/// A report of the changes that a BPCS embedding operation made to an image
+///
+/// Returned by [embed_data_with_report](crate::bpcs::embed_data_with_report).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedReport {
+    /// The number of bit planes that were written, for each `(channel, bit_index)` pair.
+    ///
+    /// This includes the message planes as well as the header planes (IV and conjugation map). Pairs that weren't used
+    /// at all are not included.
+    pub planes_used: HashMap<(u8, u8), usize>,
+
+    /// The number of bit planes that hold the embedded data itself.
+    pub message_planes: usize,
+
+    /// The number of bit planes that hold the header (IV and conjugation map) of the embedded data.
+    pub header_planes: usize,
+
+    /// The size of the header in bytes, this is the overhead of the embedding on top of the data itself.
+    pub header_overhead_bytes: usize,
+
+    /// The number of bits in the image that were flipped (in pure binary code, counted over all channels).
+    pub bits_flipped: u64,
+
+    /// The number of pixels that had at least one of their channels changed.
+    pub pixels_changed: u64,
+
+    /// The mean squared error between the original and the new image, over all channels.
+    pub mse: f64,
+
+    /// The peak signal-to-noise ratio between the original and the new image, in decibels.
+    ///
+    /// This is `f64::INFINITY` if the images are identical.
+    pub psnr: f64,
+}
+
+impl EmbedReport {
+    pub(crate) fn new<P, C1, C2>(
+        original_image: &ImageBuffer<P, C1>,
+        new_image: &ImageBuffer<P, C2>,
+        layout: &EmbeddingLayout,
+    ) -> Self
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C1: Deref<Target = [P::Subpixel]>,
+        C2: Deref<Target = [P::Subpixel]>,
+    {
+        assert_eq!(original_image.dimensions(), new_image.dimensions());
+
+        let mut planes_used: HashMap<(u8, u8), usize> = HashMap::new();
+        for (_, _, channel, bit_index) in layout.all_plane_coords() {
+            *planes_used.entry((*channel, *bit_index)).or_insert(0) += 1;
+        }
+
+        let header_planes = layout.header_plane_coords().count();
+
+        let mut bits_flipped = 0u64;
+        let mut pixels_changed = 0u64;
+        let mut squared_error_sum = 0f64;
+        for (original_pixel, new_pixel) in original_image.pixels().zip(new_image.pixels()) {
+            let mut pixel_changed = false;
+            for (original, new) in original_pixel.channels().iter().zip(new_pixel.channels()) {
+                let flipped = PrimInt::count_ones(*original ^ *new);
+                if flipped != 0 {
+                    pixel_changed = true;
+                    bits_flipped += flipped as u64;
+                    let error = original.to_f64().unwrap() - new.to_f64().unwrap();
+                    squared_error_sum += error * error;
+                }
+            }
+            if pixel_changed {
+                pixels_changed += 1;
+            }
+        }
+
+        let sample_number = original_image.width() as f64
+            * original_image.height() as f64
+            * P::CHANNEL_COUNT as f64;
+        let mse = if sample_number == 0.0 {
+            0.0
+        } else {
+            squared_error_sum / sample_number
+        };
+        let max_value = P::Subpixel::DEFAULT_MAX_VALUE.to_f64().unwrap();
+        let psnr = if mse == 0.0 {
+            f64::INFINITY
+        } else {
+            10.0 * ((max_value * max_value) / mse).log10()
+        };
+
+        EmbedReport {
+            planes_used,
+            message_planes: layout.message_plane_coords.len(),
+            header_planes,
+            header_overhead_bytes: header_planes * BYTES_PER_PLANE,
+            bits_flipped,
+            pixels_changed,
+            mse,
+            psnr,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::image::lossless::bpcs::{
+    plane_selection::{EmbeddingLayout, PlaneCoords},
+    visualization::{bit_index_color, fill_block},
+};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// The role of a bit plane in a BPCS embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlannedPlaneKind {
+    /// The plane holds a part of the IV (the length of the embedded data).
+    InitializationVector,
+    /// The plane holds a part of the conjugation map.
+    ConjugationMap,
+    /// The plane holds a part of the embedded data itself.
+    Message,
+}
+
+/// A single bit plane that a BPCS embedding would overwrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlannedPlane {
+    /// The x coordinate of the top left pixel of the plane's block.
+    pub x: u32,
+    /// The y coordinate of the top left pixel of the plane's block.
+    pub y: u32,
+    /// The channel of the plane (e.g. R,G,B = 0,1,2).
+    pub channel: u8,
+    /// The bit index of the plane, `0` is the most significant bit.
+    pub bit_index: u8,
+    /// What the plane would hold.
+    pub kind: PlannedPlaneKind,
+}
+
+/// The result of a BPCS embedding dry run
+///
+/// Returned by [plan_embedding](crate::bpcs::plan_embedding).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingPlan {
+    /// Every bit plane that would be overwritten, in the order they would be written in.
+    pub planes: Vec<PlannedPlane>,
+
+    /// An image of the same dimensions as the vessel that highlights every block that would be changed.
+    ///
+    /// Unchanged blocks are black. Changed blocks are colored by the most significant bit index that would be written
+    /// in them, on a scale from blue (the least significant bit index) to red (the most significant bit index).
+    pub mask: RgbImage,
+}
+
+impl EmbeddingPlan {
+    pub(crate) fn new(layout: &EmbeddingLayout, width: u32, height: u32, bit_depth: u8) -> Self {
+        let with_kind = |coords: &Vec<PlaneCoords>, kind: PlannedPlaneKind| {
+            coords
+                .iter()
+                .map(move |(x, y, channel, bit_index)| PlannedPlane {
+                    x: *x,
+                    y: *y,
+                    channel: *channel,
+                    bit_index: *bit_index,
+                    kind,
+                })
+                .collect::<Vec<PlannedPlane>>()
+        };
+
+        // the planes are listed in the order they are written in
+        let mut planes: Vec<PlannedPlane> = Vec::new();
+        planes.extend(with_kind(
+            &layout.iv_plane_coords,
+            PlannedPlaneKind::InitializationVector,
+        ));
+        planes.extend(with_kind(
+            &layout.message_plane_coords,
+            PlannedPlaneKind::Message,
+        ));
+        planes.extend(with_kind(
+            &layout.conj_map_plane_coords,
+            PlannedPlaneKind::ConjugationMap,
+        ));
+
+        // find the most significant bit index that is written in every block
+        let mut block_bit_indices: HashMap<(u32, u32), u8> = HashMap::new();
+        for plane in &planes {
+            let bit_index = block_bit_indices
+                .entry((plane.x, plane.y))
+                .or_insert(plane.bit_index);
+            *bit_index = (*bit_index).min(plane.bit_index);
+        }
+
+        let mut mask = RgbImage::new(width, height);
+        for ((x, y), bit_index) in block_bit_indices {
+            fill_block(&mut mask, x, y, bit_index_color(bit_index, bit_depth));
+        }
+
+        EmbeddingPlan { planes, mask }
+    }
+}
@@ -1,46 +1,94 @@
 use crate::{
     errors::SteganographyError,
     image::lossless::bpcs::{
-        bit_plane::{BitPlane, PLANE_SIZE, USIZE_PLANE_SIZE},
+        bit_plane::{BYTES_PER_PLANE, BitPlane, PLANE_SIZE, USIZE_PLANE_SIZE},
         dynamic_prefix::{
             data_bits_from_prefixed_planes, get_prefixed_planes, num_of_prefixed_planes_for_n_bits,
             prefix_length,
         },
+        plane_encoding::BlockEncoding,
     },
     utils::bit_operations_utils::{USIZE_BIT_DEPTH, bits_to_usize, unsigned_int_to_bits},
 };
+use rand::Rng;
 
 pub(crate) const MESSAGE_LENGTH_IV_BIT_NUMBER: usize = USIZE_BIT_DEPTH;
 pub(crate) const MESSAGE_REMNANT_IV_BIT_NUMBER: usize = USIZE_BIT_DEPTH;
 
+// the format version is stored in the most significant byte of the remnant IV, which was always 0 before versioning.
+//  version 0 selects planes with rand's StdRng, version 1 pins the plane selection PRNG (see selection_rng)
+pub(crate) const FORMAT_VERSION: u8 = 1;
+const FORMAT_VERSION_SHIFT: usize = MESSAGE_REMNANT_IV_BIT_NUMBER - 8;
+// the block encoding is stored in the byte below the format version. it is 0 for conjugation, so payloads without a
+//  block encoding are conjugated, and readers that don't know the byte reject other encodings as an invalid remnant
+const BLOCK_ENCODING_SHIFT: usize = FORMAT_VERSION_SHIFT - 8;
+// the byte below the block encoding is 1 if the payload is followed by the compressed cover planes it replaced (see
+//  reversible), and 0 otherwise
+const STORES_COVER_SHIFT: usize = BLOCK_ENCODING_SHIFT - 8;
+
+// the data that is stored in the IV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IVData {
+    pub(crate) message_plane_length: usize,
+    pub(crate) message_remnant_length: usize,
+    pub(crate) format_version: u8,
+    pub(crate) block_encoding: BlockEncoding,
+    pub(crate) stores_cover: bool,
+}
+
 pub(crate) fn calculate_iv_plane_number(min_alpha: f64) -> usize {
     num_of_prefixed_planes_for_n_bits(MESSAGE_LENGTH_IV_BIT_NUMBER, prefix_length(min_alpha))
         + num_of_prefixed_planes_for_n_bits(MESSAGE_REMNANT_IV_BIT_NUMBER, prefix_length(min_alpha))
 }
 
+pub(crate) fn calculate_message_plane_length_and_remnant(data_length: usize) -> (usize, usize) {
+    let message_plane_length = data_length.div_ceil(BYTES_PER_PLANE);
+    let remnant_bit_number = match (data_length * 8) % (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE) {
+        0 => USIZE_PLANE_SIZE * USIZE_PLANE_SIZE,
+        remnant_bit_number => remnant_bit_number,
+    };
+    (message_plane_length, remnant_bit_number)
+}
+
 pub(crate) fn build_message_length_iv(message_plane_length: usize) -> Vec<bool> {
     unsigned_int_to_bits(message_plane_length)
 }
 
-pub(crate) fn build_message_remnant_iv(remnant_bit_number: usize) -> Vec<bool> {
-    assert!(remnant_bit_number <= (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE));
-    unsigned_int_to_bits(remnant_bit_number)
+pub(crate) fn build_message_remnant_iv(
+    remnant_bit_number: usize,
+    block_encoding: BlockEncoding,
+    stores_cover: bool,
+) -> Vec<bool> {
+    // the remnant always comes from calculate_message_plane_length_and_remnant, which keeps it within a plane, a larger
+    //  one would overwrite the flag bits
+    debug_assert!(remnant_bit_number <= (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE));
+    unsigned_int_to_bits(
+        ((FORMAT_VERSION as usize) << FORMAT_VERSION_SHIFT)
+            | ((block_encoding.id() as usize) << BLOCK_ENCODING_SHIFT)
+            | ((stores_cover as usize) << STORES_COVER_SHIFT)
+            | remnant_bit_number,
+    )
 }
 
 pub(crate) fn build_iv_planes(
     min_alpha: f64,
     message_plane_length: usize,
     remnant_bit_number: usize,
+    block_encoding: BlockEncoding,
+    stores_cover: bool,
+    rng: &mut impl Rng,
 ) -> Vec<BitPlane> {
     let mut iv_planes: Vec<BitPlane> = Vec::new();
     iv_planes.extend(get_prefixed_planes(
         build_message_length_iv(message_plane_length),
         min_alpha,
+        rng,
     ));
 
     iv_planes.extend(get_prefixed_planes(
-        build_message_remnant_iv(remnant_bit_number),
+        build_message_remnant_iv(remnant_bit_number, block_encoding, stores_cover),
         min_alpha,
+        rng,
     ));
 
     iv_planes
@@ -49,14 +97,15 @@ pub(crate) fn build_iv_planes(
 pub(crate) fn build_conjugation_map_planes(
     conjugation_map: Vec<bool>,
     min_alpha: f64,
+    rng: &mut impl Rng,
 ) -> Vec<BitPlane> {
-    get_prefixed_planes(conjugation_map, min_alpha)
+    get_prefixed_planes(conjugation_map, min_alpha, rng)
 }
 
 pub(crate) fn extract_iv_data_from_iv_planes(
     mut planes: Vec<BitPlane>,
     min_alpha: f64,
-) -> Result<(usize, usize), SteganographyError> {
+) -> Result<IVData, SteganographyError> {
     let prefix_length = prefix_length(min_alpha);
     let msg_len_iv_plane_length =
         num_of_prefixed_planes_for_n_bits(MESSAGE_LENGTH_IV_BIT_NUMBER, prefix_length);
@@ -79,25 +128,63 @@ pub(crate) fn extract_iv_data_from_iv_planes(
     .drain(0..MESSAGE_REMNANT_IV_BIT_NUMBER)
     .collect::<Vec<bool>>();
 
-    let message_remnant_length = bits_to_usize(remnant_iv_bits.try_into().unwrap());
+    let remnant_iv = bits_to_usize(remnant_iv_bits.try_into().unwrap());
+    let format_version = (remnant_iv >> FORMAT_VERSION_SHIFT) as u8;
+    let block_encoding_id = (remnant_iv >> BLOCK_ENCODING_SHIFT) as u8;
+    let stores_cover_flag = (remnant_iv >> STORES_COVER_SHIFT) as u8;
+    let message_remnant_length = remnant_iv & ((1usize << STORES_COVER_SHIFT) - 1);
+
+    if format_version > FORMAT_VERSION {
+        return Err(SteganographyError::InvalidIVData(format!(
+            "Format version {format_version} is not supported, the latest supported version is {FORMAT_VERSION}"
+        )));
+    }
+
+    let Some(block_encoding) = BlockEncoding::from_id(block_encoding_id) else {
+        return Err(SteganographyError::InvalidIVData(format!(
+            "Block encoding {block_encoding_id} is not supported"
+        )));
+    };
+
+    let stores_cover = match stores_cover_flag {
+        0 => false,
+        1 => true,
+        _ => {
+            return Err(SteganographyError::InvalidIVData(format!(
+                "The stored cover flag must be 0 or 1, got {stores_cover_flag}"
+            )));
+        }
+    };
+
+    if message_remnant_length == 0 {
+        return Err(SteganographyError::InvalidIVData(String::from(
+            "Message remnant IV can't be zero, a full last plane is stored as a full remnant",
+        )));
+    }
 
     if message_remnant_length > USIZE_PLANE_SIZE * USIZE_PLANE_SIZE {
         let bits_per_plane = PLANE_SIZE * PLANE_SIZE;
-        return Err(SteganographyError::InvalidIVData(String::from(format!(
+        return Err(SteganographyError::InvalidIVData(format!(
             "Message remnant IV can't be more than the amount of bits per plane ({message_remnant_length} > {bits_per_plane})"
-        ))));
+        )));
     }
 
-    Ok((message_plane_length, message_remnant_length))
+    Ok(IVData {
+        message_plane_length,
+        message_remnant_length,
+        format_version,
+        block_encoding,
+        stores_cover,
+    })
 }
 
 pub(crate) fn extract_conj_map_data_from_conj_map_planes(
     planes: Vec<BitPlane>,
     min_alpha: f64,
-    message_plane_length: usize,
+    conjugation_map_length: usize,
 ) -> Result<Vec<bool>, SteganographyError> {
     let conjugation_map_data = data_bits_from_prefixed_planes(planes, min_alpha)
-        .drain(0..message_plane_length) // get only the bits of iv data, without the filling
+        .drain(0..conjugation_map_length) // get only the bits of iv data, without the filling
         .collect::<Vec<bool>>();
     Ok(conjugation_map_data)
 }
@@ -110,12 +197,28 @@ mod tests {
     #[test]
     fn test_circular_iv_generation_and_data_extraction() -> Result<(), Box<dyn std::error::Error>> {
         let (min_alpha, message_plane_length, remnant_bit_number) = (0.3, 65832usize, 4);
-        let iv_planes = build_iv_planes(min_alpha, message_plane_length, remnant_bit_number);
-
-        assert_eq!(
-            extract_iv_data_from_iv_planes(iv_planes, min_alpha)?,
-            (message_plane_length, remnant_bit_number)
-        );
+        let block_encodings = [BlockEncoding::Conjugation, BlockEncoding::MinimumChange];
+        for (block_encoding, stores_cover) in iproduct!(block_encodings, [false, true]) {
+            let iv_planes = build_iv_planes(
+                min_alpha,
+                message_plane_length,
+                remnant_bit_number,
+                block_encoding,
+                stores_cover,
+                &mut rand::rng(),
+            );
+
+            assert_eq!(
+                extract_iv_data_from_iv_planes(iv_planes, min_alpha)?,
+                IVData {
+                    message_plane_length,
+                    message_remnant_length: remnant_bit_number,
+                    format_version: FORMAT_VERSION,
+                    block_encoding,
+                    stores_cover,
+                }
+            );
+        }
 
         Ok(())
     }
@@ -125,9 +228,9 @@ mod tests {
     -> Result<(), Box<dyn std::error::Error>> {
         let message_plane_length = 47usize;
         let min_alpha = 0.3f64;
-        let conjugation_map = get_n_random_bools(message_plane_length);
+        let conjugation_map = get_n_random_bools(message_plane_length, &mut rand::rng());
         let conjugation_map_planes =
-            build_conjugation_map_planes(conjugation_map.clone(), min_alpha);
+            build_conjugation_map_planes(conjugation_map.clone(), min_alpha, &mut rand::rng());
         assert_eq!(
             extract_conj_map_data_from_conj_map_planes(
                 conjugation_map_planes,
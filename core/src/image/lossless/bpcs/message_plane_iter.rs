@@ -1,10 +1,13 @@
 use crate::{
-    image::lossless::bpcs::bit_plane::{BYTES_PER_PLANE, BitPlane},
+    image::lossless::bpcs::{
+        bit_plane::{BYTES_PER_PLANE, BitPlane, USIZE_PLANE_SIZE},
+        dynamic_prefix::get_n_random_bools,
+    },
     utils::bit_operations_utils::unsigned_int_to_bits,
 };
-use rand::{Rng, rng};
+use rand::Rng;
 
-pub(crate) fn get_bytes_per_plane_u8s<'a, T>(iter: &'a mut T) -> Option<Vec<u8>>
+pub(crate) fn get_bytes_per_plane_u8s<T>(iter: &mut T, rng: &mut impl Rng) -> Option<Vec<u8>>
 where
     T: Iterator<Item = u8>,
 {
@@ -20,7 +23,7 @@ where
             if u8s.is_empty() {
                 return None;
             } else {
-                u8s.push(rng().random_range(0u8..0b11111111u8));
+                u8s.push(rng.random_range(0u8..0b11111111u8));
             }
         }
     }
@@ -28,63 +31,66 @@ where
     Some(u8s)
 }
 
-pub(crate) struct MessagePlanesIter<'a, T>
+pub(crate) struct MessagePlanesIter<'a, T, R>
 where
     T: Iterator<Item = u8>,
+    R: Rng,
 {
     pub(crate) message_byte_iter: &'a mut T,
-    pub(crate) conjugation_map: &'a mut Vec<bool>,
+    pub(crate) filler_rng: &'a mut R,
 }
 
-impl<'a, T> MessagePlanesIter<'a, T>
+impl<'a, T, R> MessagePlanesIter<'a, T, R>
 where
     T: Iterator<Item = u8>,
+    R: Rng,
 {
-    pub(crate) fn new(message_byte_iter: &'a mut T, conjugation_map: &'a mut Vec<bool>) -> Self {
+    pub(crate) fn new(message_byte_iter: &'a mut T, filler_rng: &'a mut R) -> Self {
         MessagePlanesIter {
             message_byte_iter,
-            conjugation_map,
+            filler_rng,
         }
     }
 }
 
-impl<'a, T> Iterator for MessagePlanesIter<'a, T>
+impl<'a, T, R> Iterator for MessagePlanesIter<'a, T, R>
 where
     T: Iterator<Item = u8>,
+    R: Rng,
 {
     type Item = BitPlane;
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(plane_u8s) = get_bytes_per_plane_u8s(self.message_byte_iter) {
+        if let Some(plane_u8s) = get_bytes_per_plane_u8s(self.message_byte_iter, self.filler_rng) {
             // get bits
             let mut data_bits: Vec<bool> = Vec::with_capacity(plane_u8s.len() * 8);
             for byte in plane_u8s {
                 data_bits.extend(unsigned_int_to_bits(byte));
             }
 
-            let mut plane = BitPlane::from_bits(data_bits.try_into().unwrap());
-
-            if plane.alpha() < 0.5 {
-                plane.conjugate();
-                self.conjugation_map.push(true);
-            } else {
-                self.conjugation_map.push(false);
-            }
-
-            Some(plane)
+            // the plane is encoded (e.g. conjugated) by the layout's encoding before it is written
+            Some(BitPlane::from_bits(data_bits.try_into().unwrap()))
         } else {
             None
         }
     }
 }
 
+// a plane of random data that is indistinguishable from a message plane of random (e.g. encrypted) data
+pub(crate) fn random_message_plane(rng: &mut impl Rng) -> BitPlane {
+    let bits = get_n_random_bools(USIZE_PLANE_SIZE * USIZE_PLANE_SIZE, rng);
+    let mut plane = BitPlane::from_bits(bits.try_into().unwrap());
+    if plane.alpha() < 0.5 {
+        plane.conjugate();
+    }
+    plane
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_message_plane_iter_consistency() {
-        let mut conj_map: Vec<bool> = Vec::with_capacity(8 * 9);
-
         let mut bytes = vec![
             0b00000000u8,
             0b00100000u8,
@@ -98,10 +104,10 @@ mod tests {
         ]
         .into_iter();
 
-        let mut plane_iter = MessagePlanesIter::new(&mut bytes, &mut conj_map);
+        let mut rng = rand::rng();
+        let mut plane_iter = MessagePlanesIter::new(&mut bytes, &mut rng);
 
-        let mut next = plane_iter.next().unwrap();
-        next.conjugate(); // next is supposed to be automatically conjugated on the .next(), so we conjugate it again to get the original data
+        let next = plane_iter.next().unwrap();
         assert_eq!(
             next.bits,
             [
@@ -117,16 +123,20 @@ mod tests {
         );
 
         let next = plane_iter.next().unwrap();
-        // the 2nd bit plane might have been conjugated, depending on how the bits were filled to fit a plane, so we need the
-        //  first row (the data bits) to be equal to either the regular or conjugated version of itself
-        assert!(
-            (next.bits[0] == [false, false, true, true, true, true, false, true])
-                ^ (next.bits[0] == [false, true, true, false, true, false, false, false])
+        // only the first row of the 2nd bit plane holds data, the rest is random filler
+        assert_eq!(
+            next.bits[0],
+            [false, false, true, true, true, true, false, true]
         );
 
         let next = plane_iter.next();
         assert!(next.is_none());
+    }
 
-        assert_eq!(conj_map[0], true);
+    #[test]
+    fn test_random_message_plane_is_complex() {
+        for _ in 0..100 {
+            assert!(random_message_plane(&mut rand::rng()).alpha() >= 0.5);
+        }
     }
 }
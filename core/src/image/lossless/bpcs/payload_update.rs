@@ -0,0 +1,286 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        bit_plane::write_plane_at,
+        decode_message_planes,
+        dynamic_prefix::validate_min_alpha,
+        message_plane_iter::random_message_plane,
+        plane_selection::{EmbeddingLayout, PlaneCoords, PlaneSelector},
+        read_embedding_layout, read_message_planes, select_embedding_layout,
+        write_data_into_gray_coded_image,
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::rng;
+use std::{
+    collections::HashSet,
+    ops::{Deref, DerefMut},
+};
+
+/// A report of a BPCS payload update
+///
+/// Returned by [replace_data](crate::bpcs::replace_data) and [append_data](crate::bpcs::append_data).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadUpdateReport {
+    /// The length of the payload that was in the image before the update, in bytes.
+    pub previous_data_length: usize,
+
+    /// The length of the payload that is in the image after the update, in bytes.
+    pub new_data_length: usize,
+
+    /// The number of bit planes that held the previous payload and hold the new one.
+    pub reused_planes: usize,
+
+    /// The number of bit planes that hold the new payload and didn't hold the previous one.
+    pub new_planes: usize,
+
+    /// The number of bit planes that held the previous payload and don't hold the new one.
+    ///
+    /// These planes are overwritten with random data, so no part of the previous payload is left in the image.
+    pub released_planes: usize,
+}
+
+struct PayloadUpdate {
+    previous_data: Vec<u8>,
+    previous_layout: EmbeddingLayout,
+}
+
+// reads the payload that is currently embedded in the image, along with the planes that hold it
+fn read_current_payload<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<PayloadUpdate, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let mut selector = PlaneSelector::new(source_image, min_alpha, rng_key);
+    let (previous_layout, message_remnant_length) =
+        read_embedding_layout(source_image, &mut selector, min_alpha)?;
+
+    // the new payload would take the place of the stored cover, and the original image could never be restored
+    if previous_layout.stores_cover {
+        return Err(SteganographyError::CoverStored);
+    }
+
+    let (conjugation_map, message_planes) =
+        read_message_planes(source_image, &previous_layout, min_alpha, selector.coding())?;
+    let previous_data = decode_message_planes(
+        &previous_layout.encoding,
+        conjugation_map,
+        message_planes,
+        message_remnant_length,
+    );
+
+    Ok(PayloadUpdate {
+        previous_data,
+        previous_layout,
+    })
+}
+
+fn update_gray_coded_image<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    new_data: impl Fn(Vec<u8>) -> Vec<u8>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<PayloadUpdateReport, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    let PayloadUpdate {
+        previous_data,
+        previous_layout,
+    } = read_current_payload(source_image, min_alpha, rng_key)?;
+    let previous_data_length = previous_data.len();
+    let data = new_data(previous_data);
+
+    // writing planes never changes which planes are accepted, so the selection is the same as it would be on the cover
+    //  image, and the selection of an unchanged length is exactly the previous layout. the new payload keeps the block
+    //  encoding of the previous one
+    let new_layout = select_embedding_layout(
+        source_image,
+        data.len(),
+        min_alpha,
+        rng_key,
+        previous_layout.encoding.block_encoding(),
+    )?;
+
+    let previous_planes: HashSet<&PlaneCoords> = previous_layout.all_plane_coords().collect();
+    let new_planes: HashSet<&PlaneCoords> = new_layout.all_plane_coords().collect();
+    let released_plane_coords: Vec<PlaneCoords> = previous_planes
+        .difference(&new_planes)
+        .map(|coords| **coords)
+        .collect();
+
+    let report = PayloadUpdateReport {
+        previous_data_length,
+        new_data_length: data.len(),
+        reused_planes: previous_planes.intersection(&new_planes).count(),
+        new_planes: new_planes.difference(&previous_planes).count(),
+        released_planes: released_plane_coords.len(),
+    };
+
+    write_data_into_gray_coded_image(
+        source_image,
+        &new_layout,
+        &mut data.iter().copied(),
+        data.len(),
+        min_alpha,
+        &mut rng(),
+    );
+    for coords in released_plane_coords {
+        write_plane_at(source_image, random_message_plane(&mut rng()), coords);
+    }
+
+    Ok(report)
+}
+
+fn update_image<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    new_data: impl Fn(Vec<u8>) -> Vec<u8>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<PayloadUpdateReport, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    image_to_gray_code(source_image);
+
+    // nothing is written before the new payload is known to fit, so the image is unchanged on error
+    let report = update_gray_coded_image(source_image, new_data, min_alpha, rng_key);
+
+    image_to_binary_code(source_image);
+
+    Ok(report?)
+}
+
+/// Replace the payload of an image that already has data embedded in it using BPCS
+///
+/// The existing header is read with the key, and the new payload is embedded in place of the previous one. The
+/// original cover image is not needed.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{embed_data, replace_data};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let rng_key = [0u8; 32];
+///
+/// let old_data = [1u8, 2, 3];
+/// embed_data(&mut vessel_image, &mut old_data.into_iter(), old_data.len(), 0.3, rng_key).unwrap();
+///
+/// let report = replace_data(&mut vessel_image, b"new data", 0.3, rng_key).unwrap();
+/// assert_eq!(report.previous_data_length, 3);
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to a stego image.
+/// * `data: &[u8]` — The new payload.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient the previous payload was embedded with.
+/// * `rng_key: [u8; 32]` — The key the previous payload was embedded with, the new payload is embedded with it too.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If the image doesn't contain a payload that was embedded with the key and
+///   `min_alpha`.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the new payload doesn't fit in the image.
+/// * `SteganographyError::CoverStored` — If the previous payload was embedded with
+///   [embed_data_reversible](crate::bpcs::embed_data_reversible), replacing it would discard the cover it stores.
+///
+/// # Returns
+/// Returns `Result<PayloadUpdateReport, PixelveilError>`. The source image will be modified instead of returning a
+/// new one. If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * The planes are selected exactly like [embed_data](crate::bpcs::embed_data) selects them, so the result is the same
+///   as embedding the new payload into the original cover. The same planes are reused where the new payload's layout
+///   overlaps the previous one, and a payload of the same length reuses all of them.
+/// * Planes of the previous payload that aren't reused are overwritten with random data.
+/// * A payload that stores its cover isn't updated. Restore the original image with
+///   [restore_cover](crate::bpcs::restore_cover) and embed the new payload into it instead.
+pub fn replace_data<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<PayloadUpdateReport, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    update_image(source_image, |_| data.to_vec(), min_alpha, rng_key)
+}
+
+/// Append data to the payload of an image that already has data embedded in it using BPCS
+///
+/// The existing payload is read with the key, and the extended payload is embedded in place of it. The original cover
+/// image is not needed.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{embed_data, append_data};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let rng_key = [0u8; 32];
+///
+/// let old_data = [1u8, 2, 3];
+/// embed_data(&mut vessel_image, &mut old_data.into_iter(), old_data.len(), 0.3, rng_key).unwrap();
+///
+/// let report = append_data(&mut vessel_image, &[4, 5], 0.3, rng_key).unwrap();
+/// assert_eq!(report.new_data_length, 5);
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to a stego image.
+/// * `data: &[u8]` — The data to append to the existing payload.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient the existing payload was embedded with.
+/// * `rng_key: [u8; 32]` — The key the existing payload was embedded with.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If the image doesn't contain a payload that was embedded with the key and
+///   `min_alpha`.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the extended payload doesn't fit in the image.
+/// * `SteganographyError::CoverStored` — If the existing payload was embedded with
+///   [embed_data_reversible](crate::bpcs::embed_data_reversible), extending it would discard the cover it stores.
+///
+/// # Returns
+/// Returns `Result<PayloadUpdateReport, PixelveilError>`. The source image will be modified instead of returning a
+/// new one. If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// See the notes of [replace_data](crate::bpcs::replace_data).
+pub fn append_data<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<PayloadUpdateReport, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    update_image(
+        source_image,
+        |mut previous_data| {
+            previous_data.extend_from_slice(data);
+            previous_data
+        },
+        min_alpha,
+        rng_key,
+    )
+}
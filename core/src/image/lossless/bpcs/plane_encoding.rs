@@ -0,0 +1,316 @@
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{
+        bit_plane::BitPlane, dynamic_prefix::validate_min_alpha,
+        embed_data_into_gray_coded_image_with_encoding,
+    },
+    utils::{
+        bit_operations_utils::unsigned_int_to_bits,
+        image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+    },
+};
+use image::{ImageBuffer, Pixel};
+use rand::{RngCore, SeedableRng, rng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::ops::{Deref, DerefMut};
+
+const TRANSFORM_MASK_SEED_DOMAIN: &[u8] = b"pixelveil/bpcs/minimum-change";
+
+// the number of transforms a minimum change plane can be encoded with, and the map bits that select one of them
+const TRANSFORM_COUNT: usize = 4;
+const TRANSFORM_INDEX_BITS: usize = 2;
+
+/// How the message planes of a BPCS payload are encoded before they replace the cover planes
+///
+/// The encoding is declared in the header of the payload, so extraction detects it on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockEncoding {
+    /// A message plane is conjugated if it isn't complex enough, and written as is otherwise. Every message plane
+    /// takes one bit of the conjugation map.
+    #[default]
+    Conjugation,
+    /// Every message plane is XORed with the keyed mask (and conjugated or not) that leaves it closest to the cover
+    /// plane it replaces, so fewer bits of the image are flipped. Every message plane takes three bits of the map.
+    MinimumChange,
+}
+
+impl BlockEncoding {
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            BlockEncoding::Conjugation => 0,
+            BlockEncoding::MinimumChange => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(BlockEncoding::Conjugation),
+            1 => Some(BlockEncoding::MinimumChange),
+            _ => None,
+        }
+    }
+
+    // the number of map bits that every message plane takes
+    pub(crate) fn map_bits_per_plane(self) -> usize {
+        match self {
+            BlockEncoding::Conjugation => 1,
+            BlockEncoding::MinimumChange => 1 + TRANSFORM_INDEX_BITS,
+        }
+    }
+}
+
+// A block encoding along with the keyed masks it needs. The map bits of a plane are its conjugation bit, followed by
+//  the index of its mask (most significant bit first) for minimum change planes.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PlaneEncoding {
+    Conjugation,
+    MinimumChange(Box<[BitPlane; TRANSFORM_COUNT]>),
+}
+
+impl PlaneEncoding {
+    pub(crate) fn new(block_encoding: BlockEncoding, rng_key: [u8; 32]) -> Self {
+        match block_encoding {
+            BlockEncoding::Conjugation => PlaneEncoding::Conjugation,
+            BlockEncoding::MinimumChange => {
+                PlaneEncoding::MinimumChange(Box::new(transform_masks(rng_key)))
+            }
+        }
+    }
+
+    pub(crate) fn block_encoding(&self) -> BlockEncoding {
+        match self {
+            PlaneEncoding::Conjugation => BlockEncoding::Conjugation,
+            PlaneEncoding::MinimumChange(_) => BlockEncoding::MinimumChange,
+        }
+    }
+
+    pub(crate) fn map_bits_per_plane(&self) -> usize {
+        self.block_encoding().map_bits_per_plane()
+    }
+
+    // Encodes a message plane and pushes its map bits. The cover plane is only read by the minimum change encoding.
+    //  Every encoded plane has a complexity of at least min_alpha, so the extractor selects the same planes.
+    pub(crate) fn encode(
+        &self,
+        mut data_plane: BitPlane,
+        min_alpha: f64,
+        cover_plane: impl FnOnce() -> BitPlane,
+        encoding_map: &mut Vec<bool>,
+    ) -> BitPlane {
+        let masks = match self {
+            PlaneEncoding::Conjugation => {
+                let is_conjugated = data_plane.alpha() < 0.5;
+                if is_conjugated {
+                    data_plane.conjugate();
+                }
+                encoding_map.push(is_conjugated);
+                return data_plane;
+            }
+            PlaneEncoding::MinimumChange(masks) => masks,
+        };
+
+        let cover_plane = cover_plane();
+        let mut best: Option<(usize, BitPlane, bool, usize)> = None;
+        for (mask_index, mask) in masks.iter().enumerate() {
+            let mut candidate = data_plane.clone();
+            candidate.xor(mask);
+            for is_conjugated in [false, true] {
+                if is_conjugated {
+                    candidate.conjugate();
+                }
+                if candidate.alpha() < min_alpha {
+                    continue;
+                }
+                let distance = candidate.hamming_distance(&cover_plane);
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_distance, ..)| distance < *best_distance)
+                {
+                    best = Some((distance, candidate.clone(), is_conjugated, mask_index));
+                }
+            }
+        }
+
+        // a plane or its conjugate always has a complexity of at least 0.5, so the unmasked candidates can't both be
+        //  rejected by a valid min_alpha
+        let (_, plane, is_conjugated, mask_index) =
+            best.expect("A plane or its conjugate is always complex enough.");
+        encoding_map.push(is_conjugated);
+        encoding_map.extend(
+            (0..TRANSFORM_INDEX_BITS)
+                .rev()
+                .map(|bit| (mask_index >> bit) & 1 == 1),
+        );
+        plane
+    }
+
+    // reverses encode, map_bits are the map bits of the plane
+    pub(crate) fn decode(&self, mut plane: BitPlane, map_bits: &[bool]) -> BitPlane {
+        assert_eq!(map_bits.len(), self.map_bits_per_plane());
+        if map_bits[0] {
+            plane.conjugate();
+        }
+        if let PlaneEncoding::MinimumChange(masks) = self {
+            let mask_index = map_bits[1..]
+                .iter()
+                .fold(0usize, |index, bit| (index << 1) | *bit as usize);
+            plane.xor(&masks[mask_index]);
+        }
+        plane
+    }
+
+    // Checks whether a plane that was read as a message plane could have been encoded with its map bits. Conjugation
+    //  leaves every message plane with a complexity of at least 0.5, and every conjugated one above 0.5. Minimum
+    //  change planes only have to be complex enough to be selected, which every selected plane is.
+    pub(crate) fn is_consistent(&self, plane: &BitPlane, map_bits: &[bool]) -> bool {
+        match self {
+            PlaneEncoding::Conjugation => {
+                let alpha = plane.alpha();
+                alpha > 0.5 || (alpha == 0.5 && !map_bits[0])
+            }
+            PlaneEncoding::MinimumChange(_) => true,
+        }
+    }
+}
+
+// The first mask is empty, so the data can always be written as is. The rest are drawn from a ChaCha20 stream that is
+//  seeded with a hash of the key, so only the key holder knows them, and they never change between versions.
+fn transform_masks(rng_key: [u8; 32]) -> [BitPlane; TRANSFORM_COUNT] {
+    let mut hasher = Sha256::new();
+    hasher.update(TRANSFORM_MASK_SEED_DOMAIN);
+    hasher.update(rng_key);
+    let mut mask_rng = ChaCha20Rng::from_seed(hasher.finalize().into());
+
+    std::array::from_fn(|mask_index| match mask_index {
+        0 => BitPlane::new(),
+        _ => BitPlane::from_bits(
+            unsigned_int_to_bits(mask_rng.next_u64())
+                .try_into()
+                .unwrap(),
+        ),
+    })
+}
+
+/// Embed data into an image using BPCS, flipping as few bits of the image as possible
+///
+/// [embed_data](crate::bpcs::embed_data) replaces every selected cover plane with a message plane, conjugating the
+/// message plane if it isn't complex enough. This function chooses, for every block, between several keyed reversible
+/// transforms of the message plane, and writes the one that is closest to the cover plane it replaces. The chosen
+/// transforms are recorded in the map that holds the conjugation bits in a regular embedding. The result can be
+/// extracted with [extract_data](crate::bpcs::extract_data) like any other payload.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_minimum_change;
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let data = b"fewer flipped bits";
+///
+/// embed_data_minimum_change(&mut vessel_image, data, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &[u8]` — The data to embed.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image
+///   and for deriving the transforms.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * Every message plane takes three bits of the map instead of one, so the header is larger and slightly less data
+///   fits into the image than [estimate_maximum_capacity](crate::bpcs::estimate_maximum_capacity) reports.
+/// * On random (e.g. encrypted) data the closest of the transforms flips roughly 18% fewer bits per message plane
+///   than writing the plane as is, which raises the PSNR of the image at the same payload.
+/// * The same encoding is available through
+///   [BpcsConfigBuilder::block_encoding](crate::bpcs::BpcsConfigBuilder::block_encoding).
+pub fn embed_data_minimum_change<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    image_to_gray_code(source_image);
+
+    let embedded = embed_data_into_gray_coded_image_with_encoding(
+        source_image,
+        &mut data.iter().copied(),
+        data.len(),
+        min_alpha,
+        rng_key,
+        BlockEncoding::MinimumChange,
+        &mut rng(),
+    );
+
+    // planes are only written after all of them were successfully selected, so a failed embedding leaves the image
+    //  unchanged
+    image_to_binary_code(source_image);
+
+    embedded?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::lossless::bpcs::message_plane_iter::random_message_plane;
+
+    #[test]
+    fn test_encode_and_decode_are_circular() {
+        let min_alpha = 0.3;
+        for block_encoding in [BlockEncoding::Conjugation, BlockEncoding::MinimumChange] {
+            let encoding = PlaneEncoding::new(block_encoding, [5u8; 32]);
+            for _ in 0..100 {
+                let data_plane = random_message_plane(&mut rand::rng());
+                let cover_plane = random_message_plane(&mut rand::rng());
+                let mut encoding_map: Vec<bool> = Vec::new();
+
+                let plane = encoding.encode(
+                    data_plane.clone(),
+                    min_alpha,
+                    || cover_plane.clone(),
+                    &mut encoding_map,
+                );
+
+                assert_eq!(encoding_map.len(), block_encoding.map_bits_per_plane());
+                assert!(plane.alpha() >= min_alpha);
+                assert!(encoding.is_consistent(&plane, &encoding_map));
+                // the data as is is always a candidate, unless it isn't complex enough
+                assert!(
+                    plane.hamming_distance(&cover_plane)
+                        <= data_plane.hamming_distance(&cover_plane)
+                        || block_encoding == BlockEncoding::Conjugation
+                );
+                assert_eq!(encoding.decode(plane, &encoding_map), data_plane);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transform_masks_depend_on_the_key() {
+        let masks = transform_masks([0u8; 32]);
+        assert_eq!(masks, transform_masks([0u8; 32]));
+        assert_ne!(masks, transform_masks([1u8; 32]));
+        assert_eq!(masks[0], BitPlane::new());
+        assert_ne!(masks[1], masks[2]);
+    }
+}
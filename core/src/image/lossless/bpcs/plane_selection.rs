@@ -1,20 +1,38 @@
 use crate::{
     errors::SteganographyError,
     image::lossless::bpcs::{
+        bit_plane::{ImageCoding, PLANE_SIZE},
         bit_plane_iter::BitIndexedBitPlaneIter,
-        dynamic_prefix::{num_of_prefixed_planes_for_n_bits, prefix_length},
-        initialization_vector::{MESSAGE_LENGTH_IV_BIT_NUMBER, MESSAGE_REMNANT_IV_BIT_NUMBER},
+        dynamic_prefix::{HeaderMask, num_of_prefixed_planes_for_n_bits, prefix_length},
+        initialization_vector::{
+            FORMAT_VERSION, MESSAGE_LENGTH_IV_BIT_NUMBER, MESSAGE_REMNANT_IV_BIT_NUMBER,
+        },
+        plane_encoding::{BlockEncoding, PlaneEncoding},
+        progress::{BpcsStage, PROGRESS_INTERVAL, ReportProgress, no_progress},
+        selection_rng::SelectionRng,
     },
+    utils::image_utils::BitPlaneSubpixel,
 };
-use image::RgbImage;
+use image::{ImageBuffer, Pixel};
 use itertools::Itertools;
-use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
-use std::collections::HashMap;
+use std::{cell::OnceCell, collections::HashMap, ops::Deref};
 
-pub(crate) fn count_accepted_planes(source_image: &RgbImage, min_alpha: f64) -> u64 {
+/// The coordinates of a bit plane in an image: `(x, y, channel, bit_index)`, where `(x, y)` is the top left pixel of the
+/// plane and bit index 0 is the most significant bit
+pub type PlaneCoords = (u32, u32, u8, u8);
+
+pub(crate) fn count_accepted_planes_with_coding<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    coding: ImageCoding,
+) -> u64
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
     let mut accepted_count = 0u64;
-    for bit_index in 0..8 {
-        let plane_iter = BitIndexedBitPlaneIter::new(source_image, bit_index);
+    for bit_index in 0..P::Subpixel::BIT_DEPTH {
+        let plane_iter = BitIndexedBitPlaneIter::with_coding(source_image, bit_index, coding);
         for (_, plane) in plane_iter {
             if plane.alpha() >= min_alpha {
                 accepted_count += 1;
@@ -24,33 +42,110 @@ pub(crate) fn count_accepted_planes(source_image: &RgbImage, min_alpha: f64) ->
     accepted_count
 }
 
-pub(crate) fn collect_accepted_planes_at_bit_index(
-    source_image: &RgbImage,
+pub(crate) fn collect_accepted_planes_at_bit_index<P, C>(
+    source_image: &ImageBuffer<P, C>,
     min_alpha: f64,
     bit_index: u8,
-) -> Vec<(u32, u32, u8, u8)> {
+    coding: ImageCoding,
+) -> Vec<PlaneCoords>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    scan_accepted_planes_at_bit_index(source_image, min_alpha, bit_index, coding, &no_progress)
+        .expect("Scanning without a progress observer can't be cancelled.")
+}
+
+// collects the accepted planes like collect_accepted_planes_at_bit_index, and reports the scanned planes
+pub(crate) fn scan_accepted_planes_at_bit_index<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    bit_index: u8,
+    coding: ImageCoding,
+    progress: &ReportProgress,
+) -> Result<Vec<PlaneCoords>, SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
     let mut accepted_coords: Vec<(u32, u32, u8, u8)> = Vec::new();
 
-    let plane_iter = BitIndexedBitPlaneIter::new(source_image, bit_index);
-    for ((x, y, channel, bit_index), plane) in plane_iter {
+    let stage = BpcsStage::ScanningPlanes { bit_index };
+    let total_planes = (source_image.width() / PLANE_SIZE) as usize
+        * (source_image.height() / PLANE_SIZE) as usize
+        * P::CHANNEL_COUNT as usize;
+    progress(stage, 0, total_planes)?;
+
+    let plane_iter = BitIndexedBitPlaneIter::with_coding(source_image, bit_index, coding);
+    for (scanned_planes, ((x, y, channel, bit_index), plane)) in plane_iter.enumerate() {
         if plane.alpha() >= min_alpha {
             accepted_coords.push((x, y, channel, bit_index));
         }
+        if (scanned_planes + 1).is_multiple_of(PROGRESS_INTERVAL) {
+            progress(stage, scanned_planes + 1, total_planes)?;
+        }
+    }
+
+    progress(stage, total_planes, total_planes)?;
+    Ok(accepted_coords)
+}
+
+// The complexity of every plane of an image, computed lazily for each bit index. Selectors with different minimum
+//  complexities can share it, so the image is only scanned once.
+pub(crate) struct PlaneComplexities<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    source_image: &'a ImageBuffer<P, C>,
+    coding: ImageCoding,
+    complexities: Vec<OnceCell<Vec<(PlaneCoords, f64)>>>,
+}
+
+impl<'a, P, C> PlaneComplexities<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    pub(crate) fn new(source_image: &'a ImageBuffer<P, C>, coding: ImageCoding) -> Self {
+        PlaneComplexities {
+            source_image,
+            coding,
+            complexities: (0..P::Subpixel::BIT_DEPTH)
+                .map(|_| OnceCell::new())
+                .collect(),
+        }
     }
 
-    accepted_coords
+    // returns the same planes in the same order as collect_accepted_planes_at_bit_index
+    pub(crate) fn accepted_planes_at_bit_index(
+        &self,
+        min_alpha: f64,
+        bit_index: u8,
+    ) -> Vec<PlaneCoords> {
+        self.complexities[bit_index as usize]
+            .get_or_init(|| {
+                BitIndexedBitPlaneIter::with_coding(self.source_image, bit_index, self.coding)
+                    .map(|(coords, plane)| (coords, plane.alpha()))
+                    .collect()
+            })
+            .iter()
+            .filter(|(_, alpha)| *alpha >= min_alpha)
+            .map(|(coords, _)| *coords)
+            .collect()
+    }
 }
 
 pub(crate) fn drain_n_random_items_from_vec<T>(
     items: &mut Vec<T>,
     n: usize,
-    rng: &mut StdRng,
+    rng: &mut SelectionRng,
 ) -> Vec<T> {
     assert!(n <= items.len());
     let mut selected_items: Vec<T> = Vec::with_capacity(n);
     let mut selected_indexes = (0..items.len()).collect_vec();
 
-    selected_indexes.shuffle(rng);
+    rng.shuffle(&mut selected_indexes);
     let mut selected_indexes = selected_indexes[0..n].to_vec();
 
     selected_indexes.sort();
@@ -61,28 +156,45 @@ pub(crate) fn drain_n_random_items_from_vec<T>(
         selected_items.push(p);
     }
 
-    selected_items.shuffle(rng);
+    rng.shuffle(&mut selected_items);
 
     selected_items
 }
 
-pub(crate) struct PlaneSelector<'a> {
-    source_image: &'a RgbImage,
+pub(crate) struct PlaneSelector<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    source_image: &'a ImageBuffer<P, C>,
     min_alpha: f64,
-    plane_map: HashMap<u8, Option<Vec<(u32, u32, u8, u8)>>>,
-    rng: StdRng,
+    plane_map: HashMap<u8, Option<Vec<PlaneCoords>>>,
+    accepted_planes: HashMap<u8, Vec<PlaneCoords>>,
+    plane_filter: Box<dyn Fn(&PlaneCoords) -> bool + 'a>,
+    coding: ImageCoding,
+    complexities: Option<&'a PlaneComplexities<'a, P, C>>,
+    randomization_seed: [u8; 32],
+    rng: SelectionRng,
+    progress: &'a ReportProgress<'a>,
+    header_mask: Option<HeaderMask>,
 }
 
-impl<'a> PlaneSelector<'a> {
+impl<'a, P, C> PlaneSelector<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
     pub(crate) fn new(
-        source_image: &'a RgbImage,
+        source_image: &'a ImageBuffer<P, C>,
         min_alpha: f64,
         randomization_seed: [u8; 32],
     ) -> Self {
         // Generate empty map, if a value at a given bit index is None, it wasn't calculated yet. If it is Some(vec) then vec is a Vec that
         //  contains the remaining unselected bit planes at that bit index
-        let mut plane_map: HashMap<u8, Option<Vec<(u32, u32, u8, u8)>>> = HashMap::with_capacity(8);
-        for bit_index in 0u8..8u8 {
+        let bit_depth = P::Subpixel::BIT_DEPTH;
+        let mut plane_map: HashMap<u8, Option<Vec<PlaneCoords>>> =
+            HashMap::with_capacity(bit_depth as usize);
+        for bit_index in 0u8..bit_depth {
             plane_map.insert(bit_index, None);
         }
 
@@ -90,31 +202,112 @@ impl<'a> PlaneSelector<'a> {
             source_image,
             min_alpha,
             plane_map,
-            rng: StdRng::from_seed(randomization_seed),
+            accepted_planes: HashMap::with_capacity(bit_depth as usize),
+            plane_filter: Box::new(|_| true),
+            coding: ImageCoding::GrayCode,
+            complexities: None,
+            randomization_seed,
+            rng: SelectionRng::new(FORMAT_VERSION, randomization_seed),
+            progress: &no_progress,
+            header_mask: None,
         }
     }
 
+    // Restarts the selection from scratch, with the plane selection PRNG of the given format version. The accepted
+    //  planes that were already collected are kept, so they don't have to be collected again.
+    pub(crate) fn restart_with_format_version(&mut self, format_version: u8) {
+        for remaining_planes in self.plane_map.values_mut() {
+            *remaining_planes = None;
+        }
+        self.rng = SelectionRng::new(format_version, self.randomization_seed);
+    }
+
+    // Restricts the selection to accepted planes that pass the filter (on top of any previously added filter). Must be
+    //  called before any planes are selected.
+    pub(crate) fn with_plane_filter(self, filter: impl Fn(&PlaneCoords) -> bool + 'a) -> Self {
+        let previous_filter = self.plane_filter;
+        PlaneSelector {
+            plane_filter: Box::new(move |coords| previous_filter(coords) && filter(coords)),
+            ..self
+        }
+    }
+
+    // Reads the planes of a binary coded source image as Gray Code on the fly, instead of requiring the source image to
+    //  be converted to Gray Code. Must be called before any planes are selected.
+    pub(crate) fn reading_binary_coded_image(self) -> Self {
+        PlaneSelector {
+            coding: ImageCoding::BinaryCode,
+            ..self
+        }
+    }
+
+    // Takes the accepted planes from precomputed plane complexities of the source image, instead of scanning the source
+    //  image. Must be called before any planes are selected.
+    pub(crate) fn with_complexities(self, complexities: &'a PlaneComplexities<'a, P, C>) -> Self {
+        assert!(std::ptr::eq(self.source_image, complexities.source_image));
+        PlaneSelector {
+            coding: complexities.coding,
+            complexities: Some(complexities),
+            ..self
+        }
+    }
+
+    // Reports the scanning and the selection of planes, and stops them with an error if the reporter cancels. Planes
+    //  that are taken from precomputed complexities aren't scanned, so they aren't reported.
+    pub(crate) fn with_progress(self, progress: &'a ReportProgress<'a>) -> Self {
+        PlaneSelector { progress, ..self }
+    }
+
+    // Masks the header planes of the embedding with masks that are keyed with the selector's seed, see HeaderMask. Must
+    //  be called before the IV is selected.
+    pub(crate) fn with_masked_header(self) -> Self {
+        PlaneSelector {
+            header_mask: Some(HeaderMask::new(self.randomization_seed)),
+            ..self
+        }
+    }
+
+    pub(crate) fn header_mask(&self) -> Option<&HeaderMask> {
+        self.header_mask.as_ref()
+    }
+
+    pub(crate) fn coding(&self) -> ImageCoding {
+        self.coding
+    }
+
+    pub(crate) fn randomization_seed(&self) -> [u8; 32] {
+        self.randomization_seed
+    }
+
     pub(crate) fn select_n_planes(
         &mut self,
         n: usize,
     ) -> Result<Vec<(u32, u32, u8, u8)>, SteganographyError> {
         let mut unselected_num = n;
-        let mut total_selected: Vec<(u32, u32, u8, u8)> = Vec::with_capacity(n);
+        // n can come from an IV that was read from the image, so nothing is allocated for it up front
+        let mut total_selected: Vec<(u32, u32, u8, u8)> = Vec::new();
 
-        for bit_index in (0u8..8u8).rev() {
+        for bit_index in (0u8..P::Subpixel::BIT_DEPTH).rev() {
             // if the current bit index accepted planes weren't mapped yet, get them and insert them as Some into the map
-            match self.plane_map.get(&bit_index).unwrap() {
-                None => {
-                    self.plane_map.insert(
-                        bit_index,
-                        Some(collect_accepted_planes_at_bit_index(
+            if self.plane_map.get(&bit_index).unwrap().is_none() {
+                if !self.accepted_planes.contains_key(&bit_index) {
+                    let mut accepted_planes = match self.complexities {
+                        Some(complexities) => {
+                            complexities.accepted_planes_at_bit_index(self.min_alpha, bit_index)
+                        }
+                        None => scan_accepted_planes_at_bit_index(
                             self.source_image,
                             self.min_alpha,
                             bit_index,
-                        )),
-                    );
+                            self.coding,
+                            self.progress,
+                        )?,
+                    };
+                    accepted_planes.retain(|coords| (self.plane_filter)(coords));
+                    self.accepted_planes.insert(bit_index, accepted_planes);
                 }
-                _ => {}
+                self.plane_map
+                    .insert(bit_index, Some(self.accepted_planes[&bit_index].clone()));
             }
 
             // get the current bit index's remaining accepted bit planes
@@ -136,12 +329,14 @@ impl<'a> PlaneSelector<'a> {
                 unselected_num -= curr_bit_index_selected.len();
 
                 total_selected.extend(curr_bit_index_selected);
+                (self.progress)(BpcsStage::SelectingPlanes, total_selected.len(), n)?;
                 break;
             } else {
                 // if the bit planes of this bit index are not enough
                 unselected_num -= curr_bit_index_planes.len();
 
-                total_selected.extend(curr_bit_index_planes.drain(..));
+                total_selected.append(curr_bit_index_planes);
+                (self.progress)(BpcsStage::SelectingPlanes, total_selected.len(), n)?;
 
                 continue;
             }
@@ -154,7 +349,7 @@ impl<'a> PlaneSelector<'a> {
             ));
         }
 
-        total_selected.shuffle(&mut self.rng);
+        self.rng.shuffle(&mut total_selected);
         Ok(total_selected)
     }
 
@@ -175,10 +370,10 @@ impl<'a> PlaneSelector<'a> {
     pub(crate) fn select_conjugation_map_planes(
         &mut self,
         min_alpha: f64,
-        message_plane_length: usize,
+        conjugation_map_length: usize,
     ) -> Result<Vec<(u32, u32, u8, u8)>, SteganographyError> {
         let conjugation_map_plane_num =
-            num_of_prefixed_planes_for_n_bits(message_plane_length, prefix_length(min_alpha));
+            num_of_prefixed_planes_for_n_bits(conjugation_map_length, prefix_length(min_alpha));
         self.select_n_planes(conjugation_map_plane_num)
     }
 
@@ -190,6 +385,77 @@ impl<'a> PlaneSelector<'a> {
     }
 }
 
+pub(crate) struct EmbeddingLayout {
+    pub(crate) iv_plane_coords: Vec<PlaneCoords>,
+    pub(crate) conj_map_plane_coords: Vec<PlaneCoords>,
+    pub(crate) message_plane_coords: Vec<PlaneCoords>,
+    pub(crate) encoding: PlaneEncoding,
+    // whether the message planes hold a reversible container, which is declared in the IV
+    pub(crate) stores_cover: bool,
+    // the masks of the IV and conjugation map planes, if the header is masked
+    pub(crate) header_mask: Option<HeaderMask>,
+}
+
+impl EmbeddingLayout {
+    pub(crate) fn select<P, C>(
+        selector: &mut PlaneSelector<P, C>,
+        min_alpha: f64,
+        message_plane_length: usize,
+    ) -> Result<Self, SteganographyError>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        EmbeddingLayout::select_with_encoding(
+            selector,
+            min_alpha,
+            message_plane_length,
+            BlockEncoding::Conjugation,
+        )
+    }
+
+    // selects the planes of an embedding whose message planes are encoded with block_encoding, the transforms of the
+    //  encoding are keyed with the selector's seed
+    pub(crate) fn select_with_encoding<P, C>(
+        selector: &mut PlaneSelector<P, C>,
+        min_alpha: f64,
+        message_plane_length: usize,
+        block_encoding: BlockEncoding,
+    ) -> Result<Self, SteganographyError>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        // the order of selection is part of the format, it must match the order of extraction
+        let iv_plane_coords = selector.select_iv_planes(min_alpha)?;
+        let conj_map_plane_coords = selector.select_conjugation_map_planes(
+            min_alpha,
+            message_plane_length * block_encoding.map_bits_per_plane(),
+        )?;
+        let message_plane_coords = selector.select_message_planes(message_plane_length)?;
+
+        Ok(EmbeddingLayout {
+            iv_plane_coords,
+            conj_map_plane_coords,
+            message_plane_coords,
+            encoding: PlaneEncoding::new(block_encoding, selector.randomization_seed()),
+            stores_cover: false,
+            header_mask: selector.header_mask().cloned(),
+        })
+    }
+
+    pub(crate) fn header_plane_coords(&self) -> impl Iterator<Item = &PlaneCoords> {
+        self.iv_plane_coords
+            .iter()
+            .chain(self.conj_map_plane_coords.iter())
+    }
+
+    pub(crate) fn all_plane_coords(&self) -> impl Iterator<Item = &PlaneCoords> {
+        self.header_plane_coords()
+            .chain(self.message_plane_coords.iter())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,6 +490,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_selection_with_complexities_matches_scanning() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let randomization_seed = [3u8; 32];
+        let image_path = "tests/assets/test_deterministic_plane_selection.png";
+        let source_image = open(image_path)?.to_rgb8();
+        let complexities = PlaneComplexities::new(&source_image, ImageCoding::GrayCode);
+
+        for min_alpha in [0.1, 0.3, 0.45] {
+            let mut scanning_selector =
+                PlaneSelector::new(&source_image, min_alpha, randomization_seed);
+            let mut cached_selector =
+                PlaneSelector::new(&source_image, min_alpha, randomization_seed)
+                    .with_complexities(&complexities);
+            assert_eq!(
+                scanning_selector.select_n_planes(5_000)?,
+                cached_selector.select_n_planes(5_000)?
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_failing_plane_selection() -> Result<(), Box<dyn std::error::Error>> {
         let min_alpha = 0.2f64;
@@ -0,0 +1,179 @@
+use crate::{
+    image::lossless::bpcs::{
+        bit_plane::{BYTES_PER_PLANE, PLANE_SIZE},
+        dynamic_prefix::{num_of_prefixed_planes_for_n_bits, prefix_length, validate_min_alpha},
+        initialization_vector::calculate_iv_plane_number,
+        plane_encoding::BlockEncoding,
+        plane_selection::PlaneSelector,
+        select_and_read_iv,
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel};
+use std::ops::Deref;
+
+/// The header information of a BPCS payload, as declared by the image
+///
+/// Returned by [probe](crate::bpcs::probe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadInfo {
+    /// The length of the embedded data in bytes.
+    ///
+    /// For payloads that store their cover, this is the length of the data together with the compressed cover.
+    pub data_length: usize,
+
+    /// The version of the format the payload was embedded with.
+    ///
+    /// Images that were embedded before the format was versioned have a version of `0`.
+    pub format_version: u8,
+
+    /// How the message planes of the payload are encoded.
+    pub block_encoding: BlockEncoding,
+
+    /// Whether the payload stores the cover it replaced, so the original image can be restored with
+    /// [restore_cover](crate::bpcs::restore_cover).
+    pub stores_cover: bool,
+}
+
+/// Check whether an image carries a BPCS payload for a key, without extracting it
+///
+/// Only the header planes of the payload are selected and decoded. The image isn't copied or converted to Gray Code,
+/// the header planes are converted on the fly.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::probe;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// if let Some(payload_info) = probe(&vessel_image, 0.3, [0u8; 32]) {
+///     println!("found {} bytes", payload_info.data_length);
+/// }
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to probe, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// This function does not return errors.
+///
+/// # Returns
+/// Returns `Option<PayloadInfo>`. `Some(...)` holds the declared length, format version, block encoding and
+/// reversibility of the payload, and `None` is returned if the image doesn't carry a payload for the key and
+/// `min_alpha`, or if `min_alpha` isn't between 0.0 and 0.5.
+///
+/// # Notes
+/// * The header is validated, but the payload itself isn't read. In very rare cases an image without a payload can
+///   hold a header that looks valid by chance, so [extract_data](crate::bpcs::extract_data) can still fail after a
+///   successful probe.
+/// * The accepted bit planes of the least significant bit indexes are still scanned to select the header planes.
+pub fn probe<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Option<PayloadInfo>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha).ok()?;
+
+    let mut selector =
+        PlaneSelector::new(source_image, min_alpha, rng_key).reading_binary_coded_image();
+    let (_, iv_data) = select_and_read_iv(source_image, &mut selector, min_alpha).ok()?;
+    let message_plane_length = iv_data.message_plane_length;
+    // the IV is read from the image, so the length of the map it declares can overflow
+    let conjugation_map_length =
+        message_plane_length.checked_mul(iv_data.block_encoding.map_bits_per_plane())?;
+
+    // a payload can't declare more planes than the image has
+    let image_plane_number = (source_image.width() / PLANE_SIZE) as u128
+        * (source_image.height() / PLANE_SIZE) as u128
+        * P::CHANNEL_COUNT as u128
+        * P::Subpixel::BIT_DEPTH as u128;
+    let declared_plane_number = calculate_iv_plane_number(min_alpha) as u128
+        + num_of_prefixed_planes_for_n_bits(conjugation_map_length, prefix_length(min_alpha))
+            as u128
+        + message_plane_length as u128;
+    if declared_plane_number > image_plane_number {
+        return None;
+    }
+
+    // an empty payload still declares a full remnant
+    let data_length = match message_plane_length {
+        0 => 0,
+        _ => (message_plane_length - 1) * BYTES_PER_PLANE + iv_data.message_remnant_length / 8,
+    };
+
+    Some(PayloadInfo {
+        data_length,
+        format_version: iv_data.format_version,
+        block_encoding: iv_data.block_encoding,
+        stores_cover: iv_data.stores_cover,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bpcs::extract_data,
+        errors::{PixelveilError, SteganographyError},
+        image::lossless::bpcs::{
+            bit_plane::write_plane_at, initialization_vector::build_iv_planes,
+        },
+        utils::image_utils::{image_to_binary_code, image_to_gray_code},
+    };
+    use image::{Rgb, RgbImage};
+    use std::iter::zip;
+
+    #[test]
+    fn test_iv_declaring_usize_max_planes() -> Result<(), Box<dyn std::error::Error>> {
+        let (min_alpha, rng_key) = (0.3, [12u8; 32]);
+        // a small image of noise, so the IV fits and the planes it declares are quickly found missing
+        let source_image = RgbImage::from_fn(64, 64, |_, _| Rgb(rand::random()));
+
+        for block_encoding in [BlockEncoding::Conjugation, BlockEncoding::MinimumChange] {
+            let mut stego_image = source_image.clone();
+            image_to_gray_code(&mut stego_image);
+            let iv_plane_coords =
+                PlaneSelector::new(&stego_image, min_alpha, rng_key).select_iv_planes(min_alpha)?;
+            let iv_planes = build_iv_planes(
+                min_alpha,
+                usize::MAX,
+                64,
+                block_encoding,
+                false,
+                &mut rand::rng(),
+            );
+            for (coords, plane) in zip(iv_plane_coords, iv_planes) {
+                write_plane_at(&mut stego_image, plane, coords);
+            }
+            image_to_binary_code(&mut stego_image);
+
+            // the conjugation map of a minimum change payload overflows, a conjugation one doesn't fit into the image
+            assert_eq!(probe(&stego_image, min_alpha, rng_key), None);
+            let extracted = extract_data(&stego_image, min_alpha, rng_key);
+            match block_encoding {
+                BlockEncoding::Conjugation => assert!(matches!(
+                    extracted,
+                    Err(PixelveilError::Steganography(
+                        SteganographyError::InsufficientPlaneNumber(..)
+                    ))
+                )),
+                BlockEncoding::MinimumChange => assert!(matches!(
+                    extracted,
+                    Err(PixelveilError::Steganography(
+                        SteganographyError::InvalidIVData(_)
+                    ))
+                )),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        dynamic_prefix::validate_min_alpha,
+        extract_data_with_selector,
+        initialization_vector::calculate_message_plane_length_and_remnant,
+        plane_selection::{EmbeddingLayout, PlaneSelector},
+        write_data_into_gray_coded_image_with_progress,
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::rng;
+use std::{
+    cell::RefCell,
+    ops::{ControlFlow, Deref, DerefMut},
+};
+
+// the number of planes between two reports of the same stage
+pub(crate) const PROGRESS_INTERVAL: usize = 1024;
+
+// Reports progress to an observer, and returns SteganographyError::Cancelled if the observer cancels. Shared by
+//  reference between the plane selector and the writer.
+pub(crate) type ReportProgress<'a> =
+    dyn Fn(BpcsStage, usize, usize) -> Result<(), SteganographyError> + 'a;
+
+pub(crate) fn no_progress(_: BpcsStage, _: usize, _: usize) -> Result<(), SteganographyError> {
+    Ok(())
+}
+
+fn progress_reporter<'a>(
+    observer: &'a mut impl ProgressObserver,
+) -> impl Fn(BpcsStage, usize, usize) -> Result<(), SteganographyError> + 'a {
+    let observer = RefCell::new(observer);
+    move |stage, completed, total| match observer.borrow_mut().on_progress(stage, completed, total)
+    {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(SteganographyError::Cancelled),
+    }
+}
+
+/// A stage of a BPCS operation, as reported to a [ProgressObserver]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpcsStage {
+    /// The bit planes at a bit index are scanned to find the accepted ones. Bit indexes are scanned from the least
+    /// significant one up, and only until there are enough accepted planes.
+    ScanningPlanes {
+        /// The bit index that is scanned.
+        bit_index: u8,
+    },
+
+    /// Planes are selected from the accepted planes.
+    SelectingPlanes,
+
+    /// The selected planes are overwritten with the payload.
+    WritingPlanes,
+}
+
+/// Observes the progress of a long BPCS operation, and can cancel it
+///
+/// Closures of the type `FnMut(BpcsStage, usize, usize) -> ControlFlow<()>` implement this trait, so a closure can be
+/// passed wherever an observer is expected.
+pub trait ProgressObserver {
+    /// Called periodically while an operation runs.
+    ///
+    /// `completed` and `total` are counted in bit planes, and are relative to the current stage. Returning
+    /// `ControlFlow::Break(())` cancels the operation, which then returns `SteganographyError::Cancelled`.
+    fn on_progress(&mut self, stage: BpcsStage, completed: usize, total: usize) -> ControlFlow<()>;
+}
+
+impl<F> ProgressObserver for F
+where
+    F: FnMut(BpcsStage, usize, usize) -> ControlFlow<()>,
+{
+    fn on_progress(&mut self, stage: BpcsStage, completed: usize, total: usize) -> ControlFlow<()> {
+        self(stage, completed, total)
+    }
+}
+
+/// Embed data into an image using BPCS, while reporting the progress to an observer that can cancel the embedding
+///
+/// This is [embed_data](crate::bpcs::embed_data) with progress reporting. The observer is called while planes are
+/// scanned, selected and written.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{BpcsStage, embed_data_with_progress};
+/// use image::RgbImage;
+/// use std::ops::ControlFlow;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let data: [u8; _] = [6, 9, 193, 7, 1, 7];
+///
+/// embed_data_with_progress(
+///     &mut vessel_image,
+///     &mut data.into_iter(),
+///     data.len(),
+///     0.3,
+///     [0u8; 32],
+///     &mut |stage: BpcsStage, completed: usize, total: usize| {
+///         println!("{stage:?}: {completed}/{total}");
+///         ControlFlow::Continue(())
+///     },
+/// ).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in six arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &mut impl Iterator<Item = u8>` — An iterator that yields the bytes that are going to be embedded.
+/// * `data_length: usize` — The length of the data iterator, in bytes (the number of u8s). Must be the exact length
+///   of the `data` iterator.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+/// * `observer: &mut impl ProgressObserver` — The observer to report the progress to.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data.
+/// * `SteganographyError::DataLengthMismatch` — If the `data` iterator doesn't yield exactly `data_length` bytes.
+/// * `SteganographyError::Cancelled` — If the observer cancelled the embedding.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, including a cancellation, the source image is left unchanged.
+///
+/// # Notes
+/// The original content of the selected planes is kept in memory while writing, so a cancelled embedding can be rolled
+/// back.
+pub fn embed_data_with_progress<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &mut impl Iterator<Item = u8>,
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    observer: &mut impl ProgressObserver,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let progress = progress_reporter(observer);
+
+    image_to_gray_code(source_image);
+
+    let (message_plane_length, _) = calculate_message_plane_length_and_remnant(data_length);
+    let mut selector =
+        PlaneSelector::new(source_image, min_alpha, rng_key).with_progress(&progress);
+    let selected = EmbeddingLayout::select(&mut selector, min_alpha, message_plane_length);
+    drop(selector);
+    let layout = match selected {
+        Ok(layout) => layout,
+        Err(e) => {
+            image_to_binary_code(source_image);
+            return Err(e.into());
+        }
+    };
+
+    // a cancelled writing restores the planes it already wrote
+    let written = write_data_into_gray_coded_image_with_progress(
+        source_image,
+        &layout,
+        data,
+        data_length,
+        min_alpha,
+        &mut rng(),
+        &progress,
+    );
+
+    image_to_binary_code(source_image);
+
+    Ok(written?)
+}
+
+/// Extract data from an image using BPCS, while reporting the progress to an observer that can cancel the extraction
+///
+/// This is [extract_data](crate::bpcs::extract_data) with progress reporting. The observer is called while planes are
+/// scanned and selected.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{BpcsStage, extract_data_with_progress};
+/// use image::RgbImage;
+/// use std::ops::ControlFlow;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let extracted_data = extract_data_with_progress(
+///     &vessel_image,
+///     0.3,
+///     [0u8; 32],
+///     &mut |stage: BpcsStage, completed: usize, total: usize| {
+///         println!("{stage:?}: {completed}/{total}");
+///         ControlFlow::Continue(())
+///     },
+/// ).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+/// * `observer: &mut impl ProgressObserver` — The observer to report the progress to.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — if the IV in the image contains invalid data. The most likely causes of this
+///   are trying to extract data from an image that doesn't have data hidden in it or incorrect parameters.
+/// * `SteganographyError::Cancelled` — If the observer cancelled the extraction.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is a vector of the
+/// extracted data bytes.
+pub fn extract_data_with_progress<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    observer: &mut impl ProgressObserver,
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let progress = progress_reporter(observer);
+
+    let selector = PlaneSelector::new(source_image, min_alpha, rng_key)
+        .reading_binary_coded_image()
+        .with_progress(&progress);
+    Ok(extract_data_with_selector(
+        source_image,
+        selector,
+        min_alpha,
+    )?)
+}
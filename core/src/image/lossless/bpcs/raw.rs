@@ -0,0 +1,359 @@
+//! Low-level access to the bit planes that BPCS works on, for research and for building custom methods.
+//!
+//! The functions in the [bpcs](crate::bpcs) module only deal with payloads. This module exposes the building blocks
+//! they are made of: the [BitPlane] type and its complexity and conjugation, iteration over the planes of an image by
+//! bit index and channel, reading and writing planes at given coordinates, and the dynamic prefix that is used to make
+//! the header planes complex enough.
+//!
+//! # Example
+//! ```no_run
+//! use pixelveil::bpcs::raw::{ImageCoding, bit_planes};
+//! use image::RgbImage;
+//!
+//! let vessel_image = RgbImage::new(512, 512);
+//!
+//! // count the planes of the least significant bit index that BPCS would be able to replace
+//! let complex_planes = bit_planes(&vessel_image, 7, ImageCoding::BinaryCode)?
+//!     .filter(|(_, plane)| plane.alpha() >= 0.3)
+//!     .count();
+//! # Ok::<(), pixelveil::errors::PixelveilError>(())
+//! ```
+//!
+//! # Notes
+//! BPCS works on the Gray Code of an image. Planes are always read as Gray Code, the [ImageCoding] argument only tells
+//! the functions how the image itself is stored. Writing is done on the raw bits, so an image has to be converted to
+//! Gray Code with [image_to_gray_code](crate::image_utils::image_to_gray_code) before Gray Code planes are written
+//! into it, and converted back with [image_to_binary_code](crate::image_utils::image_to_binary_code) afterwards.
+
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{
+        bit_plane::{
+            USIZE_PLANE_SIZE, check_bit_index, check_channel_and_bit_index,
+            read_planes_from_image_and_coords, write_plane_at,
+        },
+        bit_plane_iter::BitIndexedBitPlaneIter,
+        dynamic_prefix::{data_bits_from_prefixed_planes, get_prefixed_planes, validate_min_alpha},
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel};
+use rand::Rng;
+use std::ops::{Deref, DerefMut};
+
+pub use crate::image::lossless::bpcs::{
+    bit_plane::{BitPlane, ImageCoding, PLANE_SIZE},
+    dynamic_prefix::prefix_length,
+    plane_selection::PlaneCoords,
+};
+
+// returns an error instead of letting an out of bounds panic happen deep inside the image crate
+fn check_plane_in_image<P, C>(
+    image: &ImageBuffer<P, C>,
+    coords: PlaneCoords,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let (x, y, channel, bit_index) = coords;
+    if x as u64 + PLANE_SIZE as u64 > image.width() as u64
+        || y as u64 + PLANE_SIZE as u64 > image.height() as u64
+    {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "the plane at {coords:?} doesn't fit in an image of dimensions {}x{}",
+            image.width(),
+            image.height()
+        )));
+    }
+    check_channel_and_bit_index::<P>(channel, bit_index)
+}
+
+/// Iterate over every plane of an image at a single bit index
+///
+/// The planes are yielded with their coordinates, in the same order BPCS scans them: by plane column, then by plane
+/// row and then by channel. Pixels on the right and bottom edges that don't fill a whole plane are skipped.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::{ImageCoding, bit_planes};
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// for ((x, y, channel, _), plane) in bit_planes(&vessel_image, 7, ImageCoding::BinaryCode)? {
+///     println!("({x}, {y}) channel {channel}: {}", plane.alpha());
+/// }
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `image: &ImageBuffer<P, C>` — The image to read the planes from.
+/// * `bit_index: u8` — The bit index of the planes, `0` is the most significant bit.
+/// * `coding: ImageCoding` — How the image is stored.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the bit index is out of bounds for the subpixel type of the image.
+///
+/// # Returns
+/// Returns `Result<impl Iterator<Item = (PlaneCoords, BitPlane)>, PixelveilError>`. If `Ok(...)` is returned, the
+/// contained value is an iterator of the planes and their coordinates, the planes are read as Gray Code.
+///
+/// # Notes
+/// An image that is narrower or shorter than [PLANE_SIZE] has no planes, so its iterator is empty.
+pub fn bit_planes<P, C>(
+    image: &ImageBuffer<P, C>,
+    bit_index: u8,
+    coding: ImageCoding,
+) -> Result<impl Iterator<Item = (PlaneCoords, BitPlane)>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    check_bit_index::<P>(bit_index)?;
+    Ok(BitIndexedBitPlaneIter::with_coding(
+        image, bit_index, coding,
+    ))
+}
+
+/// Iterate over the planes of a single channel of an image at a single bit index
+///
+/// This is [bit_planes] with only the planes of `channel`.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::{ImageCoding, channel_bit_planes};
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// // the complex planes of the green channel's least significant bit
+/// let complex_green_planes = channel_bit_planes(&vessel_image, 1, 7, ImageCoding::BinaryCode)?
+///     .filter(|(_, plane)| plane.alpha() >= 0.3)
+///     .count();
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `image: &ImageBuffer<P, C>` — The image to read the planes from.
+/// * `channel: u8` — The channel of the planes.
+/// * `bit_index: u8` — The bit index of the planes, `0` is the most significant bit.
+/// * `coding: ImageCoding` — How the image is stored.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the channel or bit index are out of bounds for the pixel type of the
+///   image.
+///
+/// # Returns
+/// Returns `Result<impl Iterator<Item = (PlaneCoords, BitPlane)>, PixelveilError>`. If `Ok(...)` is returned, the
+/// contained value is an iterator of the planes of the channel and their coordinates, the planes are read as Gray
+/// Code.
+pub fn channel_bit_planes<P, C>(
+    image: &ImageBuffer<P, C>,
+    channel: u8,
+    bit_index: u8,
+    coding: ImageCoding,
+) -> Result<impl Iterator<Item = (PlaneCoords, BitPlane)>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    check_channel_and_bit_index::<P>(channel, bit_index)?;
+    Ok(bit_planes(image, bit_index, coding)?
+        .filter(move |((_, _, plane_channel, _), _)| *plane_channel == channel))
+}
+
+/// Read the plane at the given coordinates
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::{ImageCoding, read_plane};
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// // the least significant bit plane of the red channel of the top left block
+/// let plane = read_plane(&vessel_image, (0, 0, 0, 7), ImageCoding::BinaryCode)?;
+/// println!("complexity: {}", plane.alpha());
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `image: &ImageBuffer<P, C>` — The image to read the plane from.
+/// * `coords: PlaneCoords` — The coordinates of the plane. `x` and `y` don't have to be multiples of [PLANE_SIZE].
+/// * `coding: ImageCoding` — How the image is stored.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the plane doesn't fit in the image, or if the channel or bit index are out
+///   of bounds.
+///
+/// # Returns
+/// Returns `Result<BitPlane, PixelveilError>`. If `Ok(...)` is returned, the contained value is the plane, read as
+/// Gray Code.
+pub fn read_plane<P, C>(
+    image: &ImageBuffer<P, C>,
+    coords: PlaneCoords,
+    coding: ImageCoding,
+) -> Result<BitPlane, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    check_plane_in_image(image, coords)?;
+    Ok(
+        read_planes_from_image_and_coords(image, vec![coords], coding)
+            .pop()
+            .unwrap(),
+    )
+}
+
+/// Overwrite the bits of the plane at the given coordinates
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::{ImageCoding, read_plane, write_plane};
+/// use pixelveil::image_utils::{image_to_binary_code, image_to_gray_code};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// image_to_gray_code(&mut vessel_image);
+///
+/// // conjugate a Gray Code plane in place
+/// let mut plane = read_plane(&vessel_image, (8, 8, 2, 6), ImageCoding::GrayCode)?;
+/// plane.conjugate();
+/// write_plane(&mut vessel_image, &plane, (8, 8, 2, 6))?;
+///
+/// image_to_binary_code(&mut vessel_image);
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `image: &mut ImageBuffer<P, C>` — A mutable reference to the image to write the plane into.
+/// * `plane: &BitPlane` — The plane to write.
+/// * `coords: PlaneCoords` — The coordinates of the plane. `x` and `y` don't have to be multiples of [PLANE_SIZE].
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If the plane doesn't fit in the image, or if the channel or bit index are out
+///   of bounds. The image is left unchanged.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the image will be modified instead of returning a new one.
+///
+/// # Notes
+/// The bits are written as they are. To write a Gray Code plane, convert the image to Gray Code first.
+pub fn write_plane<P, C>(
+    image: &mut ImageBuffer<P, C>,
+    plane: &BitPlane,
+    coords: PlaneCoords,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    check_plane_in_image(image, coords)?;
+    write_plane_at(image, plane.clone(), coords);
+    Ok(())
+}
+
+/// The number of data bits that fit in a single plane that is built by [encode_prefixed_planes]
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::data_bits_per_prefixed_plane;
+///
+/// let bits_per_plane = data_bits_per_prefixed_plane(0.3);
+/// ```
+///
+/// # Arguments
+/// The function takes in one argument:
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+pub fn data_bits_per_prefixed_plane(min_alpha: f64) -> usize {
+    (USIZE_PLANE_SIZE * USIZE_PLANE_SIZE).saturating_sub(prefix_length(min_alpha))
+}
+
+/// Encode bits into planes that are all at least as complex as `min_alpha`, using a dynamic prefix
+///
+/// The first [prefix_length] bits of every plane are random, and are drawn again until the plane is complex enough.
+/// The rest of the plane holds the data. This is how BPCS stores its header, so a header plane never has to be
+/// conjugated.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::encode_prefixed_planes;
+///
+/// let bits = vec![true; 100];
+/// let planes = encode_prefixed_planes(&bits, 0.3, &mut rand::rng())?;
+///
+/// assert!(planes.iter().all(|plane| plane.alpha() >= 0.3));
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `bits: &[bool]` — The data bits to encode.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng: &mut impl Rng` — The source of the prefix bits, and of the filler bits that pad the last plane.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+///
+/// # Returns
+/// Returns `Result<Vec<BitPlane>, PixelveilError>`. If `Ok(...)` is returned, the contained value holds the planes,
+/// there are `bits.len()` divided by [data_bits_per_prefixed_plane] of them, rounded up. At least one plane is always
+/// returned.
+pub fn encode_prefixed_planes(
+    bits: &[bool],
+    min_alpha: f64,
+    rng: &mut impl Rng,
+) -> Result<Vec<BitPlane>, PixelveilError> {
+    validate_min_alpha(min_alpha)?;
+
+    Ok(get_prefixed_planes(bits.to_vec(), min_alpha, rng))
+}
+
+/// Decode the data bits of planes that were built by [encode_prefixed_planes]
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::raw::{decode_prefixed_planes, encode_prefixed_planes};
+///
+/// let bits = vec![true, false, true];
+/// let planes = encode_prefixed_planes(&bits, 0.3, &mut rand::rng())?;
+///
+/// // the last plane is padded with filler bits
+/// assert_eq!(decode_prefixed_planes(planes, 0.3)?[..bits.len()], bits);
+/// # Ok::<(), pixelveil::errors::PixelveilError>(())
+/// ```
+///
+/// # Arguments
+/// The function takes in two arguments:
+/// * `planes: impl IntoIterator<Item = BitPlane>` — The planes to decode, in order.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient that the planes were built with.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+///
+/// # Returns
+/// Returns `Result<Vec<bool>, PixelveilError>`. If `Ok(...)` is returned, the contained value holds the data bits of
+/// the planes, including the filler bits that pad the last plane.
+pub fn decode_prefixed_planes(
+    planes: impl IntoIterator<Item = BitPlane>,
+    min_alpha: f64,
+) -> Result<Vec<bool>, PixelveilError> {
+    validate_min_alpha(min_alpha)?;
+
+    Ok(data_bits_from_prefixed_planes(
+        planes.into_iter().collect(),
+        min_alpha,
+    ))
+}
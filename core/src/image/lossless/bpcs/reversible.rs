@@ -0,0 +1,365 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        bit_plane::{
+            BYTES_PER_PLANE, BitPlane, ImageCoding, get_planes_from_image_and_coords,
+            read_planes_from_image_and_coords, write_plane_at,
+        },
+        capacity::max_message_plane_length,
+        cover_compression::{compress_planes, decompress_planes},
+        decode_message_planes,
+        dynamic_prefix::validate_min_alpha,
+        plane_encoding::BlockEncoding,
+        plane_selection::{EmbeddingLayout, PlaneSelector, collect_accepted_planes_at_bit_index},
+        read_embedding_layout, read_message_planes, select_embedding_layout,
+        write_data_into_gray_coded_image,
+    },
+    utils::image_utils::{
+        BitPlaneSubpixel, OwnedImageBuffer, image_to_binary_code, image_to_gray_code,
+    },
+};
+use image::{ImageBuffer, Pixel};
+use rand::{Rng, rng};
+use std::{
+    iter::zip,
+    ops::{Deref, DerefMut},
+};
+
+// A reversible embedding writes a container into the message planes instead of the payload itself: the length of the
+//  payload, the payload, the compressed (Gray coded) cover planes of every plane the embedding replaces in the order
+//  of all_plane_coords, and random padding.
+const PAYLOAD_LENGTH_BYTES: usize = 8;
+
+// the compression ratio is capped while growing the container, so covers that don't compress grow it quickly until
+//  the selection runs out of planes
+const MAX_GROWTH_COMPRESSION_RATIO: f64 = 0.99;
+
+fn build_reversible_container(
+    data: &[u8],
+    compressed_cover: Vec<u8>,
+    container_length: usize,
+    filler_rng: &mut impl Rng,
+) -> Vec<u8> {
+    let mut container: Vec<u8> = Vec::with_capacity(container_length);
+    container.extend((data.len() as u64).to_be_bytes());
+    container.extend_from_slice(data);
+    container.extend(compressed_cover);
+    assert!(container.len() <= container_length);
+    container.resize_with(container_length, || filler_rng.random());
+    container
+}
+
+// splits a reversible container into the payload and the compressed cover (which is followed by the padding)
+pub(crate) fn split_reversible_container(
+    mut container: Vec<u8>,
+) -> Result<(Vec<u8>, Vec<u8>), SteganographyError> {
+    if container.len() < PAYLOAD_LENGTH_BYTES {
+        return Err(SteganographyError::InvalidCoverData(format!(
+            "The container is {} bytes long, too short to hold the length of the payload",
+            container.len()
+        )));
+    }
+
+    let payload_length = u64::from_be_bytes(container[..PAYLOAD_LENGTH_BYTES].try_into().unwrap());
+    let stored_length = (container.len() - PAYLOAD_LENGTH_BYTES) as u64;
+    if payload_length > stored_length {
+        return Err(SteganographyError::InvalidCoverData(format!(
+            "The payload length ({payload_length}) is longer than the container ({stored_length})"
+        )));
+    }
+
+    let compressed_cover = container.split_off(PAYLOAD_LENGTH_BYTES + payload_length as usize);
+    container.drain(..PAYLOAD_LENGTH_BYTES);
+    Ok((container, compressed_cover))
+}
+
+// Selects a layout whose message planes can hold the payload together with the compressed cover planes of the whole
+//  layout. The compressed cover grows with the container, so the container is grown until both fit, or until the
+//  selection fails because the image doesn't have enough planes.
+fn select_reversible_layout<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(EmbeddingLayout, Vec<u8>, usize), SteganographyError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let mut container_length = PAYLOAD_LENGTH_BYTES + data.len();
+    loop {
+        let mut layout = select_embedding_layout(
+            source_image,
+            container_length,
+            min_alpha,
+            rng_key,
+            BlockEncoding::Conjugation,
+        )?;
+        layout.stores_cover = true;
+
+        let cover_planes = get_planes_from_image_and_coords(
+            source_image,
+            layout.all_plane_coords().copied().collect(),
+        );
+        let compressed_cover = compress_planes(&cover_planes);
+        let needed_length = PAYLOAD_LENGTH_BYTES + data.len() + compressed_cover.len();
+        if needed_length <= container_length {
+            return Ok((layout, compressed_cover, container_length));
+        }
+
+        // every byte the container grows by adds about compression_ratio bytes of compressed cover
+        let compression_ratio = (compressed_cover.len() as f64
+            / (cover_planes.len() * BYTES_PER_PLANE) as f64)
+            .min(MAX_GROWTH_COMPRESSION_RATIO);
+        let missing_length = (needed_length - container_length) as f64;
+        container_length += (missing_length / (1.0 - compression_ratio)).ceil() as usize;
+    }
+}
+
+/// Embed data into an image using BPCS, so the original image can be restored after extraction
+///
+/// The bit planes that an embedding replaces are compressed losslessly and embedded together with the data. The data
+/// can be extracted with [extract_data](crate::bpcs::extract_data) like any other payload, and [restore_cover] returns
+/// both the data and a bit-exact copy of the original image.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{embed_data_reversible, estimate_reversible_capacity};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let data = b"patient record 4471";
+///
+/// if estimate_reversible_capacity(&vessel_image, 0.3) >= data.len() as u64 {
+///     embed_data_reversible(&mut vessel_image, data, 0.3, [0u8; 32]).unwrap();
+/// }
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &[u8]` — The data to embed.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key, used for pseudo-random selection of where to change the source image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If the image doesn't contain enough bit planes to store the
+///   inputted data together with the compressed cover planes.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * The bit planes BPCS accepts are the noise-like ones, which barely compress in photographs and scans. Reversible
+///   embedding suits images with structured complex regions (e.g. rendered graphics, text or dithering), use
+///   [estimate_reversible_capacity] to check an image before embedding.
+/// * [replace_data](crate::bpcs::replace_data) and [append_data](crate::bpcs::append_data) re-embed the payload
+///   without the compressed cover, so the original image can't be restored after them.
+pub fn embed_data_reversible<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    image_to_gray_code(source_image);
+
+    // select everything before writing anything, so a payload that doesn't fit leaves the image unchanged
+    let (layout, compressed_cover, container_length) =
+        match select_reversible_layout(source_image, data, min_alpha, rng_key) {
+            Ok(selection) => selection,
+            Err(e) => {
+                image_to_binary_code(source_image);
+                return Err(e.into());
+            }
+        };
+
+    let container =
+        build_reversible_container(data, compressed_cover, container_length, &mut rng());
+    write_data_into_gray_coded_image(
+        source_image,
+        &layout,
+        &mut container.into_iter(),
+        container_length,
+        min_alpha,
+        &mut rng(),
+    );
+
+    image_to_binary_code(source_image);
+
+    Ok(())
+}
+
+/// Extract the data of a reversible embedding, and restore the image it was embedded into
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::restore_cover;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let (data, original_image) = restore_cover(&vessel_image, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If the IV in the image contains invalid data. The most likely causes of this
+///   are an image that doesn't have data hidden in it or incorrect parameters.
+/// * `SteganographyError::CoverNotStored` — If the payload wasn't embedded with [embed_data_reversible].
+/// * `SteganographyError::InvalidCoverData` — If the stored container is malformed.
+///
+/// # Returns
+/// Returns `Result<(Vec<u8>, OwnedImageBuffer<P>), PixelveilError>`. If `Ok(...)` is returned, the contained values
+/// are the extracted data and a new image that is identical to the image before embedding.
+///
+/// # Notes
+/// The original image can only be restored from a lossless copy of the embedded image, e.g. a PNG, and only if no
+/// pixel of it was changed after embedding.
+pub fn restore_cover<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(Vec<u8>, OwnedImageBuffer<P>), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let mut selector =
+        PlaneSelector::new(source_image, min_alpha, rng_key).reading_binary_coded_image();
+    let (layout, message_remnant_length) =
+        read_embedding_layout(source_image, &mut selector, min_alpha)?;
+    if !layout.stores_cover {
+        return Err(SteganographyError::CoverNotStored.into());
+    }
+
+    let (conjugation_map, message_planes) =
+        read_message_planes(source_image, &layout, min_alpha, selector.coding())?;
+    let container = decode_message_planes(
+        &layout.encoding,
+        conjugation_map,
+        message_planes,
+        message_remnant_length,
+    );
+    let (data, compressed_cover) = split_reversible_container(container)?;
+    let cover_planes = decompress_planes(&compressed_cover, layout.all_plane_coords().count());
+
+    let mut cover_image: OwnedImageBuffer<P> = ImageBuffer::from_raw(
+        source_image.width(),
+        source_image.height(),
+        source_image.as_raw().to_vec(),
+    )
+    .unwrap();
+    image_to_gray_code(&mut cover_image);
+    for (coords, plane) in zip(layout.all_plane_coords(), cover_planes) {
+        write_plane_at(&mut cover_image, plane, *coords);
+    }
+    image_to_binary_code(&mut cover_image);
+
+    Ok((data, cover_image))
+}
+
+/// Estimates the maximum payload capacity for an image that can be embedded using [embed_data_reversible]
+///
+/// Every accepted bit plane of the image is compressed the way a reversible embedding compresses the planes it
+/// replaces, and the compressed size is subtracted from the capacity of a regular embedding.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::estimate_reversible_capacity;
+/// use image::RgbImage;
+///
+/// let img = RgbImage::new(512, 512);
+/// let capacity = estimate_reversible_capacity(&img, 0.3);
+/// ```
+///
+/// # Arguments
+/// The function takes in two arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The source image to analyze, it is left untouched.
+/// * `min_alpha: f64` — The BPCS complexity threshold (0.0–0.5).
+///
+/// # Errors
+/// This function does not return errors.
+///
+/// # Returns
+/// Returns a `u64` indicating the number of payload bytes that can be embedded reversibly into `source_image`, `0` if
+/// the accepted bit planes don't compress enough to leave room for a payload.
+///
+/// # Notes
+/// * The estimate assumes that every accepted bit plane is replaced. Smaller payloads replace fewer planes, but the
+///   ratio between the payload and the compressed cover stays about the same, so the estimate is close to the largest
+///   payload that fits.
+/// * Every accepted bit plane is compressed, so this is much slower than
+///   [estimate_maximum_capacity](crate::bpcs::estimate_maximum_capacity).
+/// * If `min_alpha` isn't between 0.0 and 0.5 nothing can be embedded with it, so 0 is returned.
+pub fn estimate_reversible_capacity<P, C>(source_image: &ImageBuffer<P, C>, min_alpha: f64) -> u64
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    if validate_min_alpha(min_alpha).is_err() {
+        return 0;
+    }
+
+    let mut accepted_planes: Vec<BitPlane> = Vec::new();
+    for bit_index in (0..P::Subpixel::BIT_DEPTH).rev() {
+        let accepted_plane_coords = collect_accepted_planes_at_bit_index(
+            source_image,
+            min_alpha,
+            bit_index,
+            ImageCoding::BinaryCode,
+        );
+        accepted_planes.extend(read_planes_from_image_and_coords(
+            source_image,
+            accepted_plane_coords,
+            ImageCoding::BinaryCode,
+        ));
+    }
+
+    let container_capacity =
+        max_message_plane_length(min_alpha, accepted_planes.len()) * BYTES_PER_PLANE;
+    container_capacity
+        .saturating_sub(PAYLOAD_LENGTH_BYTES + compress_planes(&accepted_planes).len()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reversible_container_is_circular() -> Result<(), Box<dyn std::error::Error>> {
+        let data = [4u8, 8, 15, 16, 23, 42];
+        let compressed_cover = vec![7u8; 20];
+        let container =
+            build_reversible_container(&data, compressed_cover.clone(), 64, &mut rand::rng());
+        assert_eq!(container.len(), 64);
+
+        let (extracted_data, extracted_cover) = split_reversible_container(container)?;
+        assert_eq!(extracted_data, data);
+        assert_eq!(extracted_cover[..compressed_cover.len()], compressed_cover);
+
+        assert!(split_reversible_container(vec![0u8; 7]).is_err());
+        let mut too_long = 100u64.to_be_bytes().to_vec();
+        too_long.extend([0u8; 99]);
+        assert!(split_reversible_container(too_long).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use rand::{RngCore, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use rand_chacha::ChaCha20Rng;
+
+// The PRNG that drives plane selection. The selected planes are part of the embedding format, so every format version
+//  pins its own algorithm, and the old algorithms are kept so images that were embedded with them can be extracted.
+pub(crate) enum SelectionRng {
+    // format version 0, rand's StdRng and shuffle. rand doesn't guarantee that they stay the same between releases, so
+    //  this is only used to extract images that were embedded before the format was versioned.
+    Legacy(StdRng),
+    // format version 1, ChaCha20 with our own shuffle and bounded integer generation, so no rand release can change it
+    ChaCha20(ChaCha20Rng),
+}
+
+impl SelectionRng {
+    pub(crate) fn new(format_version: u8, seed: [u8; 32]) -> Self {
+        match format_version {
+            0 => SelectionRng::Legacy(StdRng::from_seed(seed)),
+            1 => SelectionRng::ChaCha20(ChaCha20Rng::from_seed(seed)),
+            _ => panic!("Format version {format_version} has no plane selection PRNG."),
+        }
+    }
+
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        match self {
+            SelectionRng::Legacy(rng) => items.shuffle(rng),
+            SelectionRng::ChaCha20(rng) => {
+                // Fisher–Yates
+                for i in (1..items.len()).rev() {
+                    let j = uniform_below(rng, i as u64 + 1) as usize;
+                    items.swap(i, j);
+                }
+            }
+        }
+    }
+}
+
+// returns a uniformly distributed integer in 0..bound
+fn uniform_below(rng: &mut ChaCha20Rng, bound: u64) -> u64 {
+    assert_ne!(bound, 0);
+    // reject the lowest (2^64 mod bound) values, so the remaining range is a multiple of bound
+    let threshold = bound.wrapping_neg() % bound;
+    loop {
+        let value = rng.next_u64();
+        if value >= threshold {
+            return value % bound;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chacha20_selection_is_pinned() {
+        // these values are part of format version 1, if this test fails then images embedded with it can't be
+        //  extracted anymore
+        let mut rng = SelectionRng::new(1, [7u8; 32]);
+        let mut items = (0..16).collect::<Vec<u32>>();
+        rng.shuffle(&mut items);
+        assert_eq!(
+            items,
+            vec![8, 10, 15, 9, 13, 2, 0, 14, 3, 11, 12, 5, 1, 6, 7, 4]
+        );
+    }
+
+    #[test]
+    fn test_chacha20_keystream() {
+        // the first keystream words of ChaCha20 with an all zero key and nonce (RFC 7539, section A.1)
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        assert_eq!(rng.next_u64(), 0x903df1a0_ade0b876);
+    }
+
+    #[test]
+    fn test_uniform_below_bounds() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        for bound in [1u64, 2, 3, 7, 1 << 40, u64::MAX] {
+            for _ in 0..100 {
+                assert!(uniform_below(&mut rng, bound) < bound);
+            }
+        }
+    }
+}
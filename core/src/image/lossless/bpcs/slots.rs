@@ -0,0 +1,258 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        dynamic_prefix::validate_min_alpha,
+        extract_data_with_selector,
+        initialization_vector::calculate_message_plane_length_and_remnant,
+        plane_selection::{EmbeddingLayout, PlaneCoords, PlaneSelector},
+        write_data_into_gray_coded_image,
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::rng;
+use std::ops::{Deref, DerefMut};
+
+/// A payload that is embedded into its own slot by [embed_slots]
+#[derive(Debug, Clone, Copy)]
+pub struct SlotPayload<'a> {
+    /// The data to embed in the slot.
+    pub data: &'a [u8],
+    /// The randomization key of the slot, this is the only key that can extract the slot's data.
+    pub rng_key: [u8; 32],
+}
+
+fn mix_u64(mut z: u64) -> u64 {
+    // the SplitMix64 finalizer, it is fixed here so slot partitions never change between versions
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// The partition is deliberately unkeyed: every recipient has to compute it with only their own key, so it can only
+//  depend on the coordinates and the slot count. It keeps the slots apart, the keys are what protect them.
+pub(crate) fn slot_of_plane(coords: &PlaneCoords, slot_count: usize) -> usize {
+    let (x, y, channel, bit_index) = *coords;
+    let mut hash = mix_u64(slot_count as u64);
+    hash = mix_u64(hash ^ x as u64);
+    hash = mix_u64(hash ^ y as u64);
+    hash = mix_u64(hash ^ (((channel as u64) << 8) | bit_index as u64));
+    (hash % slot_count as u64) as usize
+}
+
+fn slot_selector<'a, P, C>(
+    source_image: &'a ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    slot_index: usize,
+    slot_count: usize,
+) -> PlaneSelector<'a, P, C>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    PlaneSelector::new(source_image, min_alpha, rng_key)
+        .with_plane_filter(move |coords| slot_of_plane(coords, slot_count) == slot_index)
+}
+
+/// Embed multiple payloads into an image using BPCS, each under its own key
+///
+/// The accepted bit planes of the image are partitioned into one slot per payload, so the payloads never overwrite
+/// each other. Each payload is embedded into its slot exactly like [embed_data](crate::bpcs::embed_data) would, using
+/// its own key. Each recipient can then extract their payload with [extract_slot], using only their own key.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::{SlotPayload, embed_slots};
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(512, 512);
+/// let payloads = [
+///     SlotPayload { data: b"for alice", rng_key: [1u8; 32] },
+///     SlotPayload { data: b"for bob", rng_key: [2u8; 32] },
+/// ];
+///
+/// embed_slots(&mut vessel_image, &payloads, 0.3).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `payloads: &[SlotPayload]` — The payloads to embed, each with its own key. The number of payloads is the slot
+///   count of the image.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient, shared by all slots.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `payloads` is empty, if two payloads have the same key, or if `min_alpha`
+///   isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If one of the slots doesn't contain enough bit planes to store
+///   its payload.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * Each slot gets roughly `1 / payloads.len()` of the image's capacity.
+/// * The slot count is not secret, recipients need it to extract their payload.
+/// * The partition of the planes into slots is public. It only depends on the coordinates of the planes and on the
+///   slot count, so anyone can tell which planes belong to which slot. It keeps the payloads from colliding but doesn't
+///   hide them: only the key of a slot selects the planes inside it that hold its payload, and only that key can
+///   extract it.
+pub fn embed_slots<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    payloads: &[SlotPayload],
+    min_alpha: f64,
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    if payloads.is_empty() {
+        return Err(PixelveilError::InvalidParameter(
+            "at least one payload must be embedded".to_string(),
+        ));
+    }
+    if payloads.iter().enumerate().any(|(i, payload)| {
+        payloads[..i]
+            .iter()
+            .any(|other| other.rng_key == payload.rng_key)
+    }) {
+        return Err(PixelveilError::InvalidParameter(
+            "every slot must have a different key".to_string(),
+        ));
+    }
+    let slot_count = payloads.len();
+
+    image_to_gray_code(source_image);
+
+    // select every slot before writing anything, so a slot that doesn't fit leaves the image unchanged
+    let mut layouts: Vec<EmbeddingLayout> = Vec::with_capacity(slot_count);
+    for (slot_index, payload) in payloads.iter().enumerate() {
+        let (message_plane_length, _) =
+            calculate_message_plane_length_and_remnant(payload.data.len());
+        let mut selector = slot_selector(
+            source_image,
+            min_alpha,
+            payload.rng_key,
+            slot_index,
+            slot_count,
+        );
+        let selection = EmbeddingLayout::select(&mut selector, min_alpha, message_plane_length);
+        drop(selector);
+        match selection {
+            Ok(layout) => layouts.push(layout),
+            Err(e) => {
+                image_to_binary_code(source_image);
+                return Err(e.into());
+            }
+        }
+    }
+
+    for (payload, layout) in payloads.iter().zip(layouts) {
+        write_data_into_gray_coded_image(
+            source_image,
+            &layout,
+            &mut payload.data.iter().copied(),
+            payload.data.len(),
+            min_alpha,
+            &mut rng(),
+        );
+    }
+
+    image_to_binary_code(source_image);
+
+    Ok(())
+}
+
+/// Extract a single payload from an image that was embedded using [embed_slots]
+///
+/// Every slot of the image is tried with the given key, and the data of the slot that the key opens is returned. The
+/// partition of the planes into slots is public (see [embed_slots]), so trying every slot doesn't need any key but the
+/// slot's own.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::extract_slot;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let extracted_data = extract_slot(&vessel_image, 0.3, [2u8; 32], 2).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The key of the slot to extract.
+/// * `slot_count: usize` — The number of payloads that were embedded into the image.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `slot_count` is zero, or if `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If none of the slots could be opened with the given key. The most likely
+///   causes of this are an incorrect key, `min_alpha` or `slot_count`.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the data of the slot
+/// that was opened with the key.
+pub fn extract_slot<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    slot_count: usize,
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    if slot_count == 0 {
+        return Err(PixelveilError::InvalidParameter(
+            "the slot count must be at least 1".to_string(),
+        ));
+    }
+
+    // the slot index isn't known to the recipient, so every slot is tried. an incorrect key produces an invalid IV
+    //  in all but a negligible fraction of cases
+    for slot_index in 0..slot_count {
+        let selector = slot_selector(source_image, min_alpha, rng_key, slot_index, slot_count)
+            .reading_binary_coded_image();
+        if let Ok(data) = extract_data_with_selector(source_image, selector, min_alpha) {
+            return Ok(data);
+        }
+    }
+
+    Err(SteganographyError::InvalidIVData(format!(
+        "None of the {slot_count} slots could be opened with the given key"
+    ))
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_partition_is_deterministic_and_spread() {
+        let slot_count = 3;
+        let mut slot_sizes = [0usize; 3];
+        for (x, y, channel, bit_index) in iproduct!(0..64u32, 0..64u32, 0..3u8, 0..8u8) {
+            let coords = (x * 8, y * 8, channel, bit_index);
+            let slot = slot_of_plane(&coords, slot_count);
+            assert_eq!(slot, slot_of_plane(&coords, slot_count));
+            slot_sizes[slot] += 1;
+        }
+
+        // every slot should get roughly a third of the planes
+        let total: usize = slot_sizes.iter().sum();
+        for size in slot_sizes {
+            assert!(size > total / 4 && size < total / 2);
+        }
+    }
+}
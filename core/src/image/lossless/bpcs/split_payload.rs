@@ -0,0 +1,385 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        capacity::image_data_capacity, dynamic_prefix::validate_min_alpha, embed_data, extract_data,
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel};
+use rand::{Rng, rng};
+use std::{
+    iter::zip,
+    ops::{Deref, DerefMut},
+};
+
+const PART_MAGIC: [u8; 4] = *b"PVSP";
+pub(crate) const PART_HEADER_LENGTH: usize = 28; // magic, payload ID, part index, part count, total length
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PartHeader {
+    pub(crate) payload_id: u64,
+    pub(crate) part_index: u32,
+    pub(crate) part_count: u32,
+    pub(crate) total_length: u64,
+}
+
+impl PartHeader {
+    pub(crate) fn to_bytes(self) -> [u8; PART_HEADER_LENGTH] {
+        let mut bytes = [0u8; PART_HEADER_LENGTH];
+        bytes[0..4].copy_from_slice(&PART_MAGIC);
+        bytes[4..12].copy_from_slice(&self.payload_id.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.part_index.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.part_count.to_be_bytes());
+        bytes[20..28].copy_from_slice(&self.total_length.to_be_bytes());
+        bytes
+    }
+
+    // splits a part into its header and its data, returns None if the part doesn't start with a valid header
+    pub(crate) fn parse(part: &[u8]) -> Option<(Self, &[u8])> {
+        if part.len() < PART_HEADER_LENGTH || part[0..4] != PART_MAGIC {
+            return None;
+        }
+        let header = PartHeader {
+            payload_id: u64::from_be_bytes(part[4..12].try_into().unwrap()),
+            part_index: u32::from_be_bytes(part[12..16].try_into().unwrap()),
+            part_count: u32::from_be_bytes(part[16..20].try_into().unwrap()),
+            total_length: u64::from_be_bytes(part[20..28].try_into().unwrap()),
+        };
+        if header.part_count == 0 || header.part_index >= header.part_count {
+            return None;
+        }
+        Some((header, &part[PART_HEADER_LENGTH..]))
+    }
+}
+
+// splits the data length between the images in proportion to their capacities
+fn part_lengths(data_length: usize, capacities: &[u64]) -> Vec<usize> {
+    let total_capacity: u64 = capacities.iter().sum();
+    if total_capacity == 0 {
+        let mut lengths = vec![0usize; capacities.len()];
+        lengths[0] = data_length;
+        return lengths;
+    }
+
+    let mut lengths: Vec<usize> = capacities
+        .iter()
+        .map(|capacity| (data_length as u128 * *capacity as u128 / total_capacity as u128) as usize)
+        .collect();
+
+    // hand out the bytes that were lost to rounding down to images that still have room for them. if the images can't
+    //  hold the data, whatever is left goes to the first image, which then fails to embed its part
+    let mut remaining = data_length - lengths.iter().sum::<usize>();
+    for (length, capacity) in zip(lengths.iter_mut(), capacities) {
+        if remaining == 0 {
+            break;
+        }
+        if (*length as u64) < *capacity {
+            *length += 1;
+            remaining -= 1;
+        }
+    }
+    lengths[0] += remaining;
+    lengths
+}
+
+/// Embed a payload across multiple images using BPCS
+///
+/// The payload is split into one part per image, in proportion to the capacity of each image. Every part is prefixed
+/// with a small header that holds a payload ID that is shared by all parts, the part's sequence number and the number
+/// of parts. The parts are then embedded using [embed_data](crate::bpcs::embed_data), all with the same key.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_across_images;
+/// use image::RgbImage;
+///
+/// let mut vessel_images = vec![RgbImage::new(512, 512), RgbImage::new(512, 512)];
+/// let data = vec![7u8; 100_000];
+///
+/// embed_data_across_images(&mut vessel_images, &data, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_images: &mut [ImageBuffer<P, C>]` — The images to split the payload across.
+/// * `data: &[u8]` — The payload.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient, shared by all images.
+/// * `rng_key: [u8; 32]` — The randomization key, shared by all images.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `source_images` is empty or holds more than `u32::MAX` images, or if
+///   `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If one of the images doesn't contain enough bit planes to store
+///   its part. This happens when the images together can't hold the payload, or when one of them can't even hold the
+///   header of its part.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source images will be modified instead of returning new ones.
+/// If an error is returned, all of the source images are left unchanged.
+///
+/// # Notes
+/// * Every part has an overhead of 28 bytes on top of the regular BPCS overhead.
+/// * Every image holds a part, even if it is empty, so all of the images are needed to reassemble the payload.
+/// * Use [extract_data_across_images](crate::bpcs::extract_data_across_images) to reassemble the payload, the images
+///   can be given in any order.
+pub fn embed_data_across_images<P, C>(
+    source_images: &mut [ImageBuffer<P, C>],
+    data: &[u8],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    if source_images.is_empty() {
+        return Err(PixelveilError::InvalidParameter(
+            "at least one image must be given".to_string(),
+        ));
+    }
+    let part_count: u32 = source_images.len().try_into().map_err(|_| {
+        PixelveilError::InvalidParameter(
+            "a payload can't be split into more than u32::MAX parts".to_string(),
+        )
+    })?;
+
+    let capacities: Vec<u64> = source_images
+        .iter()
+        .map(|image| {
+            image_data_capacity(image, min_alpha).saturating_sub(PART_HEADER_LENGTH) as u64
+        })
+        .collect();
+    let lengths = part_lengths(data.len(), &capacities);
+
+    let payload_id: u64 = rng().random();
+    let mut remaining_data = data;
+    let mut parts: Vec<Vec<u8>> = Vec::with_capacity(source_images.len());
+
+    for (part_index, length) in lengths.into_iter().enumerate() {
+        let (part_data, rest) = remaining_data.split_at(length);
+        remaining_data = rest;
+
+        let header = PartHeader {
+            payload_id,
+            part_index: part_index as u32,
+            part_count,
+            total_length: data.len() as u64,
+        };
+        let mut part = header.to_bytes().to_vec();
+        part.extend_from_slice(part_data);
+        parts.push(part);
+    }
+
+    embed_part_per_image(source_images, parts, min_alpha, rng_key)
+}
+
+// embeds every part into the image at the same index, if one of them fails all of the images are left unchanged
+pub(crate) fn embed_part_per_image<P, C>(
+    source_images: &mut [ImageBuffer<P, C>],
+    parts: Vec<Vec<u8>>,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    assert_eq!(source_images.len(), parts.len());
+    let mut backups: Vec<Vec<P::Subpixel>> = Vec::with_capacity(source_images.len());
+
+    for (image_index, part) in parts.into_iter().enumerate() {
+        let image = &mut source_images[image_index];
+        backups.push(image.as_raw().to_vec());
+        let part_length = part.len();
+        if let Err(e) = embed_data(
+            image,
+            &mut part.into_iter(),
+            part_length,
+            min_alpha,
+            rng_key,
+        ) {
+            // embed_data leaves the failed image unchanged, so only the images before it are restored
+            backups.pop();
+            for (image, backup) in source_images.iter_mut().zip(backups) {
+                image.copy_from_slice(&backup);
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reassemble a payload that was embedded across multiple images using [embed_data_across_images]
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::extract_data_across_images;
+/// use image::RgbImage;
+///
+/// let vessel_images = vec![RgbImage::new(512, 512), RgbImage::new(512, 512)];
+///
+/// let data = extract_data_across_images(&vessel_images, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_images: &[ImageBuffer<P, C>]` — The images that hold the parts of the payload, in any order. They are
+///   left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5, or if `source_images` is empty.
+/// * `SteganographyError::InvalidIVData` — If one of the images doesn't have data embedded in it with the key and
+///   `min_alpha`.
+/// * `SteganographyError::InconsistentPayloadParts` — If one of the images doesn't hold a payload part, the parts
+///   belong to different payloads or a part is given more than once.
+/// * `SteganographyError::MissingPayloadParts` — If the payload was split into more parts than there are images.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the reassembled
+/// payload.
+pub fn extract_data_across_images<P, C>(
+    source_images: &[ImageBuffer<P, C>],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    if source_images.is_empty() {
+        return Err(PixelveilError::InvalidParameter(
+            "at least one image must be given".to_string(),
+        ));
+    }
+
+    let mut first_header: Option<PartHeader> = None;
+    let mut parts: Vec<Option<Vec<u8>>> = Vec::new();
+
+    for (image_index, image) in source_images.iter().enumerate() {
+        let part = extract_data(image, min_alpha, rng_key)?;
+
+        let (header, part_data) = PartHeader::parse(&part).ok_or_else(|| {
+            SteganographyError::InconsistentPayloadParts(format!(
+                "image {image_index} doesn't hold a payload part"
+            ))
+        })?;
+
+        let expected_header = *first_header.get_or_insert(header);
+        if (header.payload_id, header.part_count, header.total_length)
+            != (
+                expected_header.payload_id,
+                expected_header.part_count,
+                expected_header.total_length,
+            )
+        {
+            return Err(SteganographyError::InconsistentPayloadParts(format!(
+                "image {image_index} holds a part of a different payload"
+            ))
+            .into());
+        }
+
+        if parts.is_empty() {
+            // the part count is read from the image, so it is checked against the images before it is allocated
+            if header.part_count as usize > source_images.len() {
+                return Err(SteganographyError::MissingPayloadParts(
+                    header.part_count as usize,
+                    source_images.len(),
+                )
+                .into());
+            }
+            parts = vec![None; header.part_count as usize];
+        }
+        let slot = &mut parts[header.part_index as usize];
+        if slot.is_some() {
+            return Err(SteganographyError::InconsistentPayloadParts(format!(
+                "part {} was given more than once",
+                header.part_index
+            ))
+            .into());
+        }
+        *slot = Some(part_data.to_vec());
+    }
+
+    // every image holds a different part and there are no more images than parts, so every part was found
+    let header = first_header.expect("At least one image was read.");
+    let data: Vec<u8> = parts.into_iter().flatten().flatten().collect();
+    if data.len() as u64 != header.total_length {
+        return Err(SteganographyError::InconsistentPayloadParts(format!(
+            "the parts hold {} bytes, but the payload is {} bytes long",
+            data.len(),
+            header.total_length
+        ))
+        .into());
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_header_round_trip() {
+        let header = PartHeader {
+            payload_id: 0x0123456789abcdef,
+            part_index: 2,
+            part_count: 3,
+            total_length: 1_000,
+        };
+        let mut part = header.to_bytes().to_vec();
+        part.extend_from_slice(&[1, 2, 3]);
+
+        assert_eq!(PartHeader::parse(&part), Some((header, &[1u8, 2, 3][..])));
+        assert_eq!(PartHeader::parse(&part[1..]), None);
+        assert_eq!(PartHeader::parse(&[]), None);
+    }
+
+    #[test]
+    fn test_part_lengths() {
+        assert_eq!(part_lengths(100, &[300, 100]), vec![75, 25]);
+        assert_eq!(part_lengths(10, &[1, 1, 1]), vec![4, 3, 3]);
+        assert_eq!(part_lengths(10, &[0, 0]), vec![10, 0]);
+        assert_eq!(part_lengths(0, &[5, 5]), vec![0, 0]);
+        // the bytes lost to rounding only go to images with room for them
+        assert_eq!(part_lengths(3, &[0, 2, 2]), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_part_count_is_checked_before_allocating() -> Result<(), Box<dyn std::error::Error>> {
+        let (min_alpha, rng_key) = (0.3, [4u8; 32]);
+        let header = PartHeader {
+            payload_id: 7,
+            part_index: 0,
+            part_count: u32::MAX,
+            total_length: u64::MAX,
+        };
+        let part = header.to_bytes();
+        let mut vessel_image = image::open("tests/assets/test_circular_bpcs_vessel.png")?.to_rgb8();
+        embed_data(
+            &mut vessel_image,
+            &mut part.into_iter(),
+            part.len(),
+            min_alpha,
+            rng_key,
+        )?;
+
+        assert!(matches!(
+            extract_data_across_images(&[vessel_image], min_alpha, rng_key),
+            Err(PixelveilError::Steganography(
+                SteganographyError::MissingPayloadParts(part_count, 1)
+            )) if part_count == u32::MAX as usize
+        ));
+
+        Ok(())
+    }
+}
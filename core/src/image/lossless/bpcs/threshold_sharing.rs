@@ -0,0 +1,274 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        dynamic_prefix::validate_min_alpha, extract_data, split_payload::embed_part_per_image,
+    },
+    utils::{
+        image_utils::BitPlaneSubpixel,
+        secret_sharing_utils::{combine_shares, split_secret},
+    },
+};
+use image::{ImageBuffer, Pixel};
+use rand::{Rng, rng};
+use std::ops::{Deref, DerefMut};
+
+const SHARE_MAGIC: [u8; 4] = *b"PVSS";
+const SHARE_HEADER_LENGTH: usize = 15; // magic, secret ID, share index, threshold, share count
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ShareHeader {
+    secret_id: u64,
+    share_index: u8,
+    threshold: u8,
+    share_count: u8,
+}
+
+impl ShareHeader {
+    fn to_bytes(self) -> [u8; SHARE_HEADER_LENGTH] {
+        let mut bytes = [0u8; SHARE_HEADER_LENGTH];
+        bytes[0..4].copy_from_slice(&SHARE_MAGIC);
+        bytes[4..12].copy_from_slice(&self.secret_id.to_be_bytes());
+        bytes[12] = self.share_index;
+        bytes[13] = self.threshold;
+        bytes[14] = self.share_count;
+        bytes
+    }
+
+    // splits a share into its header and its data, returns None if the share doesn't start with a valid header
+    fn parse(share: &[u8]) -> Option<(Self, &[u8])> {
+        if share.len() < SHARE_HEADER_LENGTH || share[0..4] != SHARE_MAGIC {
+            return None;
+        }
+        let header = ShareHeader {
+            secret_id: u64::from_be_bytes(share[4..12].try_into().unwrap()),
+            share_index: share[12],
+            threshold: share[13],
+            share_count: share[14],
+        };
+        if header.share_index == 0
+            || header.share_index > header.share_count
+            || header.threshold == 0
+            || header.threshold > header.share_count
+        {
+            return None;
+        }
+        Some((header, &share[SHARE_HEADER_LENGTH..]))
+    }
+}
+
+// the distinct shares of one secret that were found, they agree on its header and their length
+struct SecretShares {
+    header: ShareHeader,
+    shares: Vec<(u8, Vec<u8>)>,
+}
+
+/// Share a secret across multiple images using Shamir's secret sharing and BPCS
+///
+/// The secret is split into one share per image, such that any `threshold` of the images recover the secret, and fewer
+/// than `threshold` of them reveal nothing about it. Each share is prefixed with a small header that holds its share
+/// index and is embedded using [embed_data](crate::bpcs::embed_data), all with the same key.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_secret_shares;
+/// use image::RgbImage;
+///
+/// let mut vessel_images = vec![RgbImage::new(512, 512); 5];
+/// let secret = b"a very sensitive key";
+///
+/// // any 3 of the 5 images recover the secret
+/// embed_secret_shares(&mut vessel_images, secret, 3, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in five arguments:
+/// * `source_images: &mut [ImageBuffer<P, C>]` — The images to share the secret across, one share per image.
+/// * `secret: &[u8]` — The secret.
+/// * `threshold: u8` — The number of images that are needed to recover the secret.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient, shared by all images.
+/// * `rng_key: [u8; 32]` — The randomization key, shared by all images.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `source_images` holds more than 255 images, if `threshold` is zero or
+///   larger than the number of images, or if `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientPlaneNumber` — If one of the images doesn't contain enough bit planes to store
+///   its share.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source images will be modified instead of returning new ones.
+/// If an error is returned, all of the source images are left unchanged.
+///
+/// # Notes
+/// * Every share is as long as the secret, plus a 15 byte header. Every image has to be able to hold a full share.
+/// * The sharing is done over GF(256) byte by byte, so it is meant for short secrets such as keys.
+/// * Use [recover_secret_from_shares](crate::bpcs::recover_secret_from_shares) to recover the secret.
+pub fn embed_secret_shares<P, C>(
+    source_images: &mut [ImageBuffer<P, C>],
+    secret: &[u8],
+    threshold: u8,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let share_count: u8 = source_images.len().try_into().map_err(|_| {
+        PixelveilError::InvalidParameter(
+            "a secret can't be shared across more than 255 images".to_string(),
+        )
+    })?;
+    if threshold == 0 || threshold > share_count {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "the threshold must be between 1 and the number of images ({share_count}), got {threshold}"
+        )));
+    }
+
+    let mut rng = rng();
+    let secret_id: u64 = rng.random();
+    let shares: Vec<Vec<u8>> = split_secret(secret, threshold, share_count, &mut rng)
+        .into_iter()
+        .map(|(share_index, share_data)| {
+            let header = ShareHeader {
+                secret_id,
+                share_index,
+                threshold,
+                share_count,
+            };
+            let mut share = header.to_bytes().to_vec();
+            share.extend(share_data);
+            share
+        })
+        .collect();
+
+    embed_part_per_image(source_images, shares, min_alpha, rng_key)
+}
+
+/// Recover a secret that was shared across multiple images using [embed_secret_shares]
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::recover_secret_from_shares;
+/// use image::RgbImage;
+///
+/// let vessel_images = vec![RgbImage::new(512, 512); 3];
+///
+/// let secret = recover_secret_from_shares(&vessel_images, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_images: &[ImageBuffer<P, C>]` — The images that hold the shares, in any order. They are left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientShares` — If no secret has as many distinct shares in the images as its
+///   threshold. The error holds the threshold and the number of shares of the secret with the most shares.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the secret.
+///
+/// # Notes
+/// * Images that don't hold a share with the key and `min_alpha`, or whose share is malformed, are skipped.
+/// * Images that hold the same share are only counted once.
+/// * Shares of different secrets aren't mixed. If the images hold enough shares of more than one secret, the secret
+///   with the earliest share in `source_images` is recovered.
+pub fn recover_secret_from_shares<P, C>(
+    source_images: &[ImageBuffer<P, C>],
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let mut secrets: Vec<SecretShares> = Vec::new();
+
+    for image in source_images {
+        // an image that doesn't hold a share (e.g. one that was never embedded into) can't stop the recovery, so it is
+        //  skipped
+        let Ok(share) = extract_data(image, min_alpha, rng_key) else {
+            continue;
+        };
+        let Some((header, share_data)) = ShareHeader::parse(&share) else {
+            continue;
+        };
+
+        let secret = secrets.iter_mut().find(|secret| {
+            (
+                secret.header.secret_id,
+                secret.header.threshold,
+                secret.header.share_count,
+            ) == (header.secret_id, header.threshold, header.share_count)
+                && secret.shares[0].1.len() == share_data.len()
+        });
+        match secret {
+            Some(secret) => {
+                if !secret.shares.iter().any(|(x, _)| *x == header.share_index) {
+                    secret
+                        .shares
+                        .push((header.share_index, share_data.to_vec()));
+                }
+            }
+            None => secrets.push(SecretShares {
+                header,
+                shares: vec![(header.share_index, share_data.to_vec())],
+            }),
+        }
+    }
+
+    let Some(secret) = secrets
+        .iter()
+        .find(|secret| secret.shares.len() >= secret.header.threshold as usize)
+    else {
+        // report the secret that came closest to being recovered
+        let (threshold, found) = secrets
+            .iter()
+            .max_by_key(|secret| secret.shares.len())
+            .map_or((1, 0), |secret| {
+                (secret.header.threshold as usize, secret.shares.len())
+            });
+        return Err(SteganographyError::InsufficientShares(threshold, found).into());
+    };
+
+    let shares: Vec<(u8, &[u8])> = secret
+        .shares
+        .iter()
+        .take(secret.header.threshold as usize)
+        .map(|(x, share)| (*x, share.as_slice()))
+        .collect();
+    Ok(combine_shares(&shares))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_header_round_trip() {
+        let header = ShareHeader {
+            secret_id: 42,
+            share_index: 3,
+            threshold: 2,
+            share_count: 4,
+        };
+        let mut share = header.to_bytes().to_vec();
+        share.push(9);
+
+        assert_eq!(ShareHeader::parse(&share), Some((header, &[9u8][..])));
+
+        let invalid_index = ShareHeader {
+            share_index: 0,
+            ..header
+        };
+        assert_eq!(ShareHeader::parse(&invalid_index.to_bytes()), None);
+    }
+}
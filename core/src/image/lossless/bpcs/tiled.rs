@@ -0,0 +1,547 @@
+use crate::{
+    errors::{PixelveilError, SteganographyError},
+    image::lossless::bpcs::{
+        bit_plane::{BYTES_PER_PLANE, ImageCoding, PLANE_SIZE},
+        capacity::max_message_plane_length,
+        dynamic_prefix::validate_min_alpha,
+        embed_data_into_gray_coded_image, extract_data,
+        plane_selection::count_accepted_planes_with_coding,
+    },
+    utils::image_utils::{BitPlaneSubpixel, image_to_binary_code, image_to_gray_code},
+};
+use image::{ImageBuffer, Pixel};
+use rand::rng;
+use sha2::{Digest, Sha256};
+use std::ops::{Deref, DerefMut};
+
+const TILE_KEY_DOMAIN: &[u8] = b"pixelveil/bpcs/tile";
+const TILE_HEADER_LENGTH: usize = 1; // a flag that marks the final tile of the payload
+
+const FINAL_TILE: u8 = 1;
+const INTERMEDIATE_TILE: u8 = 0;
+
+// every tile gets its own key, so tiles with identical content don't get identical selections
+fn tile_rng_key(rng_key: [u8; 32], tile_index: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(TILE_KEY_DOMAIN);
+    hasher.update(rng_key);
+    hasher.update(tile_index.to_be_bytes());
+    hasher.finalize().into()
+}
+
+// The number of payload bytes a tile can hold, or None if it can't hold a header and a byte of data. Embedding never
+//  changes which planes are accepted, so the embedder and the extractor always agree on it.
+fn tile_data_capacity<P, C>(
+    tile: &ImageBuffer<P, C>,
+    min_alpha: f64,
+    coding: ImageCoding,
+) -> Option<usize>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let accepted_planes_num = count_accepted_planes_with_coding(tile, min_alpha, coding) as usize;
+    let capacity = max_message_plane_length(min_alpha, accepted_planes_num) * BYTES_PER_PLANE;
+    match capacity > TILE_HEADER_LENGTH {
+        true => Some(capacity - TILE_HEADER_LENGTH),
+        false => None,
+    }
+}
+
+// the number of subpixels in a tile of tile_height rows, the last tile of an image may be shorter
+fn tile_length<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    tile_height: u32,
+) -> Result<usize, PixelveilError>
+where
+    P: Pixel,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    if tile_height == 0 || !tile_height.is_multiple_of(PLANE_SIZE) {
+        return Err(PixelveilError::InvalidParameter(format!(
+            "the tile height must be a positive multiple of {PLANE_SIZE}, got {tile_height}"
+        )));
+    }
+    Ok(source_image.width() as usize * P::CHANNEL_COUNT as usize * tile_height as usize)
+}
+
+/// Embeds a payload into an image one tile at a time, for images that are too large to process at once
+///
+/// A tile is a horizontal strip of the image, such as a block of rows that a streaming decoder produced. Each tile
+/// holds its own BPCS embedding with a key that is derived from the key and the index of the tile, so only a single
+/// tile has to be in memory at a time. If every tile is measured with [measure_tile](TiledEmbedder::measure_tile)
+/// first, the payload is spread over all the tiles in proportion to their capacity. Otherwise it is written greedily:
+/// every tile is filled to its capacity before the next one is used, and tiles after the end of the payload are left
+/// untouched.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::TiledEmbedder;
+/// use image::RgbImage;
+///
+/// let data = b"a payload that is spread over a huge image";
+/// let mut embedder = TiledEmbedder::new(data, 0.3, [0u8; 32]).unwrap();
+///
+/// // the tiles would normally come from a row-streaming decoder, which is run once to measure them, and once more to
+/// // embed them and pass them to a row-streaming encoder
+/// let mut tiles = vec![RgbImage::new(40_000, 256); 4];
+/// for tile in tiles.iter() {
+///     embedder.measure_tile(tile);
+/// }
+/// for tile in tiles.iter_mut() {
+///     embedder.embed_tile(tile).unwrap();
+/// }
+/// embedder.finish().unwrap();
+/// ```
+///
+/// # Notes
+/// * Every tile must be given in order, and the same tiles must be given to a [TiledExtractor] in the same order.
+/// * The height of every tile but the last should be a multiple of 8, so the bit planes of the tiles line up with the
+///   bit planes of the whole image.
+/// * A greedy embedding changes the top of the image and leaves the rest of it untouched, which makes the payload
+///   easier to detect. Measure the tiles first unless they can only be decoded once.
+/// * Tiles are modified as soon as they are given, so if the payload doesn't fit, the tiles that were already given
+///   hold a partial payload. Use [embed_data_tiled] to check the capacity before anything is written.
+pub struct TiledEmbedder<'a> {
+    remaining_data: &'a [u8],
+    data_length: usize,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    tile_index: u64,
+    measured_capacity: usize,
+    finished: bool,
+}
+
+impl<'a> TiledEmbedder<'a> {
+    /// Create an embedder for a payload
+    ///
+    /// # Arguments
+    /// The function takes in three arguments:
+    /// * `data: &[u8]` — The data to embed.
+    /// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+    /// * `rng_key: [u8; 32]` — The randomization key, the key of every tile is derived from it.
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+    ///
+    /// # Returns
+    /// Returns `Result<TiledEmbedder, PixelveilError>`. If `Ok(...)` is returned, the contained value is an embedder
+    /// that expects the first tile of the image.
+    pub fn new(data: &'a [u8], min_alpha: f64, rng_key: [u8; 32]) -> Result<Self, PixelveilError> {
+        validate_min_alpha(min_alpha)?;
+        Ok(TiledEmbedder {
+            remaining_data: data,
+            data_length: data.len(),
+            min_alpha,
+            rng_key,
+            tile_index: 0,
+            measured_capacity: 0,
+            finished: false,
+        })
+    }
+
+    /// Measure the capacity of the next tile of the image, so the payload can be spread over all the tiles
+    ///
+    /// Every tile of the image should be measured, in order, before the first tile is embedded.
+    ///
+    /// # Arguments
+    /// The function takes in one argument:
+    /// * `tile: &ImageBuffer<P, C>` — The next tile of the image, it is left untouched.
+    ///
+    /// # Errors
+    /// This function does not return errors.
+    ///
+    /// # Returns
+    /// Returns `usize`, the number of payload bytes the tile can hold. Tiles that are too flat to hold anything can
+    /// hold 0 bytes.
+    pub fn measure_tile<P, C>(&mut self, tile: &ImageBuffer<P, C>) -> usize
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        let capacity =
+            tile_data_capacity(tile, self.min_alpha, ImageCoding::BinaryCode).unwrap_or(0);
+        self.measured_capacity += capacity;
+        capacity
+    }
+
+    // The number of payload bytes that go into a tile of the given capacity. Every tile takes its share of the payload
+    //  by its part of the measured capacity, rounded up, so the shares of the measured tiles add up to the whole
+    //  payload if it fits.
+    fn part_length(&self, capacity: usize) -> usize {
+        let share = match self.measured_capacity {
+            0 => capacity,
+            measured_capacity => (capacity as u128 * self.data_length as u128)
+                .div_ceil(measured_capacity as u128) as usize,
+        };
+        share.min(capacity).min(self.remaining_data.len())
+    }
+
+    /// Embed the next part of the payload into the next tile of the image
+    ///
+    /// # Arguments
+    /// The function takes in one argument:
+    /// * `tile: &mut ImageBuffer<P, C>` — The next tile of the image.
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `SteganographyError::InsufficientPlaneNumber` — If the tile can't hold the part that was sized for it. The
+    ///   tile is left unchanged.
+    ///
+    /// # Returns
+    /// Returns `Result<usize, PixelveilError>`. If `Ok(...)` is returned, the contained value is the number of payload
+    /// bytes that were embedded into the tile. Tiles that are too flat to hold anything, and tiles after the end of the
+    /// payload, are left untouched and hold 0 bytes.
+    pub fn embed_tile<P, C>(
+        &mut self,
+        tile: &mut ImageBuffer<P, C>,
+    ) -> Result<usize, PixelveilError>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]> + DerefMut,
+    {
+        if self.finished {
+            return Ok(0);
+        }
+        let tile_key = tile_rng_key(self.rng_key, self.tile_index);
+        self.tile_index += 1;
+
+        image_to_gray_code(tile);
+
+        let Some(capacity) = tile_data_capacity(tile, self.min_alpha, ImageCoding::GrayCode) else {
+            image_to_binary_code(tile);
+            return Ok(0);
+        };
+        let part_length = self.part_length(capacity);
+        let (part_data, rest) = self.remaining_data.split_at(part_length);
+        let is_final = rest.is_empty();
+
+        let mut part = Vec::with_capacity(TILE_HEADER_LENGTH + part_length);
+        part.push(match is_final {
+            true => FINAL_TILE,
+            false => INTERMEDIATE_TILE,
+        });
+        part.extend_from_slice(part_data);
+
+        let embedded = embed_data_into_gray_coded_image(
+            tile,
+            &mut part.iter().copied(),
+            part.len(),
+            self.min_alpha,
+            tile_key,
+            &mut rng(),
+        );
+
+        image_to_binary_code(tile);
+        embedded?;
+
+        self.remaining_data = rest;
+        self.finished = is_final;
+        Ok(part_length)
+    }
+
+    /// Check whether the whole payload was embedded
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Finish the embedding, after the last tile of the image was given
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `SteganographyError::InsufficientTileCapacity` — If the tiles couldn't hold the whole payload.
+    ///
+    /// # Returns
+    /// Returns `Result<(), PixelveilError>`.
+    pub fn finish(self) -> Result<(), PixelveilError> {
+        match self.finished {
+            true => Ok(()),
+            false => {
+                Err(SteganographyError::InsufficientTileCapacity(self.remaining_data.len()).into())
+            }
+        }
+    }
+}
+
+/// Extracts a payload that was embedded with a [TiledEmbedder], one tile at a time
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::TiledExtractor;
+/// use image::RgbImage;
+///
+/// let mut extractor = TiledExtractor::new(0.3, [0u8; 32]).unwrap();
+///
+/// let tiles = vec![RgbImage::new(40_000, 256); 4];
+/// for tile in tiles.iter() {
+///     extractor.extract_tile(tile).unwrap();
+///     if extractor.is_finished() {
+///         break; // the rest of the image doesn't have to be decoded
+///     }
+/// }
+/// let data = extractor.finish().unwrap();
+/// ```
+///
+/// # Notes
+/// The tiles are only borrowed, so the memory usage is bounded by the size of a tile and the size of the payload.
+pub struct TiledExtractor {
+    min_alpha: f64,
+    rng_key: [u8; 32],
+    tile_index: u64,
+    data: Vec<u8>,
+    finished: bool,
+}
+
+impl TiledExtractor {
+    /// Create an extractor
+    ///
+    /// # Arguments
+    /// The function takes in two arguments:
+    /// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+    /// * `rng_key: [u8; 32]` — The randomization key.
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+    ///
+    /// # Returns
+    /// Returns `Result<TiledExtractor, PixelveilError>`. If `Ok(...)` is returned, the contained value is an extractor
+    /// that expects the first tile of the image.
+    pub fn new(min_alpha: f64, rng_key: [u8; 32]) -> Result<Self, PixelveilError> {
+        validate_min_alpha(min_alpha)?;
+        Ok(TiledExtractor {
+            min_alpha,
+            rng_key,
+            tile_index: 0,
+            data: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Extract the part of the payload that the next tile of the image holds
+    ///
+    /// # Arguments
+    /// The function takes in one argument:
+    /// * `tile: &ImageBuffer<P, C>` — The next tile of the image, it is left untouched.
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `SteganographyError::InvalidIVData` — If a tile that should hold a part doesn't have data embedded in it with
+    ///   the key and `min_alpha`.
+    /// * `SteganographyError::InconsistentPayloadParts` — If the data in a tile isn't a part of a tiled payload.
+    ///
+    /// # Returns
+    /// Returns `Result<(), PixelveilError>`. Tiles after the final part of the payload are ignored.
+    pub fn extract_tile<P, C>(&mut self, tile: &ImageBuffer<P, C>) -> Result<(), PixelveilError>
+    where
+        P: Pixel<Subpixel: BitPlaneSubpixel>,
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        if self.finished {
+            return Ok(());
+        }
+        let tile_index = self.tile_index;
+        let tile_key = tile_rng_key(self.rng_key, tile_index);
+        self.tile_index += 1;
+
+        if tile_data_capacity(tile, self.min_alpha, ImageCoding::BinaryCode).is_none() {
+            return Ok(());
+        }
+
+        let part = extract_data(tile, self.min_alpha, tile_key)?;
+        match part.split_first() {
+            Some((&FINAL_TILE, part_data)) => {
+                self.data.extend_from_slice(part_data);
+                self.finished = true;
+            }
+            Some((&INTERMEDIATE_TILE, part_data)) => self.data.extend_from_slice(part_data),
+            _ => {
+                return Err(SteganographyError::InconsistentPayloadParts(format!(
+                    "tile {tile_index} doesn't hold a part of a tiled payload"
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the final part of the payload was extracted
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Finish the extraction and return the payload
+    ///
+    /// # Errors
+    /// The errors that can be returned are:
+    /// * `SteganographyError::IncompleteTiledPayload` — If the final part of the payload wasn't found in the tiles that
+    ///   were given.
+    ///
+    /// # Returns
+    /// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the payload.
+    pub fn finish(self) -> Result<Vec<u8>, PixelveilError> {
+        match self.finished {
+            true => Ok(self.data),
+            false => {
+                Err(SteganographyError::IncompleteTiledPayload(self.tile_index as usize).into())
+            }
+        }
+    }
+}
+
+/// Embed data into an image using tiled BPCS
+///
+/// The image is processed in horizontal tiles of `tile_height` rows using a [TiledEmbedder], which bounds the memory
+/// that BPCS needs by the size of a tile. The tiles are borrowed from the image's buffer, so they aren't copied.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::embed_data_tiled;
+/// use image::RgbImage;
+///
+/// let mut vessel_image = RgbImage::new(40_000, 30_000);
+/// let data = b"a payload for a gigapixel image";
+///
+/// embed_data_tiled(&mut vessel_image, data, 256, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in five arguments:
+/// * `source_image: &mut ImageBuffer<P, C>` — A mutable reference to the source image.
+/// * `data: &[u8]` — The data to embed.
+/// * `tile_height: u32` — The number of rows in a tile.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `tile_height` isn't a positive multiple of 8, or if `min_alpha` isn't
+///   between 0.0 and 0.5.
+/// * `SteganographyError::InsufficientTileCapacity` — If the tiles can't hold the whole payload.
+///
+/// # Returns
+/// Returns `Result<(), PixelveilError>`, the source image will be modified instead of returning a new one.
+/// If an error is returned, the source image is left unchanged.
+///
+/// # Notes
+/// * The capacity of the tiles is measured in a first pass over the image, before anything is written, and the
+///   payload is spread over all the tiles in proportion to their capacity.
+/// * Use [extract_data_tiled] or a [TiledExtractor] with the same tile height to extract the data.
+pub fn embed_data_tiled<P, C>(
+    source_image: &mut ImageBuffer<P, C>,
+    data: &[u8],
+    tile_height: u32,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<(), PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let (width, height) = source_image.dimensions();
+    let tile_length = tile_length(source_image, tile_height)?;
+    let row_length = tile_length / tile_height as usize;
+    let buffer: &mut [P::Subpixel] = source_image;
+    let buffer = &mut buffer[..row_length * height as usize];
+
+    // every tile is measured before anything is written, so the payload is spread over all of them
+    let mut embedder = TiledEmbedder::new(data, min_alpha, rng_key)?;
+    let mut capacity = 0usize;
+    for tile_buffer in buffer.chunks(tile_length) {
+        let rows = (tile_buffer.len() / row_length) as u32;
+        let tile = ImageBuffer::<P, &[P::Subpixel]>::from_raw(width, rows, tile_buffer).unwrap();
+        capacity += embedder.measure_tile(&tile);
+    }
+    if capacity < data.len() {
+        return Err(SteganographyError::InsufficientTileCapacity(data.len() - capacity).into());
+    }
+
+    for tile_buffer in buffer.chunks_mut(tile_length) {
+        let rows = (tile_buffer.len() / row_length) as u32;
+        let mut tile =
+            ImageBuffer::<P, &mut [P::Subpixel]>::from_raw(width, rows, tile_buffer).unwrap();
+        embedder.embed_tile(&mut tile)?;
+        if embedder.is_finished() {
+            break;
+        }
+    }
+    embedder.finish()
+}
+
+/// Extract data from an image using tiled BPCS
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::extract_data_tiled;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(40_000, 30_000);
+///
+/// let data = extract_data_tiled(&vessel_image, 256, 0.3, [0u8; 32]).unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in four arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to extract data from, it is left untouched.
+/// * `tile_height: u32` — The number of rows in a tile, must be the tile height the data was embedded with.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+/// * `rng_key: [u8; 32]` — The randomization key.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `tile_height` isn't a positive multiple of 8, or if `min_alpha` isn't
+///   between 0.0 and 0.5.
+/// * `SteganographyError::InvalidIVData` — If a tile that should hold a part doesn't have data embedded in it with the
+///   key and `min_alpha`.
+/// * `SteganographyError::InconsistentPayloadParts` — If the data in a tile isn't a part of a tiled payload.
+/// * `SteganographyError::IncompleteTiledPayload` — If the image ended before the final part of the payload.
+///
+/// # Returns
+/// Returns `Result<Vec<u8>, PixelveilError>`. If `Ok(...)` is returned, the contained value is the payload.
+///
+/// # Notes
+/// The tiles are read until the final part of the payload is found.
+pub fn extract_data_tiled<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    tile_height: u32,
+    min_alpha: f64,
+    rng_key: [u8; 32],
+) -> Result<Vec<u8>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let (width, height) = source_image.dimensions();
+    let tile_length = tile_length(source_image, tile_height)?;
+    let row_length = tile_length / tile_height as usize;
+    let buffer = &source_image.as_raw()[..row_length * height as usize];
+
+    let mut extractor = TiledExtractor::new(min_alpha, rng_key)?;
+    for tile_buffer in buffer.chunks(tile_length) {
+        let rows = (tile_buffer.len() / row_length) as u32;
+        let tile = ImageBuffer::<P, &[P::Subpixel]>::from_raw(width, rows, tile_buffer).unwrap();
+        extractor.extract_tile(&tile)?;
+        if extractor.is_finished() {
+            break;
+        }
+    }
+    extractor.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_rng_keys_differ() {
+        let rng_key = [3u8; 32];
+        assert_eq!(tile_rng_key(rng_key, 0), tile_rng_key(rng_key, 0));
+        assert_ne!(tile_rng_key(rng_key, 0), tile_rng_key(rng_key, 1));
+        assert_ne!(tile_rng_key(rng_key, 0), tile_rng_key([4u8; 32], 0));
+    }
+}
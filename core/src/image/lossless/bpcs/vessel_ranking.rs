@@ -0,0 +1,216 @@
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{
+        bit_plane::{BYTES_PER_PLANE, ImageCoding},
+        bit_plane_iter::BitIndexedBitPlaneIter,
+        capacity::{calculate_num_of_embedding_planes, max_message_plane_length},
+        dynamic_prefix::validate_min_alpha,
+        initialization_vector::calculate_message_plane_length_and_remnant,
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel};
+use std::{cmp::Ordering, ops::Deref, thread};
+
+/// How well a candidate vessel suits a payload, as scored by [rank_vessels]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VesselScore {
+    /// The index of the image in the slice that was passed to [rank_vessels].
+    pub index: usize,
+    /// The maximum number of payload bytes that can be embedded into the image.
+    pub capacity: usize,
+    /// Whether the payload fits in the image.
+    pub fits: bool,
+    /// The length of the payload divided by the capacity of the image. Values above 1.0 mean that the payload doesn't
+    /// fit, and an image without any capacity has an infinite usage.
+    pub capacity_usage: f64,
+    /// The share of the changed planes that lie in the more significant half of the bit indices, from 0.0 to 1.0.
+    /// Changes to more significant bits are easier to detect, so lower is better.
+    pub high_bit_plane_share: f64,
+    /// The most significant bit index that would be changed, `0` is the most significant bit. `None` if no planes
+    /// would be changed.
+    pub most_significant_bit_index: Option<u8>,
+}
+
+impl VesselScore {
+    // fitting vessels come first, then the ones that change fewer high bit planes, then the ones with more headroom
+    fn rank(&self, other: &Self) -> Ordering {
+        other
+            .fits
+            .cmp(&self.fits)
+            .then(
+                self.high_bit_plane_share
+                    .total_cmp(&other.high_bit_plane_share),
+            )
+            .then(self.capacity_usage.total_cmp(&other.capacity_usage))
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+// walks the accepted planes from the least significant bit index up, in the same order the plane selector does, and
+//  returns the share of the taken planes that are in the more significant half and the most significant taken index
+fn high_bit_plane_usage(
+    accepted_per_bit_index: &[usize],
+    needed_planes: usize,
+) -> (f64, Option<u8>) {
+    let high_bit_indices = accepted_per_bit_index.len() / 2;
+
+    let (mut taken, mut taken_high) = (0usize, 0usize);
+    let mut most_significant_bit_index: Option<u8> = None;
+    for (bit_index, accepted) in accepted_per_bit_index.iter().enumerate().rev() {
+        if taken == needed_planes {
+            break;
+        }
+        let taken_here = (*accepted).min(needed_planes - taken);
+        if taken_here == 0 {
+            continue;
+        }
+        taken += taken_here;
+        if bit_index < high_bit_indices {
+            taken_high += taken_here;
+        }
+        most_significant_bit_index = Some(bit_index as u8);
+    }
+
+    match taken {
+        0 => (0.0, None),
+        _ => (taken_high as f64 / taken as f64, most_significant_bit_index),
+    }
+}
+
+fn score_vessel<P, C>(
+    index: usize,
+    source_image: &ImageBuffer<P, C>,
+    payload_length: usize,
+    min_alpha: f64,
+) -> VesselScore
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let accepted_per_bit_index: Vec<usize> = (0..P::Subpixel::BIT_DEPTH)
+        .map(|bit_index| {
+            BitIndexedBitPlaneIter::with_coding(source_image, bit_index, ImageCoding::BinaryCode)
+                .filter(|(_, plane)| plane.alpha() >= min_alpha)
+                .count()
+        })
+        .collect();
+    let accepted_planes_num: usize = accepted_per_bit_index.iter().sum();
+
+    let capacity = max_message_plane_length(min_alpha, accepted_planes_num) * BYTES_PER_PLANE;
+    let (message_plane_length, _) = calculate_message_plane_length_and_remnant(payload_length);
+    let needed_planes = calculate_num_of_embedding_planes(min_alpha, message_plane_length);
+    let (high_bit_plane_share, most_significant_bit_index) =
+        high_bit_plane_usage(&accepted_per_bit_index, needed_planes);
+
+    VesselScore {
+        index,
+        capacity,
+        fits: needed_planes <= accepted_planes_num,
+        capacity_usage: match capacity {
+            0 => f64::INFINITY,
+            _ => payload_length as f64 / capacity as f64,
+        },
+        high_bit_plane_share,
+        most_significant_bit_index,
+    }
+}
+
+/// Score a batch of candidate vessels for a payload, and rank them from the most to the least suitable one
+///
+/// Every image is scored by its BPCS capacity, by how much of that capacity the payload would use, and by how many of
+/// the changed planes would lie in the more significant bit indices. The images are scored in parallel.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::rank_vessels;
+/// use image::RgbImage;
+///
+/// let candidates = vec![RgbImage::new(512, 512), RgbImage::new(1024, 768)];
+///
+/// let ranking = rank_vessels(&candidates, 10_000, 0.3).unwrap();
+/// let best_vessel = &candidates[ranking[0].index];
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `images: &[ImageBuffer<P, C>]` — The candidate vessels, they are left untouched.
+/// * `payload_length: usize` — The length of the payload that is going to be embedded, in bytes.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `min_alpha` isn't between 0.0 and 0.5.
+///
+/// # Returns
+/// Returns `Result<Vec<VesselScore>, PixelveilError>`. If `Ok(...)` is returned, the contained value holds a score for
+/// every image, ranked from the most suitable vessel to the least suitable one. The vessels that can hold the payload
+/// come first. Among them, the ones that change a smaller share of high bit planes come first, and ties are broken by
+/// the lower capacity usage and then by the original order.
+///
+/// # Notes
+/// The images are split between the available threads, and every image is scanned once.
+pub fn rank_vessels<P, C>(
+    images: &[ImageBuffer<P, C>],
+    payload_length: usize,
+    min_alpha: f64,
+) -> Result<Vec<VesselScore>, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel> + Sync,
+    C: Deref<Target = [P::Subpixel]> + Sync,
+{
+    validate_min_alpha(min_alpha)?;
+
+    let thread_num = thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+        .min(images.len())
+        .max(1);
+    let chunk_size = images.len().div_ceil(thread_num).max(1);
+
+    let mut scores: Vec<VesselScore> = thread::scope(|scope| {
+        let handles: Vec<_> = images
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .map(|(i, image)| {
+                            score_vessel(
+                                chunk_index * chunk_size + i,
+                                image,
+                                payload_length,
+                                min_alpha,
+                            )
+                        })
+                        .collect::<Vec<VesselScore>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    scores.sort_by(VesselScore::rank);
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_bit_plane_usage() {
+        // bit index 7 is the least significant one, so it is used first
+        let accepted = [5, 5, 5, 5, 5, 5, 5, 10];
+        assert_eq!(high_bit_plane_usage(&accepted, 8), (0.0, Some(7)));
+        assert_eq!(high_bit_plane_usage(&accepted, 25), (0.0, Some(4)));
+        assert_eq!(high_bit_plane_usage(&accepted, 35), (10.0 / 35.0, Some(2)));
+        assert_eq!(high_bit_plane_usage(&accepted, 100), (20.0 / 45.0, Some(0)));
+        assert_eq!(high_bit_plane_usage(&[0; 8], 10), (0.0, None));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    errors::PixelveilError,
+    image::lossless::bpcs::{
+        bit_plane::{ImageCoding, PLANE_SIZE, check_channel_and_bit_index},
+        bit_plane_iter::BitIndexedBitPlaneIter,
+    },
+    utils::image_utils::BitPlaneSubpixel,
+};
+use image::{ImageBuffer, Pixel, Rgb, RgbImage};
+use std::ops::Deref;
+
+const COLOR_SCALE_STOPS: [[u8; 3]; 5] = [
+    [0, 0, 255],   // blue
+    [0, 255, 255], // cyan
+    [0, 255, 0],   // green
+    [255, 255, 0], // yellow
+    [255, 0, 0],   // red
+];
+
+pub(crate) fn color_scale(value: f64) -> Rgb<u8> {
+    let value = value.clamp(0.0, 1.0);
+    let position = value * (COLOR_SCALE_STOPS.len() - 1) as f64;
+    let lower = (position.floor() as usize).min(COLOR_SCALE_STOPS.len() - 2);
+    let fraction = position - lower as f64;
+
+    let mut color = [0u8; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        let start = COLOR_SCALE_STOPS[lower][i] as f64;
+        let end = COLOR_SCALE_STOPS[lower + 1][i] as f64;
+        *channel = (start + (end - start) * fraction).round() as u8;
+    }
+    Rgb(color)
+}
+
+// maps a bit index to the color scale, the least significant bit index is blue and the most significant one is red
+pub(crate) fn bit_index_color(bit_index: u8, bit_depth: u8) -> Rgb<u8> {
+    if bit_depth <= 1 {
+        return color_scale(1.0);
+    }
+    color_scale(1.0 - (bit_index as f64 / (bit_depth - 1) as f64))
+}
+
+pub(crate) fn fill_block(image: &mut RgbImage, x: u32, y: u32, color: Rgb<u8>) {
+    for block_y in y..(y + PLANE_SIZE).min(image.height()) {
+        for block_x in x..(x + PLANE_SIZE).min(image.width()) {
+            image.put_pixel(block_x, block_y, color);
+        }
+    }
+}
+
+/// Render the complexity of every block of an image, at a single channel and bit index
+///
+/// Every 8x8 block of the output is colored by the complexity (`alpha`) of the matching bit plane, on a scale from blue
+/// (a complexity of 0.0) through cyan, green and yellow to red (a complexity of 1.0). Planes whose complexity is at
+/// least `min_alpha` are the ones BPCS can replace, so comparing the heatmaps of the different bit indices shows where
+/// the capacity of an image comes from.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::render_complexity_heatmap;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// for bit_index in 0..8 {
+///     let heatmap = render_complexity_heatmap(&vessel_image, 0, bit_index).unwrap();
+///     heatmap.save(format!("red_bit_{bit_index}.png")).unwrap();
+/// }
+/// ```
+///
+/// # Arguments
+/// The function takes in three arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to render the heatmap of, it is left untouched.
+/// * `channel: u8` — The channel to render (e.g. R,G,B = 0,1,2).
+/// * `bit_index: u8` — The bit index to render, `0` is the most significant bit.
+///
+/// # Errors
+/// The errors that can be returned are:
+/// * `PixelveilError::InvalidParameter` — If `channel` or `bit_index` are out of bounds for the pixel type of the
+///   image.
+///
+/// # Returns
+/// Returns `Result<RgbImage, PixelveilError>`. If `Ok(...)` is returned, the contained value is an image of the same
+/// dimensions as the source image. Pixels on the right and bottom edges that don't fill a whole block are black.
+///
+/// # Notes
+/// The complexity is measured on the Gray Code of the image, like BPCS measures it.
+pub fn render_complexity_heatmap<P, C>(
+    source_image: &ImageBuffer<P, C>,
+    channel: u8,
+    bit_index: u8,
+) -> Result<RgbImage, PixelveilError>
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    check_channel_and_bit_index::<P>(channel, bit_index)?;
+
+    let mut heatmap = RgbImage::new(source_image.width(), source_image.height());
+    let plane_iter =
+        BitIndexedBitPlaneIter::with_coding(source_image, bit_index, ImageCoding::BinaryCode);
+    for ((x, y, plane_channel, _), plane) in plane_iter {
+        if plane_channel == channel {
+            fill_block(&mut heatmap, x, y, color_scale(plane.alpha()));
+        }
+    }
+    Ok(heatmap)
+}
+
+/// Render how many of the bit planes of every block of an image are complex enough to be used by BPCS
+///
+/// Every 8x8 block of the output is colored by the fraction of its bit planes (over every channel and bit index) whose
+/// complexity is at least `min_alpha`, on a scale from blue (a single plane) to red (every plane). Blocks without any
+/// usable planes are black. This shows where in the image data can be embedded, and why a vessel has little capacity.
+///
+/// # Example
+/// ```no_run
+/// use pixelveil::bpcs::render_accepted_plane_map;
+/// use image::RgbImage;
+///
+/// let vessel_image = RgbImage::new(512, 512);
+///
+/// let map = render_accepted_plane_map(&vessel_image, 0.3);
+/// map.save("accepted_planes.png").unwrap();
+/// ```
+///
+/// # Arguments
+/// The function takes in two arguments:
+/// * `source_image: &ImageBuffer<P, C>` — The image to render the map of, it is left untouched.
+/// * `min_alpha: f64` — The BPCS minimum complexity coefficient.
+///
+/// # Returns
+/// Returns an `RgbImage` of the same dimensions as the source image. Pixels on the right and bottom edges that don't
+/// fill a whole block are black.
+pub fn render_accepted_plane_map<P, C>(source_image: &ImageBuffer<P, C>, min_alpha: f64) -> RgbImage
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    let blocks_per_row = (source_image.width() / PLANE_SIZE) as usize;
+    let blocks_per_column = (source_image.height() / PLANE_SIZE) as usize;
+    let planes_per_block = P::CHANNEL_COUNT as usize * P::Subpixel::BIT_DEPTH as usize;
+
+    let mut accepted_per_block = vec![0usize; blocks_per_row * blocks_per_column];
+    for bit_index in 0..P::Subpixel::BIT_DEPTH {
+        let plane_iter =
+            BitIndexedBitPlaneIter::with_coding(source_image, bit_index, ImageCoding::BinaryCode);
+        for ((x, y, _, _), plane) in plane_iter {
+            if plane.alpha() >= min_alpha {
+                let block = (y / PLANE_SIZE) as usize * blocks_per_row + (x / PLANE_SIZE) as usize;
+                accepted_per_block[block] += 1;
+            }
+        }
+    }
+
+    let mut map = RgbImage::new(source_image.width(), source_image.height());
+    for (block, accepted) in accepted_per_block.into_iter().enumerate() {
+        if accepted == 0 {
+            continue;
+        }
+        let x = (block % blocks_per_row) as u32 * PLANE_SIZE;
+        let y = (block / blocks_per_row) as u32 * PLANE_SIZE;
+        fill_block(
+            &mut map,
+            x,
+            y,
+            accepted_plane_color(accepted, planes_per_block),
+        );
+    }
+    map
+}
+
+// maps the number of accepted planes in a block to the color scale, a single plane is blue and every plane is red
+fn accepted_plane_color(accepted: usize, planes_per_block: usize) -> Rgb<u8> {
+    if planes_per_block <= 1 {
+        return color_scale(1.0);
+    }
+    color_scale((accepted - 1) as f64 / (planes_per_block - 1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_scale_edges() {
+        assert_eq!(color_scale(0.0), Rgb([0, 0, 255]));
+        assert_eq!(color_scale(0.5), Rgb([0, 255, 0]));
+        assert_eq!(color_scale(1.0), Rgb([255, 0, 0]));
+        assert_eq!(color_scale(7.0), Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn test_bit_index_color() {
+        assert_eq!(bit_index_color(0, 8), Rgb([255, 0, 0]));
+        assert_eq!(bit_index_color(7, 8), Rgb([0, 0, 255]));
+        assert_eq!(bit_index_color(15, 16), Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn test_accepted_plane_color() {
+        assert_eq!(accepted_plane_color(1, 24), Rgb([0, 0, 255]));
+        assert_eq!(accepted_plane_color(24, 24), Rgb([255, 0, 0]));
+        assert_eq!(accepted_plane_color(1, 1), Rgb([255, 0, 0]));
+    }
+}
@@ -18,9 +18,18 @@
 //! There are some functions that use the [DynamicImage](https://docs.rs/image/latest/image/enum.DynamicImage.html)
 //! enum because they don't require a 24-bit RGB image specifically.
 //!
+//! The bit plane based functions (BPCS and the Gray Code utilities) are generic over the
+//! [ImageBuffer](https://docs.rs/image/latest/image/struct.ImageBuffer.html) pixel type, and also accept 16-bit per
+//! channel images (e.g. `ImageBuffer<Rgb<u16>, Vec<u16>>` or `ImageBuffer<Luma<u16>, Vec<u16>>`), see
+//! [BitPlaneSubpixel](image_utils::BitPlaneSubpixel).
+//!
 //! For instructions and utility functions on how to open, handle, and export RgbImages and DynamicImages, consult the
 //! [image_utils] module.
 //!
+//! # Errors
+//! Every function that can fail returns a [PixelveilError](errors::PixelveilError), which wraps steganography, image
+//! and I/O errors and reports invalid arguments instead of panicking. See the [errors] module.
+//!
 //! # Steganography Functionality
 //! Lossless Images:
 //! * [BPCS (Bit Plane Complexity Segmentation)](bpcs)
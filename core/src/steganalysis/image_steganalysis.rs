@@ -1,9 +1,20 @@
 //! Steganalysis functions for analyzing images.
 
-use crate::utils::bit_operations_utils::unsigned_int_to_bits;
+use crate::{errors::PixelveilError, utils::bit_operations_utils::unsigned_int_to_bits};
 use image::{GrayImage, Luma, Rgb, RgbImage};
 use std::collections::HashMap;
 
+// images are compared pixel by pixel, so the comparing functions only accept images of the same dimensions
+fn check_same_dimensions(image1: &RgbImage, image2: &RgbImage) -> Result<(), PixelveilError> {
+    if image1.dimensions() != image2.dimensions() {
+        return Err(PixelveilError::DimensionMismatch(
+            image1.dimensions(),
+            image2.dimensions(),
+        ));
+    }
+    Ok(())
+}
+
 /// Subtract two 24-bit RGB pixels in each one of their channels
 ///
 /// Uses the `Rgb` struct from the image crate to represent the value of each pixel.
@@ -36,13 +47,11 @@ use std::collections::HashMap;
 /// # Returns
 /// The returned value of this function is a pixel that represent the difference in each channel between the two pixels.
 pub fn subtract_pixels(p1: &Rgb<u8>, p2: &Rgb<u8>) -> Rgb<u8> {
-    Rgb::<u8> {
-        0: [
-            (p1.0[0] as i16 - p2.0[0] as i16).abs() as u8,
-            (p1.0[1] as i16 - p2.0[1] as i16).abs() as u8,
-            (p1.0[2] as i16 - p2.0[2] as i16).abs() as u8,
-        ],
-    }
+    Rgb::<u8>([
+        p1.0[0].abs_diff(p2.0[0]),
+        p1.0[1].abs_diff(p2.0[1]),
+        p1.0[2].abs_diff(p2.0[2]),
+    ])
 }
 
 /// XOR two 24-bit RGB pixels in each one of their channels
@@ -77,13 +86,11 @@ pub fn subtract_pixels(p1: &Rgb<u8>, p2: &Rgb<u8>) -> Rgb<u8> {
 /// # Returns
 /// The returned value of this function is a pixel that is the two passed in pixels, XORed with each other.
 pub fn xor_pixels(p1: &Rgb<u8>, p2: &Rgb<u8>) -> Rgb<u8> {
-    Rgb::<u8> {
-        0: [
-            (p1.0[0] ^ p2.0[0]),
-            (p1.0[1] ^ p2.0[1]),
-            (p1.0[2] ^ p2.0[2]),
-        ],
-    }
+    Rgb::<u8>([
+        (p1.0[0] ^ p2.0[0]),
+        (p1.0[1] ^ p2.0[1]),
+        (p1.0[2] ^ p2.0[2]),
+    ])
 }
 
 /// Subtracts two 24-bit RGB images from one another
@@ -98,7 +105,8 @@ pub fn xor_pixels(p1: &Rgb<u8>, p2: &Rgb<u8>) -> Rgb<u8> {
 /// let img1 = RgbImage::new(500, 500);
 /// let img2 = RgbImage::new(500, 500);
 ///
-/// let diff = subtract_images(&img1, &img2);
+/// let diff = subtract_images(&img1, &img2)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 ///
 /// # Arguments
@@ -107,22 +115,17 @@ pub fn xor_pixels(p1: &Rgb<u8>, p2: &Rgb<u8>) -> Rgb<u8> {
 /// * `image2: &RgbImage` — The second image.
 ///
 /// # Panics
-/// This function panics if the two images aren't of the same dimensions.
+/// This function does not panic.
 ///
 /// # Errors
-/// This function does not return errors.
+/// The possible errors that can be returned are:
+/// * `PixelveilError::DimensionMismatch` if the two images aren't of the same dimensions
 ///
 /// # Returns
-/// This function returns an image that every pixel at x,y is the result of applying `subtract_pixels` to the
-/// corresponding pixels in the two images.
-pub fn subtract_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
-    assert_eq!(
-        image1.dimensions(),
-        image2.dimensions(),
-        "The two passed in images do not have the same dimensions: ({:?} and {:?})",
-        image1.dimensions(),
-        image2.dimensions(),
-    );
+/// This function returns a `Result<RgbImage, PixelveilError>`. If `Ok` is returned, the unwrapped value is an image
+/// that every pixel at x,y is the result of applying `subtract_pixels` to the corresponding pixels in the two images.
+pub fn subtract_images(image1: &RgbImage, image2: &RgbImage) -> Result<RgbImage, PixelveilError> {
+    check_same_dimensions(image1, image2)?;
     let mut diff_image = RgbImage::new(image1.width(), image1.height());
 
     for (x, y) in iproduct!(0..image1.width(), 0..image1.height()) {
@@ -132,7 +135,7 @@ pub fn subtract_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
         diff_image.put_pixel(x, y, diff);
     }
 
-    diff_image
+    Ok(diff_image)
 }
 
 /// XOR two 24-bit RGB images
@@ -147,7 +150,8 @@ pub fn subtract_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
 /// let img1 = RgbImage::new(500, 500);
 /// let img2 = RgbImage::new(500, 500);
 ///
-/// let diff = xor_images(&img1, &img2);
+/// let diff = xor_images(&img1, &img2)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 ///
 /// # Arguments
@@ -156,22 +160,17 @@ pub fn subtract_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
 /// * `image2: &RgbImage` — The second image.
 ///
 /// # Panics
-/// This function panics if the two images aren't of the same dimensions.
+/// This function does not panic.
 ///
 /// # Errors
-/// This function does not return errors.
+/// The possible errors that can be returned are:
+/// * `PixelveilError::DimensionMismatch` if the two images aren't of the same dimensions
 ///
 /// # Returns
-/// This function returns an image that every pixel at x,y is the result of applying `xor_pixels` to the
-/// corresponding pixels in the two images.
-pub fn xor_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
-    assert_eq!(
-        image1.dimensions(),
-        image2.dimensions(),
-        "The two passed in images do not have the same dimensions: ({:?} and {:?})",
-        image1.dimensions(),
-        image2.dimensions(),
-    );
+/// This function returns a `Result<RgbImage, PixelveilError>`. If `Ok` is returned, the unwrapped value is an image
+/// that every pixel at x,y is the result of applying `xor_pixels` to the corresponding pixels in the two images.
+pub fn xor_images(image1: &RgbImage, image2: &RgbImage) -> Result<RgbImage, PixelveilError> {
+    check_same_dimensions(image1, image2)?;
     let mut diff_image = RgbImage::new(image1.width(), image1.height());
 
     for (x, y) in iproduct!(0..image1.width(), 0..image1.height()) {
@@ -181,7 +180,7 @@ pub fn xor_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
         diff_image.put_pixel(x, y, diff);
     }
 
-    diff_image
+    Ok(diff_image)
 }
 
 /// Highlight each different channel in each pixel between two 24-bit RGB images
@@ -196,7 +195,8 @@ pub fn xor_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
 /// let img1 = RgbImage::new(500, 500);
 /// let img2 = RgbImage::new(500, 500);
 ///
-/// let diff = highlight_image_difference(&img1, &img2);
+/// let diff = highlight_image_difference(&img1, &img2)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 ///
 /// # Arguments
@@ -205,16 +205,20 @@ pub fn xor_images(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
 /// * `image2: &RgbImage` — The second image.
 ///
 /// # Panics
-/// This function panics if the two images aren't of the same dimensions.
+/// This function does not panic.
 ///
 /// # Errors
-/// This function does not return errors.
+/// The possible errors that can be returned are:
+/// * `PixelveilError::DimensionMismatch` if the two images aren't of the same dimensions
 ///
 /// # Returns
-/// This function returns an image that for each different channel in a pixel between the two images, the value of that
-/// channel is 255.
-pub fn highlight_image_difference(image1: &RgbImage, image2: &RgbImage) -> RgbImage {
-    let mut new_image = subtract_images(image1, image2);
+/// This function returns a `Result<RgbImage, PixelveilError>`. If `Ok` is returned, the unwrapped value is an image
+/// that for each different channel in a pixel between the two images, the value of that channel is 255.
+pub fn highlight_image_difference(
+    image1: &RgbImage,
+    image2: &RgbImage,
+) -> Result<RgbImage, PixelveilError> {
+    let mut new_image = subtract_images(image1, image2)?;
 
     for (x, y) in iproduct!(0..image1.width(), 0..image1.height()) {
         let p = new_image.get_pixel_mut(x, y);
@@ -227,7 +231,7 @@ pub fn highlight_image_difference(image1: &RgbImage, image2: &RgbImage) -> RgbIm
         }
     }
 
-    new_image
+    Ok(new_image)
 }
 
 /// Slices a 24-bit RGB image into 24 bit planes that represent each bit plane of the image as defined [here](https://en.wikipedia.org/wiki/Bit_plane)
@@ -2,3 +2,4 @@
 
 pub(crate) mod bit_operations_utils;
 pub mod image_utils;
+pub(crate) mod secret_sharing_utils;
@@ -5,9 +5,9 @@ pub(crate) const USIZE_BIT_DEPTH: usize = std::mem::size_of::<usize>() * 8;
 
 pub(crate) fn bits_to_u8(bits: [bool; 8]) -> u8 {
     let mut byte = 0u8;
-    for i in 0..8 {
+    for bit in bits {
         byte <<= 1;
-        if bits[i] {
+        if bit {
             byte |= 1;
         }
     }
@@ -16,9 +16,9 @@ pub(crate) fn bits_to_u8(bits: [bool; 8]) -> u8 {
 
 pub(crate) fn bits_to_usize(bits: [bool; USIZE_BIT_DEPTH]) -> usize {
     let mut num = 0usize;
-    for i in 0..USIZE_BIT_DEPTH {
+    for bit in bits {
         num <<= 1;
-        if bits[i] {
+        if bit {
             num |= 1;
         }
     }
@@ -44,21 +44,45 @@ where
     v
 }
 
-pub(crate) fn get_bit_from_u8(byte: u8, bit_index: u8) -> bool {
-    assert!(bit_index < 8);
-    ((byte >> 7 - bit_index) & 1) != 0
+pub(crate) fn bit_depth_of<T: PrimInt>() -> u8 {
+    (std::mem::size_of::<T>() * 8) as u8
 }
 
-pub(crate) fn u8_to_gray_code(byte: u8) -> u8 {
-    byte ^ (byte >> 1)
+pub(crate) fn get_bit_from_unsigned_int<T>(num: T, bit_index: u8) -> bool
+where
+    T: Unsigned + PrimInt,
+{
+    let bit_depth = bit_depth_of::<T>();
+    assert!(bit_index < bit_depth);
+    ((num >> (bit_depth - 1 - bit_index) as usize) & T::one()) != T::zero()
+}
+
+pub(crate) fn set_bit_in_unsigned_int<T>(num: T, bit_index: u8, val: bool) -> T
+where
+    T: Unsigned + PrimInt,
+{
+    let bit_depth = bit_depth_of::<T>();
+    assert!(bit_index < bit_depth);
+    let mask = T::one() << (bit_depth - 1 - bit_index) as usize;
+    if val { num | mask } else { num & !mask }
+}
+
+pub(crate) fn unsigned_int_to_gray_code<T>(num: T) -> T
+where
+    T: Unsigned + PrimInt,
+{
+    num ^ (num >> 1)
 }
 
-pub(crate) fn u8_to_binary_code(byte: u8) -> u8 {
-    let mut mask = byte;
-    let mut binary = byte;
-    while mask != 0 {
-        mask >>= 1;
-        binary ^= mask;
+pub(crate) fn unsigned_int_to_binary_code<T>(num: T) -> T
+where
+    T: Unsigned + PrimInt,
+{
+    let mut mask = num;
+    let mut binary = num;
+    while mask != T::zero() {
+        mask = mask >> 1;
+        binary = binary ^ mask;
     }
     binary
 }
@@ -69,24 +93,49 @@ mod tests {
 
     #[test]
     fn test_get_bit() {
-        let b1 = 0b01000000 as u8;
-        assert_eq!(get_bit_from_u8(b1, 1), true)
+        let b1 = 0b01000000u8;
+        assert!(get_bit_from_unsigned_int(b1, 1));
+
+        let b2 = 0b00000000_00000100u16;
+        assert!(get_bit_from_unsigned_int(b2, 13));
+        assert!(!get_bit_from_unsigned_int(b2, 12));
+    }
+
+    #[test]
+    fn test_set_bit() {
+        assert_eq!(set_bit_in_unsigned_int(0b00000000u8, 1, true), 0b01000000u8);
+        assert_eq!(
+            set_bit_in_unsigned_int(0b11111111u8, 7, false),
+            0b11111110u8
+        );
+        assert_eq!(
+            set_bit_in_unsigned_int(0b00000000_00000000u16, 15, true),
+            0b00000000_00000001u16
+        );
     }
 
     #[test]
     fn test_to_gray_code() {
-        assert_eq!(0b0010u8, u8_to_gray_code(0b0011u8)); // 3u8
-        assert_eq!(0b1100u8, u8_to_gray_code(0b1000u8)); // 8u8
-        assert_eq!(0b1011u8, u8_to_gray_code(0b1101u8)); // 13u8
-        assert_eq!(0b1000u8, u8_to_gray_code(0b1111u8)); // 15u8
+        assert_eq!(0b0010u8, unsigned_int_to_gray_code(0b0011u8)); // 3u8
+        assert_eq!(0b1100u8, unsigned_int_to_gray_code(0b1000u8)); // 8u8
+        assert_eq!(0b1011u8, unsigned_int_to_gray_code(0b1101u8)); // 13u8
+        assert_eq!(0b1000u8, unsigned_int_to_gray_code(0b1111u8)); // 15u8
+        assert_eq!(
+            0b11000000_00000000u16,
+            unsigned_int_to_gray_code(0b10000000_00000000u16)
+        ); // 32768u16
     }
 
     #[test]
     fn test_to_binary_code() {
-        assert_eq!(u8_to_binary_code(0b0010u8), 0b0011u8); // 3u8
-        assert_eq!(u8_to_binary_code(0b1100u8), 0b1000u8); // 8u8
-        assert_eq!(u8_to_binary_code(0b1011u8), 0b1101u8); // 13u8
-        assert_eq!(u8_to_binary_code(0b1000u8), 0b1111u8); // 15u8
+        assert_eq!(unsigned_int_to_binary_code(0b0010u8), 0b0011u8); // 3u8
+        assert_eq!(unsigned_int_to_binary_code(0b1100u8), 0b1000u8); // 8u8
+        assert_eq!(unsigned_int_to_binary_code(0b1011u8), 0b1101u8); // 13u8
+        assert_eq!(unsigned_int_to_binary_code(0b1000u8), 0b1111u8); // 15u8
+        assert_eq!(
+            unsigned_int_to_binary_code(0b11000000_00000000u16),
+            0b10000000_00000000u16
+        ); // 32768u16
     }
 
     #[test]
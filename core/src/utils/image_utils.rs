@@ -1,8 +1,45 @@
 //! Utility functions that help reading images, and converting them to [Gray Code](https://en.wikipedia.org/wiki/Gray_code) and back
 
-use crate::utils::bit_operations_utils::{u8_to_binary_code, u8_to_gray_code};
-use image::{DynamicImage, ImageError, ImageFormat, ImageReader, Rgb, RgbImage, open};
-use std::io::Cursor;
+use crate::{
+    errors::PixelveilError,
+    utils::bit_operations_utils::{unsigned_int_to_binary_code, unsigned_int_to_gray_code},
+};
+use image::{
+    ColorType, DynamicImage, ImageBuffer, ImageFormat, ImageReader, Pixel, Primitive, RgbImage,
+    open,
+};
+use num::{PrimInt, Unsigned};
+use std::{
+    io::Cursor,
+    ops::{Deref, DerefMut},
+};
+
+/// A channel (subpixel) type that can be split into bit planes
+///
+/// This trait is implemented for `u8` (8 bits per channel, e.g. `RgbImage`, `GrayImage`) and `u16` (16 bits per
+/// channel, e.g. `ImageBuffer<Rgb<u16>, Vec<u16>>`, `ImageBuffer<Luma<u16>, Vec<u16>>`). Every function in this crate
+/// that works on bit planes or Gray Code accepts images whose subpixel type implements this trait.
+///
+/// # Notes
+/// The number of bit indices in every channel is equal to `BIT_DEPTH`. Bit index `0` is always the most significant
+/// bit, and bit index `BIT_DEPTH - 1` is always the least significant one.
+pub trait BitPlaneSubpixel: Primitive + PrimInt + Unsigned {
+    /// The number of bits (and therefore bit planes) in every channel of this type
+    const BIT_DEPTH: u8;
+}
+
+/// An `ImageBuffer` that owns its pixel data, e.g. `RgbImage` is `OwnedImageBuffer<Rgb<u8>>`
+///
+/// Functions that construct new images (rather than modifying the one passed to them) return this type.
+pub type OwnedImageBuffer<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+impl BitPlaneSubpixel for u8 {
+    const BIT_DEPTH: u8 = 8;
+}
+
+impl BitPlaneSubpixel for u16 {
+    const BIT_DEPTH: u8 = 16;
+}
 
 /// Open image from the raw data that describes an image file
 ///
@@ -25,15 +62,15 @@ use std::io::Cursor;
 ///
 /// # Errors
 /// The possible errors that can be returned are:
-/// * `ImageError` if the data that was passed in is not a valid image file format
+/// * `PixelveilError::Image` if the data that was passed in is not a valid image file format
 ///
 /// # Returns
-/// This function returns a `Result<RgbImage, ImageError>`.
+/// This function returns a `Result<RgbImage, PixelveilError>`.
 /// If `Ok` is returned, the unwrapped value is the opened `RgbImage`.
 ///
 /// # Notes
 /// This function is used as an abstraction for image handling in Rust, it does not have a Python wrapper function.
-pub fn open_rgbimage_from_raw(raw_data: Vec<u8>) -> Result<RgbImage, ImageError> {
+pub fn open_rgbimage_from_raw(raw_data: Vec<u8>) -> Result<RgbImage, PixelveilError> {
     let cursor = Cursor::new(raw_data);
     let img = ImageReader::new(cursor).with_guessed_format()?;
 
@@ -58,34 +95,41 @@ pub fn open_rgbimage_from_raw(raw_data: Vec<u8>) -> Result<RgbImage, ImageError>
 ///
 /// # Errors
 /// The possible errors that can be returned are:
-/// * `ImageError` if the path specifies a missing file or one of invalid image format
+/// * `PixelveilError::Io` if the path specifies a missing file, or one that can't be read
+/// * `PixelveilError::Image` if the file is not of a valid image file format
 ///
 /// # Returns
-/// This function returns a `Result<RgbImage, ImageError>`.
+/// This function returns a `Result<RgbImage, PixelveilError>`.
 /// If `Ok` is returned, the unwrapped value is the opened `RgbImage`.
 ///
 /// # Notes
 /// This function is used as an abstraction for image handling in Rust, it does not have a Python wrapper function.
-pub fn open_rgbimage_from_path(path: &str) -> Result<RgbImage, ImageError> {
+pub fn open_rgbimage_from_path(path: &str) -> Result<RgbImage, PixelveilError> {
     Ok(open(path)?.to_rgb8())
 }
 
-/// Converts a 24-bit RGB pixel from pure binary code to Gray Code as defined [here](https://en.wikipedia.org/wiki/Gray_code)
+/// Converts a pixel from pure binary code to Gray Code as defined [here](https://en.wikipedia.org/wiki/Gray_code)
 ///
 /// # Example
 /// ```
 /// # use pixelveil::image_utils::pixel_to_gray_code;
-/// # use image::Rgb;
+/// # use image::{Luma, Rgb};
 /// let mut pixel = Rgb::<u8>([0b1110101, 0b0011000, 0b1010111]);
 ///
 /// pixel_to_gray_code(&mut pixel);
 ///
 /// assert_eq!(pixel, Rgb::<u8>([0b1001111, 0b0010100, 0b1111100]));
+///
+/// let mut pixel = Luma::<u16>([0b10000000_00000001]);
+///
+/// pixel_to_gray_code(&mut pixel);
+///
+/// assert_eq!(pixel, Luma::<u16>([0b11000000_00000001]));
 /// ```
 ///
 /// # Arguments
 /// This function takes in one argument:
-/// * `pixel: &mut Rgb<u8>` — The pixel to convert.
+/// * `pixel: &mut P` — The pixel to convert. Every channel of the pixel is converted.
 ///
 /// # Panics
 /// This function does not panic.
@@ -95,28 +139,37 @@ pub fn open_rgbimage_from_path(path: &str) -> Result<RgbImage, ImageError> {
 ///
 /// # Returns
 /// This function returns (), the passed in pixel will be changed instead of constructing a new pixel.
-pub fn pixel_to_gray_code(pixel: &mut Rgb<u8>) {
-    pixel.0[0] = u8_to_gray_code(pixel.0[0]);
-    pixel.0[1] = u8_to_gray_code(pixel.0[1]);
-    pixel.0[2] = u8_to_gray_code(pixel.0[2]);
+pub fn pixel_to_gray_code<P>(pixel: &mut P)
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+{
+    for channel in pixel.channels_mut() {
+        *channel = unsigned_int_to_gray_code(*channel);
+    }
 }
 
-/// Converts a 24-bit RGB pixel from Gray Code to pure binary code as defined [here](https://en.wikipedia.org/wiki/Gray_code)
+/// Converts a pixel from Gray Code to pure binary code as defined [here](https://en.wikipedia.org/wiki/Gray_code)
 ///
 /// # Example
 /// ```
 /// # use pixelveil::image_utils::pixel_to_binary_code;
-/// # use image::Rgb;
+/// # use image::{Luma, Rgb};
 /// let mut pixel = Rgb::<u8>([0b10011111, 0b00101001, 0b11111001]);
 ///
 /// pixel_to_binary_code(&mut pixel);
 ///
 /// assert_eq!(pixel, Rgb::<u8>([0b11101010, 0b00110001, 0b10101110]));
+///
+/// let mut pixel = Luma::<u16>([0b11000000_00000001]);
+///
+/// pixel_to_binary_code(&mut pixel);
+///
+/// assert_eq!(pixel, Luma::<u16>([0b10000000_00000001]));
 /// ```
 ///
 /// # Arguments
 /// This function takes in one argument:
-/// * `pixel: &mut Rgb<u8>` — The pixel to convert.
+/// * `pixel: &mut P` — The pixel to convert. Every channel of the pixel is converted.
 ///
 /// # Panics
 /// This function does not panic.
@@ -126,10 +179,13 @@ pub fn pixel_to_gray_code(pixel: &mut Rgb<u8>) {
 ///
 /// # Returns
 /// This function returns (), the passed in pixel will be changed instead of constructing a new pixel.
-pub fn pixel_to_binary_code(pixel: &mut Rgb<u8>) {
-    pixel.0[0] = u8_to_binary_code(pixel.0[0]);
-    pixel.0[1] = u8_to_binary_code(pixel.0[1]);
-    pixel.0[2] = u8_to_binary_code(pixel.0[2]);
+pub fn pixel_to_binary_code<P>(pixel: &mut P)
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+{
+    for channel in pixel.channels_mut() {
+        *channel = unsigned_int_to_binary_code(*channel);
+    }
 }
 
 /// Converts an image from pure binary code to Gray Code
@@ -138,16 +194,21 @@ pub fn pixel_to_binary_code(pixel: &mut Rgb<u8>) {
 ///
 /// # Example
 /// ```no_run
-/// # use image::RgbImage;
+/// # use image::{ImageBuffer, Rgb, RgbImage};
 /// # use pixelveil::image_utils::image_to_gray_code;
 /// let mut img = RgbImage::new(500, 500);
 ///
 /// image_to_gray_code(&mut img);
+///
+/// let mut img16 = ImageBuffer::<Rgb<u16>, Vec<u16>>::new(500, 500);
+///
+/// image_to_gray_code(&mut img16);
 /// ```
 ///
 /// # Arguments
 /// This function takes in one argument:
-/// * `image: &mut RgbImage` — The image to convert.
+/// * `image: &mut ImageBuffer<P, C>` — The image to convert (e.g. an `RgbImage`, or an `ImageBuffer` over a borrowed
+///   `&mut [u8]` buffer).
 ///
 /// # Panics
 /// This function does not panic.
@@ -157,7 +218,11 @@ pub fn pixel_to_binary_code(pixel: &mut Rgb<u8>) {
 ///
 /// # Returns
 /// This function returns (), the passed in image will be changed instead of constructing a new image.
-pub fn image_to_gray_code(image: &mut RgbImage) {
+pub fn image_to_gray_code<P, C>(image: &mut ImageBuffer<P, C>)
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
     for pixel in image.pixels_mut() {
         pixel_to_gray_code(pixel);
     }
@@ -169,16 +234,21 @@ pub fn image_to_gray_code(image: &mut RgbImage) {
 ///
 /// # Example
 /// ```no_run
-/// # use image::RgbImage;
+/// # use image::{ImageBuffer, Rgb, RgbImage};
 /// # use pixelveil::image_utils::image_to_binary_code;
 /// let mut img = RgbImage::new(500, 500);
 ///
 /// image_to_binary_code(&mut img);
+///
+/// let mut img16 = ImageBuffer::<Rgb<u16>, Vec<u16>>::new(500, 500);
+///
+/// image_to_binary_code(&mut img16);
 /// ```
 ///
 /// # Arguments
 /// This function takes in one argument:
-/// * `image: &mut RgbImage` — The image to convert.
+/// * `image: &mut ImageBuffer<P, C>` — The image to convert (e.g. an `RgbImage`, or an `ImageBuffer` over a borrowed
+///   `&mut [u8]` buffer).
 ///
 /// # Panics
 /// This function does not panic.
@@ -188,7 +258,11 @@ pub fn image_to_gray_code(image: &mut RgbImage) {
 ///
 /// # Returns
 /// This function returns (), the passed in image will be changed instead of constructing a new image.
-pub fn image_to_binary_code(image: &mut RgbImage) {
+pub fn image_to_binary_code<P, C>(image: &mut ImageBuffer<P, C>)
+where
+    P: Pixel<Subpixel: BitPlaneSubpixel>,
+    C: Deref<Target = [P::Subpixel]> + DerefMut,
+{
     for pixel in image.pixels_mut() {
         pixel_to_binary_code(pixel);
     }
@@ -201,7 +275,8 @@ pub fn image_to_binary_code(image: &mut RgbImage) {
 /// # use pixelveil::image_utils::export_image_to_png_bytes;
 /// # use image::{DynamicImage, ColorType};
 /// let image = DynamicImage::new(500, 500, ColorType::Rgb8);
-/// let image_file_bytes = export_image_to_png_bytes(&image);
+/// let image_file_bytes = export_image_to_png_bytes(&image)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 ///
 /// # Arguments
@@ -212,23 +287,34 @@ pub fn image_to_binary_code(image: &mut RgbImage) {
 /// This function does not panic.
 ///
 /// # Errors
-/// This function does not return errors.
+/// The possible errors that can be returned are:
+/// * `PixelveilError::UnsupportedPixelFormat` if the image has floating point channels, which the .png file format
+///   can't store
+/// * `PixelveilError::Image` if encoding the image fails
 ///
 /// # Returns
-/// This function returns a `Vec<u8>` that describes the file bytes in the .png file format
+/// This function returns a `Result<Vec<u8>, PixelveilError>`.
+/// If `Ok` is returned, the unwrapped value describes the file bytes in the .png file format
 ///
 /// # Notes
 /// This function is used as an abstraction for image handling in Rust, it does not have a Python wrapper function.
-pub fn export_image_to_png_bytes(img: &DynamicImage) -> Vec<u8> {
+pub fn export_image_to_png_bytes(img: &DynamicImage) -> Result<Vec<u8>, PixelveilError> {
+    if matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F) {
+        return Err(PixelveilError::UnsupportedPixelFormat(format!(
+            "{:?} images can't be exported to the .png file format",
+            img.color()
+        )));
+    }
+
     // Create an in-memory buffer (Vec<u8>) wrapped in a Cursor
     let mut bytes: Vec<u8> = Vec::new();
     let mut cursor = Cursor::new(&mut bytes);
 
     // Use the write_to method to encode the image into the cursor/buffer
-    img.write_to(&mut cursor, ImageFormat::Png).unwrap();
+    img.write_to(&mut cursor, ImageFormat::Png)?;
 
     // Return the resulting bytes
-    bytes
+    Ok(bytes)
 }
 
 /// Convert an RgbImage to a DynamicImage
@@ -263,6 +349,7 @@ pub fn rgbimage_to_dynamicimage(img: RgbImage) -> DynamicImage {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use image::{Luma, Rgb};
 
     #[test]
     fn test_pixel_to_gray_code() {
@@ -277,4 +364,29 @@ mod tests {
         pixel_to_binary_code(&mut pixel);
         assert_eq!(pixel, Rgb::<u8>([0b11101010, 0b00110001, 0b10101110]));
     }
+
+    #[test]
+    fn test_circular_16_bit_gray_code() {
+        let original = Rgb::<u16>([
+            0b10110010_01110001,
+            0b00000000_11111111,
+            0b11111111_00000000,
+        ]);
+        let mut pixel = original;
+        pixel_to_gray_code(&mut pixel);
+        assert_eq!(
+            pixel,
+            Rgb::<u16>([
+                0b11101011_01001001,
+                0b00000000_10000000,
+                0b10000000_10000000
+            ])
+        );
+        pixel_to_binary_code(&mut pixel);
+        assert_eq!(pixel, original);
+
+        let mut luma = Luma::<u16>([0b01000000_00000000]);
+        pixel_to_gray_code(&mut luma);
+        assert_eq!(luma, Luma::<u16>([0b01100000_00000000]));
+    }
 }
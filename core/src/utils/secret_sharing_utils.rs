@@ -0,0 +1,143 @@
+use rand::Rng;
+
+// multiplication in GF(2^8) with the AES reduction polynomial (x^8 + x^4 + x^3 + x + 1)
+pub(crate) fn gf256_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+pub(crate) fn gf256_inv(a: u8) -> u8 {
+    assert_ne!(a, 0, "0 has no multiplicative inverse.");
+    // a^254 = a^-1, because the multiplicative group has 255 elements
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = gf256_mul(result, base);
+        }
+        base = gf256_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+// evaluates a polynomial (constant coefficient first) at x, using Horner's method
+fn evaluate_polynomial(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, coefficient| gf256_mul(acc, x) ^ coefficient)
+}
+
+// splits the secret into share_count shares that are indexed 1..=share_count, any threshold of them recover the secret
+pub(crate) fn split_secret<R: Rng>(
+    secret: &[u8],
+    threshold: u8,
+    share_count: u8,
+    rng: &mut R,
+) -> Vec<(u8, Vec<u8>)> {
+    assert!(threshold >= 1, "The threshold must be at least 1.");
+    assert!(
+        threshold <= share_count,
+        "The threshold can't be larger than the number of shares."
+    );
+
+    let mut shares: Vec<(u8, Vec<u8>)> = (1..=share_count)
+        .map(|x| (x, Vec::with_capacity(secret.len())))
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        // every byte gets its own random polynomial of degree threshold - 1, whose constant coefficient is the byte
+        coefficients[0] = *byte;
+        rng.fill(&mut coefficients[1..]);
+        for (x, share) in shares.iter_mut() {
+            share.push(evaluate_polynomial(&coefficients, *x));
+        }
+    }
+
+    shares
+}
+
+// recovers the secret from shares with distinct non zero indexes and equal lengths, using Lagrange interpolation at 0
+pub(crate) fn combine_shares(shares: &[(u8, &[u8])]) -> Vec<u8> {
+    let secret_length = shares.first().map_or(0, |(_, share)| share.len());
+
+    // the Lagrange basis polynomials evaluated at 0, they only depend on the share indexes
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|(x_i, _)| {
+            let mut numerator = 1u8;
+            let mut denominator = 1u8;
+            for (x_j, _) in shares {
+                if x_j != x_i {
+                    numerator = gf256_mul(numerator, *x_j);
+                    denominator = gf256_mul(denominator, x_i ^ x_j);
+                }
+            }
+            gf256_mul(numerator, gf256_inv(denominator))
+        })
+        .collect();
+
+    (0..secret_length)
+        .map(|byte_index| {
+            shares
+                .iter()
+                .zip(&basis)
+                .fold(0u8, |acc, ((_, share), basis)| {
+                    acc ^ gf256_mul(share[byte_index], *basis)
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_gf256_arithmetic() {
+        // the example from the AES specification
+        assert_eq!(gf256_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf256_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf256_mul(a, gf256_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_shares_recover_the_secret() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let secret = b"a very secret key".to_vec();
+        let shares = split_secret(&secret, 3, 5, &mut rng);
+
+        for combination in shares.iter().combinations(3) {
+            let shares: Vec<(u8, &[u8])> = combination
+                .iter()
+                .map(|(x, share)| (*x, share.as_slice()))
+                .collect();
+            assert_eq!(combine_shares(&shares), secret);
+        }
+
+        // fewer shares than the threshold don't recover the secret
+        let shares: Vec<(u8, &[u8])> = shares[0..2]
+            .iter()
+            .map(|(x, share)| (*x, share.as_slice()))
+            .collect();
+        assert_ne!(combine_shares(&shares), secret);
+    }
+}
@@ -1,7 +1,34 @@
-use image::open;
-use pixelveil::bpcs::{embed_data, extract_data};
+use image::{ImageBuffer, Rgb, RgbImage, open};
+use pixelveil::{
+    bpcs::{
+        BlockEncoding, BlockPolicy, BpcsConfig, BpcsStage, ChannelPolicy, PayloadInfo,
+        PlannedPlaneKind, SlotPayload, TiledEmbedder, TiledExtractor, append_data, embed_data,
+        embed_data_across_images, embed_data_and_verify, embed_data_deterministic,
+        embed_data_exact_size, embed_data_minimum_change, embed_data_reversible, embed_data_tiled,
+        embed_data_with_config, embed_data_with_decoy, embed_data_with_progress,
+        embed_data_with_report, embed_secret_shares, embed_slots, estimate_maximum_capacity,
+        estimate_reversible_capacity, extract_data, extract_data_across_images, extract_data_tiled,
+        extract_data_with_config, extract_data_with_progress, extract_data_with_unknown_alpha,
+        extract_hidden_data, extract_slot, plan_embedding, probe, rank_vessels,
+        raw::{
+            ImageCoding, PLANE_SIZE, bit_planes, channel_bit_planes, data_bits_per_prefixed_plane,
+            decode_prefixed_planes, encode_prefixed_planes, read_plane, write_plane,
+        },
+        recover_secret_from_shares, render_accepted_plane_map, render_complexity_heatmap,
+        replace_data, restore_cover,
+    },
+    errors::{PixelveilError, SteganographyError},
+    image_steganalysis::subtract_images,
+    image_utils::{
+        export_image_to_png_bytes, image_to_binary_code, image_to_gray_code,
+        open_rgbimage_from_path,
+    },
+};
 use std::{
+    collections::HashSet,
     io::{self, Read},
+    iter::zip,
+    ops::ControlFlow,
     os::unix::fs::MetadataExt,
 };
 
@@ -28,9 +55,1503 @@ fn test_circular_bpcs() -> Result<(), Box<dyn std::error::Error>> {
         rng_key,
     )?;
 
-    let extracted = extract_data(source_image, min_alpha, rng_key)?;
+    let extracted = extract_data(&source_image, min_alpha, rng_key)?;
 
     assert_eq!(extracted, std::fs::read(data_file_path).unwrap());
 
     Ok(())
 }
+
+#[test]
+fn test_circular_bpcs_16_bit() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let source_image = open(source_image_path)?;
+
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [7u8; 32];
+
+    let mut rgb16_image = source_image.to_rgb16();
+    embed_data(
+        &mut rgb16_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert_eq!(extract_data(&rgb16_image, min_alpha, rng_key)?, data);
+
+    let mut luma16_image = source_image.to_luma16();
+    embed_data(
+        &mut luma16_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert_eq!(extract_data(&luma16_image, min_alpha, rng_key)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_16_bit_capacity_exceeds_8_bit() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?;
+    let min_alpha = 0.3f64;
+
+    let rgb8_capacity = estimate_maximum_capacity(&source_image.to_rgb8(), min_alpha);
+    let rgb16_capacity = estimate_maximum_capacity(&source_image.to_rgb16(), min_alpha);
+
+    assert!(rgb16_capacity > rgb8_capacity);
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_borrowed_buffer() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let (width, height) = source_image.dimensions();
+    let mut frame: Vec<u8> = source_image.into_raw();
+    let original_frame = frame.clone();
+
+    let data: Vec<u8> = (0..5_000u32).map(|i| (i * 17 % 253) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [3u8; 32];
+
+    let mut frame_image =
+        ImageBuffer::<Rgb<u8>, &mut [u8]>::from_raw(width, height, frame.as_mut_slice()).unwrap();
+    embed_data(
+        &mut frame_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert_ne!(frame, original_frame);
+
+    let embedded_frame = frame.clone();
+    let frame_image =
+        ImageBuffer::<Rgb<u8>, &mut [u8]>::from_raw(width, height, frame.as_mut_slice()).unwrap();
+    assert_eq!(extract_data(&frame_image, min_alpha, rng_key)?, data);
+
+    // extraction must leave the borrowed buffer as it was
+    assert_eq!(frame, embedded_frame);
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_data_with_report() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let original_image = source_image.clone();
+
+    let data: Vec<u8> = (0..4_000u32).map(|i| (i * 13 % 241) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [11u8; 32];
+
+    let (stego_image, report) = embed_data_with_report(
+        &source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+
+    assert_eq!(source_image, original_image);
+    assert_eq!(report.message_planes, data.len().div_ceil(8));
+    assert_eq!(
+        report.planes_used.values().sum::<usize>(),
+        report.message_planes + report.header_planes
+    );
+    assert_eq!(report.header_overhead_bytes, report.header_planes * 8);
+    assert!(report.bits_flipped > 0);
+    assert!(report.pixels_changed > 0);
+    assert!(report.mse > 0.0);
+    assert!(report.psnr.is_finite() && report.psnr > 0.0);
+
+    assert_eq!(extract_data(&stego_image, min_alpha, rng_key)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_embed_leaves_image_intact() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?
+        .crop_imm(0, 0, 64, 64)
+        .to_rgb8();
+    let original_image = source_image.clone();
+
+    let data = vec![0u8; 100_000];
+    let min_alpha = 0.3f64;
+    let rng_key = [11u8; 32];
+
+    assert!(
+        embed_data_with_report(
+            &source_image,
+            &mut data.clone().into_iter(),
+            data.len(),
+            min_alpha,
+            rng_key,
+        )
+        .is_err()
+    );
+    assert_eq!(source_image, original_image);
+
+    assert!(
+        embed_data(
+            &mut source_image,
+            &mut data.clone().into_iter(),
+            data.len(),
+            min_alpha,
+            rng_key,
+        )
+        .is_err()
+    );
+    assert_eq!(source_image, original_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_data_length_mismatch() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let original_image = source_image.clone();
+    let data: Vec<u8> = (0..100).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [12u8; 32];
+
+    // a shorter iterator would have been padded with random bytes, and a longer one would have been cut off
+    for (declared, actual) in [(101, 100), (64, 65), (99, 100)] {
+        let result = embed_data(
+            &mut source_image,
+            &mut data.clone().into_iter(),
+            declared,
+            min_alpha,
+            rng_key,
+        );
+        assert!(
+            matches!(
+                result,
+                Err(PixelveilError::Steganography(SteganographyError::DataLengthMismatch {
+                    declared: d,
+                    actual: a,
+                })) if (d, a) == (declared, actual)
+            ),
+            "declared {declared}: {result:?}"
+        );
+        assert_eq!(source_image, original_image);
+    }
+
+    // the length of an ExactSizeIterator doesn't have to be passed
+    embed_data_exact_size(&mut source_image, data.clone(), min_alpha, rng_key)?;
+    assert_eq!(extract_data(&source_image, min_alpha, rng_key)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_plan_embedding_matches_embedding() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+
+    let data: Vec<u8> = (0..3_000u32).map(|i| (i * 7 % 239) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [5u8; 32];
+
+    let plan = plan_embedding(&source_image, data.len(), min_alpha, rng_key)?;
+    let (stego_image, report) = embed_data_with_report(
+        &source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+
+    assert_eq!(
+        plan.planes.len(),
+        report.message_planes + report.header_planes
+    );
+    assert_eq!(
+        plan.planes
+            .iter()
+            .filter(|plane| plane.kind == PlannedPlaneKind::Message)
+            .count(),
+        report.message_planes
+    );
+    assert_eq!(plan.mask.dimensions(), source_image.dimensions());
+
+    // every changed pixel must be inside a planned block, and every planned block must be highlighted in the mask
+    let planned_blocks: HashSet<(u32, u32)> =
+        plan.planes.iter().map(|plane| (plane.x, plane.y)).collect();
+    for (x, y, pixel) in stego_image.enumerate_pixels() {
+        let block = (x - x % 8, y - y % 8);
+        if pixel != source_image.get_pixel(x, y) {
+            assert!(planned_blocks.contains(&block));
+        }
+        assert_eq!(
+            planned_blocks.contains(&block),
+            *plan.mask.get_pixel(x, y) != Rgb([0, 0, 0])
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_estimate_maximum_capacity_is_exact() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let min_alpha = 0.3f64;
+    let rng_key = [6u8; 32];
+
+    let capacity = estimate_maximum_capacity(&source_image, min_alpha) as usize;
+    assert!(plan_embedding(&source_image, capacity, min_alpha, rng_key).is_ok());
+    assert!(matches!(
+        plan_embedding(&source_image, capacity + 1, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(..)
+        ))
+    ));
+
+    // images without room for a header have no capacity
+    for small_image in [RgbImage::new(8, 8), RgbImage::new(64, 64)] {
+        assert_eq!(estimate_maximum_capacity(&small_image, min_alpha), 0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_data_and_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let original_image = source_image.clone();
+
+    let data: Vec<u8> = (0..3_000u32).map(|i| (i * 19 % 233) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [9u8; 32];
+
+    // the declared length is longer than the data, so the data is rejected before it is verified
+    let result = embed_data_and_verify(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len() + 20,
+        min_alpha,
+        rng_key,
+    );
+    assert!(matches!(
+        result,
+        Err(PixelveilError::Steganography(
+            SteganographyError::DataLengthMismatch { .. }
+        ))
+    ));
+    assert_eq!(source_image, original_image);
+
+    embed_data_and_verify(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert_ne!(source_image, original_image);
+    assert_eq!(extract_data(&source_image, min_alpha, rng_key)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_slots() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+
+    let alice_data: Vec<u8> = (0..2_000u32).map(|i| (i * 3 % 256) as u8).collect();
+    let bob_data: Vec<u8> = (0..1_500u32).map(|i| (i * 5 % 256) as u8).collect();
+    let carol_data: Vec<u8> = (0..1_000u32).map(|i| (i * 11 % 256) as u8).collect();
+    let (alice_key, bob_key, carol_key) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+    let min_alpha = 0.3f64;
+
+    let payloads = [
+        SlotPayload {
+            data: &alice_data,
+            rng_key: alice_key,
+        },
+        SlotPayload {
+            data: &bob_data,
+            rng_key: bob_key,
+        },
+        SlotPayload {
+            data: &carol_data,
+            rng_key: carol_key,
+        },
+    ];
+    embed_slots(&mut source_image, &payloads, min_alpha)?;
+
+    assert_eq!(
+        extract_slot(&source_image, min_alpha, alice_key, 3)?,
+        alice_data
+    );
+    assert_eq!(
+        extract_slot(&source_image, min_alpha, bob_key, 3)?,
+        bob_data
+    );
+    assert_eq!(
+        extract_slot(&source_image, min_alpha, carol_key, 3)?,
+        carol_data
+    );
+    assert!(extract_slot(&source_image, min_alpha, [4u8; 32], 3).is_err());
+
+    // a shared key would let one recipient open another's slot
+    let embedded_image = source_image.clone();
+    let duplicate_key_payloads = [
+        payloads[0],
+        SlotPayload {
+            data: &bob_data,
+            rng_key: alice_key,
+        },
+    ];
+    assert!(matches!(
+        embed_slots(&mut source_image, &duplicate_key_payloads, min_alpha),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert_eq!(source_image, embedded_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_decoy() -> Result<(), Box<dyn std::error::Error>> {
+    let original_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let mut source_image = original_image.clone();
+
+    let decoy_data: Vec<u8> = (0..1_000u32).map(|i| (i * 7 % 256) as u8).collect();
+    let hidden_data: Vec<u8> = (0..3_000u32).map(|i| (i * 13 % 256) as u8).collect();
+    let (decoy_key, hidden_key) = ([5u8; 32], [6u8; 32]);
+    let min_alpha = 0.3f64;
+
+    embed_data_with_decoy(
+        &mut source_image,
+        &decoy_data,
+        decoy_key,
+        &hidden_data,
+        hidden_key,
+        min_alpha,
+    )?;
+
+    // the decoy is extracted like any other payload
+    assert_eq!(
+        extract_data(&source_image, min_alpha, decoy_key)?,
+        decoy_data
+    );
+    assert_eq!(
+        extract_hidden_data(&source_image, min_alpha, decoy_key, hidden_key)?,
+        hidden_data
+    );
+    assert!(extract_hidden_data(&source_image, min_alpha, decoy_key, [7u8; 32]).is_err());
+
+    // a payload that doesn't fit leaves the image unchanged
+    let mut small_image = image::imageops::crop_imm(&original_image, 0, 0, 64, 64).to_image();
+    let small_original = small_image.clone();
+    let result = embed_data_with_decoy(
+        &mut small_image,
+        &decoy_data,
+        decoy_key,
+        &hidden_data,
+        hidden_key,
+        min_alpha,
+    );
+    assert!(matches!(
+        result,
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(_, _)
+        ))
+    ));
+    assert_eq!(small_image, small_original);
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_and_append_data() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [8u8; 32];
+    let min_alpha = 0.3f64;
+
+    let data: Vec<u8> = (0..2_000u32).map(|i| (i * 3 % 256) as u8).collect();
+    embed_data(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+
+    // a payload of the same length reuses every plane
+    let same_length_data: Vec<u8> = data.iter().map(|byte| byte ^ 0xff).collect();
+    let report = replace_data(&mut source_image, &same_length_data, min_alpha, rng_key)?;
+    assert_eq!(report.previous_data_length, data.len());
+    assert_eq!(report.new_data_length, data.len());
+    assert_eq!(report.new_planes, 0);
+    assert_eq!(report.released_planes, 0);
+    assert_eq!(
+        extract_data(&source_image, min_alpha, rng_key)?,
+        same_length_data
+    );
+
+    let shorter_data: Vec<u8> = (0..500u32).map(|i| (i * 7 % 256) as u8).collect();
+    let report = replace_data(&mut source_image, &shorter_data, min_alpha, rng_key)?;
+    assert_eq!(report.new_data_length, shorter_data.len());
+    assert!(report.released_planes > 0);
+    assert_eq!(
+        extract_data(&source_image, min_alpha, rng_key)?,
+        shorter_data
+    );
+
+    let appended_data = [1u8, 2, 3, 4, 5];
+    let report = append_data(&mut source_image, &appended_data, min_alpha, rng_key)?;
+    assert_eq!(report.previous_data_length, shorter_data.len());
+    let mut expected_data = shorter_data.clone();
+    expected_data.extend_from_slice(&appended_data);
+    assert_eq!(
+        extract_data(&source_image, min_alpha, rng_key)?,
+        expected_data
+    );
+
+    // a payload that doesn't fit leaves the image unchanged
+    let before_failed_update = source_image.clone();
+    let huge_data = vec![0u8; estimate_maximum_capacity(&source_image, min_alpha) as usize * 2];
+    assert!(matches!(
+        replace_data(&mut source_image, &huge_data, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(_, _)
+        ))
+    ));
+    assert_eq!(source_image, before_failed_update);
+
+    // an incorrect key can't read the existing payload
+    assert!(matches!(
+        append_data(&mut source_image, &appended_data, min_alpha, [9u8; 32]),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InvalidIVData(_)
+        ))
+    ));
+    assert_eq!(source_image, before_failed_update);
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_across_images() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let mut vessel_images: Vec<RgbImage> = [(0, 0), (256, 0), (0, 256)]
+        .into_iter()
+        .map(|(x, y)| image::imageops::crop_imm(&source_image, x, y, 256, 256).to_image())
+        .collect();
+    let rng_key = [10u8; 32];
+    let min_alpha = 0.3f64;
+
+    // more data than any single vessel can hold
+    let capacities: Vec<u64> = vessel_images
+        .iter()
+        .map(|image| estimate_maximum_capacity(image, min_alpha))
+        .collect();
+    let data_length = capacities.iter().sum::<u64>() * 6 / 10;
+    assert!(capacities.iter().all(|capacity| *capacity < data_length));
+    let data: Vec<u8> = (0..data_length).map(|i| (i * 17 % 256) as u8).collect();
+
+    embed_data_across_images(&mut vessel_images, &data, min_alpha, rng_key)?;
+
+    // the images can be given in any order
+    vessel_images.reverse();
+    assert_eq!(
+        extract_data_across_images(&vessel_images, min_alpha, rng_key)?,
+        data
+    );
+
+    let missing_part = extract_data_across_images(&vessel_images[1..], min_alpha, rng_key);
+    let Err(PixelveilError::Steganography(SteganographyError::MissingPayloadParts(
+        part_count,
+        image_count,
+    ))) = missing_part
+    else {
+        panic!("expected a missing part error, got {missing_part:?}");
+    };
+    assert_eq!(part_count, 3);
+    assert_eq!(image_count, 2);
+
+    let no_images: [RgbImage; 0] = [];
+    assert!(matches!(
+        extract_data_across_images(&no_images, min_alpha, rng_key),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+
+    let duplicated_part = [
+        vessel_images[0].clone(),
+        vessel_images[0].clone(),
+        vessel_images[1].clone(),
+    ];
+    assert!(matches!(
+        extract_data_across_images(&duplicated_part, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InconsistentPayloadParts(_)
+        ))
+    ));
+
+    // the parts are sized with the exact capacity, so a payload just below it fits into a single image
+    let mut single_image = [source_image.clone()];
+    let near_full_data =
+        vec![3u8; estimate_maximum_capacity(&source_image, min_alpha) as usize - 100];
+    embed_data_across_images(&mut single_image, &near_full_data, min_alpha, rng_key)?;
+    assert_eq!(
+        extract_data_across_images(&single_image, min_alpha, rng_key)?,
+        near_full_data
+    );
+
+    // an image without planes can't hold its part, which is an error and not a panic
+    let mut with_tiny_image = [source_image.clone(), RgbImage::new(4, 4)];
+    assert!(matches!(
+        embed_data_across_images(&mut with_tiny_image, &data, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(..)
+        ))
+    ));
+    assert_eq!(with_tiny_image[0], source_image);
+
+    // a part of a different payload can't complete this one
+    let mut other_images = vessel_images.clone();
+    embed_data_across_images(&mut other_images, &data, min_alpha, rng_key)?;
+    let mixed_images = [
+        vessel_images[0].clone(),
+        vessel_images[1].clone(),
+        other_images[2].clone(),
+    ];
+    assert!(matches!(
+        extract_data_across_images(&mixed_images, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InconsistentPayloadParts(_)
+        ))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_secret_shares() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let mut vessel_images: Vec<RgbImage> = [(0, 0), (256, 0), (0, 256), (256, 256)]
+        .into_iter()
+        .map(|(x, y)| image::imageops::crop_imm(&source_image, x, y, 256, 256).to_image())
+        .collect();
+    let rng_key = [11u8; 32];
+    let min_alpha = 0.3f64;
+    let secret = b"correct horse battery staple".to_vec();
+
+    embed_secret_shares(&mut vessel_images, &secret, 3, min_alpha, rng_key)?;
+
+    // any 3 of the 4 images recover the secret, in any order
+    for skipped in 0..vessel_images.len() {
+        let mut images: Vec<RgbImage> = vessel_images
+            .iter()
+            .enumerate()
+            .filter(|(image_index, _)| *image_index != skipped)
+            .map(|(_, image)| image.clone())
+            .collect();
+        images.reverse();
+        assert_eq!(
+            recover_secret_from_shares(&images, min_alpha, rng_key)?,
+            secret
+        );
+    }
+
+    // a repeated share doesn't count twice
+    let too_few = [
+        vessel_images[0].clone(),
+        vessel_images[1].clone(),
+        vessel_images[1].clone(),
+    ];
+    assert!(matches!(
+        recover_secret_from_shares(&too_few, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientShares(3, 2)
+        ))
+    ));
+
+    // images that don't hold a share of the secret are skipped, as long as enough shares are left
+    let mut other_secret_images = vessel_images[..3].to_vec();
+    embed_secret_shares(
+        &mut other_secret_images,
+        b"another secret",
+        3,
+        min_alpha,
+        rng_key,
+    )?;
+    let with_foreign_images = [
+        source_image.clone(),
+        vessel_images[0].clone(),
+        other_secret_images[0].clone(),
+        vessel_images[2].clone(),
+        vessel_images[3].clone(),
+    ];
+    assert_eq!(
+        recover_secret_from_shares(&with_foreign_images, min_alpha, rng_key)?,
+        secret
+    );
+    assert!(matches!(
+        recover_secret_from_shares(&with_foreign_images[..4], min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientShares(3, 2)
+        ))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_probe() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [12u8; 32];
+    let min_alpha = 0.3f64;
+
+    assert_eq!(probe(&source_image, min_alpha, rng_key), None);
+
+    let data: Vec<u8> = (0..1_234u32).map(|i| (i * 3 % 256) as u8).collect();
+    embed_data(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+
+    assert_eq!(
+        probe(&source_image, min_alpha, rng_key),
+        Some(PayloadInfo {
+            data_length: data.len(),
+            format_version: 1,
+            block_encoding: BlockEncoding::Conjugation,
+            stores_cover: false,
+        })
+    );
+    assert_eq!(probe(&source_image, min_alpha, [13u8; 32]), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_images_smaller_than_a_plane() -> Result<(), Box<dyn std::error::Error>> {
+    let tiny_image = RgbImage::from_fn(4, 4, |x, y| Rgb([(x * 61) as u8, (y * 97) as u8, 7]));
+    let rng_key = [14u8; 32];
+    let min_alpha = 0.3f64;
+
+    // scanners run on every incoming image, an image without planes simply doesn't carry a payload
+    assert_eq!(probe(&tiny_image, min_alpha, rng_key), None);
+    assert!(matches!(
+        extract_data(&tiny_image, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(..) | SteganographyError::InvalidIVData(_)
+        ))
+    ));
+    assert_eq!(estimate_maximum_capacity(&tiny_image, min_alpha), 0);
+
+    let mut vessel_image = tiny_image.clone();
+    assert!(matches!(
+        embed_data(
+            &mut vessel_image,
+            &mut [1u8].into_iter(),
+            1,
+            min_alpha,
+            rng_key
+        ),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(..)
+        ))
+    ));
+    assert_eq!(vessel_image, tiny_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_min_alpha_is_rejected() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?
+        .crop_imm(0, 0, 64, 64)
+        .to_rgb8();
+    let data = [1u8, 2, 3];
+    let rng_key = [15u8; 32];
+
+    // above about 0.68 the dynamic prefix is longer than a plane, NaN and negative values are never meaningful
+    for min_alpha in [-0.1, 0.51, 0.9, f64::NAN, f64::INFINITY] {
+        let mut vessel_image = source_image.clone();
+        let results = [
+            embed_data(
+                &mut vessel_image,
+                &mut data.into_iter(),
+                data.len(),
+                min_alpha,
+                rng_key,
+            ),
+            embed_data_deterministic(&mut vessel_image, &data, min_alpha, rng_key),
+            embed_data_reversible(&mut vessel_image, &data, min_alpha, rng_key),
+            embed_data_tiled(&mut vessel_image, &data, 64, min_alpha, rng_key),
+            embed_slots(
+                &mut vessel_image,
+                &[SlotPayload {
+                    data: &data,
+                    rng_key,
+                }],
+                min_alpha,
+            ),
+            extract_data(&vessel_image, min_alpha, rng_key).map(|_| ()),
+            plan_embedding(&vessel_image, data.len(), min_alpha, rng_key).map(|_| ()),
+            rank_vessels(&[vessel_image.clone()], data.len(), min_alpha).map(|_| ()),
+            encode_prefixed_planes(&[true], min_alpha, &mut rand::rng()).map(|_| ()),
+        ];
+        for result in results {
+            assert!(matches!(result, Err(PixelveilError::InvalidParameter(_))));
+        }
+        assert_eq!(vessel_image, source_image);
+
+        assert_eq!(probe(&source_image, min_alpha, rng_key), None);
+        assert_eq!(estimate_maximum_capacity(&source_image, min_alpha), 0);
+        assert_eq!(estimate_reversible_capacity(&source_image, min_alpha), 0);
+        assert!(
+            BpcsConfig::builder()
+                .rng_key(rng_key)
+                .min_alpha(min_alpha)
+                .build()
+                .is_err()
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_data_with_unknown_alpha() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [14u8; 32];
+    let min_alpha = 0.27f64;
+
+    let data: Vec<u8> = (0..3_000u32).map(|i| (i * 5 % 256) as u8).collect();
+    embed_data(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+
+    let candidates = (0..=50).map(|i| i as f64 / 100.0);
+    let (found_alpha, extracted_data) =
+        extract_data_with_unknown_alpha(&source_image, rng_key, candidates.clone())?;
+    assert_eq!(extracted_data, data);
+    assert_eq!(extract_data(&source_image, found_alpha, rng_key)?, data);
+
+    assert!(matches!(
+        extract_data_with_unknown_alpha(&source_image, [15u8; 32], candidates),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InvalidIVData(_)
+        ))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_format_version_test_vectors() -> Result<(), Box<dyn std::error::Error>> {
+    // both vectors hold the same payload, embedded into the same 128x128 crop of the deterministic image
+    let data: Vec<u8> = (0..300u32).map(|i| ((i * 7 + 3) % 256) as u8).collect();
+    let rng_key = [21u8; 32];
+    let min_alpha = 0.3f64;
+
+    for format_version in [0u8, 1] {
+        let vessel_image = open(format!(
+            "tests/assets/test_vector_format_v{format_version}.png"
+        ))?
+        .to_rgb8();
+
+        assert_eq!(
+            probe(&vessel_image, min_alpha, rng_key),
+            Some(PayloadInfo {
+                data_length: data.len(),
+                format_version,
+                block_encoding: BlockEncoding::Conjugation,
+                stores_cover: false,
+            })
+        );
+        assert_eq!(extract_data(&vessel_image, min_alpha, rng_key)?, data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_data_deterministic() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [16u8; 32];
+    let min_alpha = 0.3f64;
+    let data: Vec<u8> = (0..1_001u32).map(|i| (i * 11 % 256) as u8).collect();
+
+    let mut first_image = source_image.clone();
+    let mut second_image = source_image.clone();
+    embed_data_deterministic(&mut first_image, &data, min_alpha, rng_key)?;
+    embed_data_deterministic(&mut second_image, &data, min_alpha, rng_key)?;
+    assert_eq!(first_image, second_image);
+    assert_eq!(extract_data(&first_image, min_alpha, rng_key)?, data);
+
+    let mut other_data = data.clone();
+    other_data[0] ^= 1;
+    let mut other_image = source_image.clone();
+    embed_data_deterministic(&mut other_image, &other_data, min_alpha, rng_key)?;
+    assert_ne!(first_image, other_image);
+
+    // the default embedding stays random
+    let mut random_image = source_image.clone();
+    embed_data(
+        &mut random_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert_ne!(first_image, random_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_data_minimum_change() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [23u8; 32];
+    let min_alpha = 0.3f64;
+    // noise-like data, like an encrypted payload
+    let data: Vec<u8> = (0..8_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+
+    let bits_flipped = |stego_image: &RgbImage| -> u32 {
+        zip(source_image.as_raw(), stego_image.as_raw())
+            .map(|(original, new)| (original ^ new).count_ones())
+            .sum()
+    };
+
+    let mut conjugated_image = source_image.clone();
+    embed_data(
+        &mut conjugated_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    let mut stego_image = source_image.clone();
+    embed_data_minimum_change(&mut stego_image, &data, min_alpha, rng_key)?;
+
+    assert!(bits_flipped(&stego_image) < bits_flipped(&conjugated_image));
+    assert_eq!(extract_data(&stego_image, min_alpha, rng_key)?, data);
+    assert_eq!(
+        probe(&stego_image, min_alpha, rng_key),
+        Some(PayloadInfo {
+            data_length: data.len(),
+            format_version: 1,
+            block_encoding: BlockEncoding::MinimumChange,
+            stores_cover: false,
+        })
+    );
+    let candidates = (0..=50).map(|i| i as f64 / 100.0);
+    let (_, extracted_data) = extract_data_with_unknown_alpha(&stego_image, rng_key, candidates)?;
+    assert_eq!(extracted_data, data);
+
+    // the encoding is kept when the payload is updated
+    append_data(&mut stego_image, b"more", min_alpha, rng_key)?;
+    assert_eq!(
+        probe(&stego_image, min_alpha, rng_key).map(|payload_info| payload_info.block_encoding),
+        Some(BlockEncoding::MinimumChange)
+    );
+    assert!(extract_data(&stego_image, min_alpha, rng_key)?.ends_with(b"more"));
+
+    // and it is available through the configuration
+    let config = BpcsConfig::builder()
+        .rng_key(rng_key)
+        .block_encoding(BlockEncoding::MinimumChange)
+        .verify(true)
+        .build()?;
+    let mut configured_image = source_image.clone();
+    embed_data_with_config(&mut configured_image, &data, &config)?;
+    assert_eq!(extract_data_with_config(&configured_image, &config)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_embed_data_reversible() -> Result<(), Box<dyn std::error::Error>> {
+    // a rendered image whose complex bit planes are regular patterns, so they compress well
+    let source_image = RgbImage::from_fn(256, 256, |x, y| {
+        Rgb([
+            (x ^ y) as u8,
+            (x + 2 * y) as u8,
+            (x.wrapping_mul(y) >> 3) as u8,
+        ])
+    });
+    let rng_key = [31u8; 32];
+    let min_alpha = 0.3f64;
+    let data: Vec<u8> = (0..3_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+    let capacity = estimate_reversible_capacity(&source_image, min_alpha);
+    assert!(capacity >= data.len() as u64);
+    assert!(capacity < estimate_maximum_capacity(&source_image, min_alpha));
+
+    let mut stego_image = source_image.clone();
+    embed_data_reversible(&mut stego_image, &data, min_alpha, rng_key)?;
+    assert_ne!(stego_image, source_image);
+
+    // the payload is extracted like any other payload, and the original image is restored bit for bit
+    assert_eq!(extract_data(&stego_image, min_alpha, rng_key)?, data);
+    let (restored_data, restored_image) = restore_cover(&stego_image, min_alpha, rng_key)?;
+    assert_eq!(restored_data, data);
+    assert_eq!(restored_image, source_image);
+    assert!(
+        probe(&stego_image, min_alpha, rng_key)
+            .is_some_and(|payload_info| payload_info.stores_cover)
+    );
+
+    // a regular payload doesn't store its cover
+    let mut regular_image = source_image.clone();
+    embed_data(
+        &mut regular_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    assert!(matches!(
+        restore_cover(&regular_image, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::CoverNotStored
+        ))
+    ));
+
+    // updating the payload would discard the stored cover, so the image is left as it is
+    let mut updated_image = stego_image.clone();
+    assert!(matches!(
+        replace_data(&mut updated_image, b"new data", min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::CoverStored
+        ))
+    ));
+    assert!(matches!(
+        append_data(&mut updated_image, b"more", min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::CoverStored
+        ))
+    ));
+    assert_eq!(updated_image, stego_image);
+
+    // the noise-like bit planes of a photograph barely compress, so there is no room left for a payload
+    let photo = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let mut photo_stego_image = photo.clone();
+    assert_eq!(estimate_reversible_capacity(&photo, min_alpha), 0);
+    assert!(matches!(
+        embed_data_reversible(&mut photo_stego_image, &data, min_alpha, rng_key),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientPlaneNumber(..)
+        ))
+    ));
+    assert_eq!(photo_stego_image, photo);
+
+    Ok(())
+}
+
+#[test]
+fn test_progress_and_cancellation() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [17u8; 32];
+    let min_alpha = 0.3f64;
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 13 % 256) as u8).collect();
+
+    let mut stages: Vec<BpcsStage> = Vec::new();
+    let mut stego_image = source_image.clone();
+    embed_data_with_progress(
+        &mut stego_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+        &mut |stage, completed: usize, total: usize| {
+            assert!(completed <= total);
+            stages.push(stage);
+            ControlFlow::Continue(())
+        },
+    )?;
+    assert!(matches!(
+        stages[0],
+        BpcsStage::ScanningPlanes { bit_index: 7 }
+    ));
+    assert!(stages.contains(&BpcsStage::SelectingPlanes));
+    assert_eq!(stages.last(), Some(&BpcsStage::WritingPlanes));
+
+    let extracted_data =
+        extract_data_with_progress(&stego_image, min_alpha, rng_key, &mut |_, _, _| {
+            ControlFlow::Continue(())
+        })?;
+    assert_eq!(extracted_data, data);
+
+    // cancelling at any stage leaves the image untouched
+    for cancelled_stage in [
+        BpcsStage::ScanningPlanes { bit_index: 7 },
+        BpcsStage::SelectingPlanes,
+        BpcsStage::WritingPlanes,
+    ] {
+        let mut cancelled_image = source_image.clone();
+        let result = embed_data_with_progress(
+            &mut cancelled_image,
+            &mut data.clone().into_iter(),
+            data.len(),
+            min_alpha,
+            rng_key,
+            &mut |stage, completed: usize, _| match stage == cancelled_stage && completed > 0 {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(PixelveilError::Steganography(SteganographyError::Cancelled))
+        ));
+        assert_eq!(cancelled_image, source_image);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_tiled() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let rng_key = [18u8; 32];
+    let min_alpha = 0.3f64;
+    let tile_height = 64u32;
+
+    let data: Vec<u8> = (0..40_000u32).map(|i| (i * 17 % 256) as u8).collect();
+    let mut stego_image = source_image.clone();
+    embed_data_tiled(&mut stego_image, &data, tile_height, min_alpha, rng_key)?;
+    assert_eq!(
+        extract_data_tiled(&stego_image, tile_height, min_alpha, rng_key)?,
+        data
+    );
+
+    // the payload is spread over every tile, instead of filling the top of the image
+    for y in (0..source_image.height()).step_by(tile_height as usize) {
+        let rows = y..(y + tile_height).min(source_image.height());
+        assert!(rows.into_iter().any(|y| {
+            (0..source_image.width()).any(|x| source_image[(x, y)] != stego_image[(x, y)])
+        }));
+    }
+
+    // the tiles can be embedded one at a time too, a greedy embedding fills the top of the image and leaves the rest
+    //  of it untouched
+    let mut greedy_image = source_image.clone();
+    let mut embedder = TiledEmbedder::new(&data, min_alpha, rng_key)?;
+    for y in (0..greedy_image.height()).step_by(tile_height as usize) {
+        let mut tile =
+            image::imageops::crop_imm(&greedy_image, 0, y, greedy_image.width(), tile_height)
+                .to_image();
+        embedder.embed_tile(&mut tile)?;
+        image::imageops::replace(&mut greedy_image, &tile, 0, y as i64);
+    }
+    embedder.finish()?;
+    assert_eq!(
+        extract_data_tiled(&greedy_image, tile_height, min_alpha, rng_key)?,
+        data
+    );
+    let last_row = (0..source_image.height())
+        .rev()
+        .find(|y| (0..source_image.width()).any(|x| source_image[(x, *y)] != greedy_image[(x, *y)]))
+        .unwrap();
+    assert!(last_row < source_image.height() - tile_height);
+
+    // the tiles can be given one at a time, as a streaming decoder would produce them
+    let mut extractor = TiledExtractor::new(min_alpha, rng_key)?;
+    for y in (0..stego_image.height()).step_by(tile_height as usize) {
+        let tile = image::imageops::crop_imm(&stego_image, 0, y, stego_image.width(), tile_height);
+        extractor.extract_tile(&tile.to_image())?;
+    }
+    assert_eq!(extractor.finish()?, data);
+
+    let too_much_data = vec![0u8; 2_000_000];
+    let mut unchanged_image = source_image.clone();
+    assert!(matches!(
+        embed_data_tiled(
+            &mut unchanged_image,
+            &too_much_data,
+            tile_height,
+            min_alpha,
+            rng_key
+        ),
+        Err(PixelveilError::Steganography(
+            SteganographyError::InsufficientTileCapacity(_)
+        ))
+    ));
+    assert_eq!(unchanged_image, source_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_data_from_borrowed_buffer() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let mut source_image = open(source_image_path)?.to_rgb8();
+
+    let data: Vec<u8> = (0..5_000u32).map(|i| (i * 13 % 256) as u8).collect();
+    let min_alpha = 0.3f64;
+    let rng_key = [11u8; 32];
+
+    embed_data(
+        &mut source_image,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        rng_key,
+    )?;
+    let stego_image = source_image.clone();
+
+    // a view over a read-only buffer, like a memory mapped file, can be extracted from directly
+    let borrowed_image: ImageBuffer<Rgb<u8>, &[u8]> = ImageBuffer::from_raw(
+        source_image.width(),
+        source_image.height(),
+        source_image.as_raw().as_slice(),
+    )
+    .unwrap();
+    assert_eq!(extract_data(&borrowed_image, min_alpha, rng_key)?, data);
+    assert_eq!(
+        extract_data_with_progress(&borrowed_image, min_alpha, rng_key, &mut |_, _, _| {
+            ControlFlow::Continue(())
+        })?,
+        data
+    );
+    assert_eq!(source_image, stego_image);
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_bit_plane_api() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let mut source_image = open(source_image_path)?.to_rgb8();
+    let mut gray_coded_image = source_image.clone();
+    image_to_gray_code(&mut gray_coded_image);
+
+    // reading a binary coded image on the fly matches reading its Gray Code
+    let binary_planes: Vec<_> = bit_planes(&source_image, 5, ImageCoding::BinaryCode)?.collect();
+    let gray_planes: Vec<_> = bit_planes(&gray_coded_image, 5, ImageCoding::GrayCode)?.collect();
+    assert_eq!(binary_planes, gray_planes);
+    assert_eq!(
+        binary_planes.len(),
+        ((source_image.width() / PLANE_SIZE) * (source_image.height() / PLANE_SIZE) * 3) as usize
+    );
+
+    let green_planes: Vec<_> =
+        channel_bit_planes(&source_image, 1, 5, ImageCoding::BinaryCode)?.collect();
+    assert_eq!(green_planes.len(), binary_planes.len() / 3);
+    assert!(
+        green_planes
+            .iter()
+            .all(|((_, _, channel, _), _)| *channel == 1)
+    );
+
+    // channels and bit indexes the image doesn't have are rejected up front, and images without planes have none
+    assert!(matches!(
+        bit_planes(&source_image, 8, ImageCoding::BinaryCode),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        channel_bit_planes(&source_image, 3, 5, ImageCoding::BinaryCode),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert_eq!(
+        bit_planes(&RgbImage::new(4, 4), 7, ImageCoding::BinaryCode)?.count(),
+        0
+    );
+
+    // conjugation mirrors the complexity
+    let (coords, mut plane) = binary_planes[0].clone();
+    let alpha = plane.alpha();
+    plane.conjugate();
+    assert!((plane.alpha() - (1.0 - alpha)).abs() < 1e-9);
+
+    write_plane(&mut gray_coded_image, &plane, coords)?;
+    assert_eq!(
+        read_plane(&gray_coded_image, coords, ImageCoding::GrayCode)?,
+        plane
+    );
+    image_to_binary_code(&mut gray_coded_image);
+    assert_eq!(
+        read_plane(&gray_coded_image, coords, ImageCoding::BinaryCode)?,
+        plane
+    );
+
+    // the dynamic prefix keeps every plane complex and the data intact
+    let min_alpha = 0.3f64;
+    let bits: Vec<bool> = (0..500).map(|i| i % 3 == 0).collect();
+    let planes = encode_prefixed_planes(&bits, min_alpha, &mut rand::rng())?;
+    assert_eq!(
+        planes.len(),
+        bits.len().div_ceil(data_bits_per_prefixed_plane(min_alpha))
+    );
+    assert!(planes.iter().all(|plane| plane.alpha() >= min_alpha));
+    assert_eq!(
+        decode_prefixed_planes(planes, min_alpha)?[..bits.len()],
+        bits
+    );
+
+    // writing planes through the raw API doesn't stop the regular functions from working
+    let data = [4u8, 8, 15, 16, 23, 42];
+    embed_data(
+        &mut source_image,
+        &mut data.into_iter(),
+        data.len(),
+        min_alpha,
+        [3u8; 32],
+    )?;
+    assert_eq!(extract_data(&source_image, min_alpha, [3u8; 32])?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_complexity_heatmap_and_accepted_plane_map() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let source_image = open(source_image_path)?.to_rgb8();
+    let min_alpha = 0.3f64;
+
+    // a flat image has no complexity and no usable planes
+    let flat_image = RgbImage::new(64, 64);
+    let flat_heatmap = render_complexity_heatmap(&flat_image, 2, 7)?;
+    assert!(
+        flat_heatmap
+            .pixels()
+            .all(|pixel| *pixel == Rgb([0, 0, 255]))
+    );
+    let flat_map = render_accepted_plane_map(&flat_image, min_alpha);
+    assert!(flat_map.pixels().all(|pixel| *pixel == Rgb([0, 0, 0])));
+
+    let heatmap = render_complexity_heatmap(&source_image, 0, 7)?;
+    assert_eq!(heatmap.dimensions(), source_image.dimensions());
+
+    // every plane passes a threshold of 0
+    let full_map = render_accepted_plane_map(&source_image, 0.0);
+    let covered_width = source_image.width() / PLANE_SIZE * PLANE_SIZE;
+    let covered_height = source_image.height() / PLANE_SIZE * PLANE_SIZE;
+    for (x, y, pixel) in full_map.enumerate_pixels() {
+        if x < covered_width && y < covered_height {
+            assert_eq!(*pixel, Rgb([255, 0, 0]));
+        }
+    }
+
+    // the map only marks blocks that hold usable planes
+    let map = render_accepted_plane_map(&source_image, min_alpha);
+    let mut usable_blocks: HashSet<(u32, u32)> = HashSet::new();
+    for bit_index in 0..8 {
+        for ((x, y, _, _), plane) in bit_planes(&source_image, bit_index, ImageCoding::BinaryCode)?
+        {
+            if plane.alpha() >= min_alpha {
+                usable_blocks.insert((x, y));
+            }
+        }
+    }
+    for (x, y, pixel) in map.enumerate_pixels() {
+        let block = (x / PLANE_SIZE * PLANE_SIZE, y / PLANE_SIZE * PLANE_SIZE);
+        assert_eq!(*pixel != Rgb([0, 0, 0]), usable_blocks.contains(&block));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_rank_vessels() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image = open("tests/assets/test_deterministic_plane_selection.png")?.to_rgb8();
+    let cropped_image = image::imageops::crop_imm(
+        &source_image,
+        0,
+        0,
+        source_image.width() / 2,
+        source_image.height() / 2,
+    )
+    .to_image();
+    let flat_image = RgbImage::new(source_image.width(), source_image.height());
+    let candidates = vec![flat_image, cropped_image, source_image.clone()];
+
+    let min_alpha = 0.3f64;
+    let payload_length = 2_000;
+    let ranking = rank_vessels(&candidates, payload_length, min_alpha)?;
+
+    assert_eq!(ranking.len(), candidates.len());
+    let mut indices: Vec<usize> = ranking.iter().map(|score| score.index).collect();
+    indices.sort();
+    assert_eq!(indices, vec![0, 1, 2]);
+
+    // the flat image can't hold anything, so it is ranked last
+    let last = ranking.last().unwrap();
+    assert_eq!(last.index, 0);
+    assert!(!last.fits);
+    assert_eq!(last.capacity, 0);
+    assert_eq!(last.most_significant_bit_index, None);
+
+    for score in &ranking {
+        assert!((0.0..=1.0).contains(&score.high_bit_plane_share));
+        assert_eq!(score.fits, score.capacity >= payload_length);
+    }
+
+    // the full image has more capacity than a quarter of it, so the same payload uses less of it
+    let full = ranking.iter().find(|score| score.index == 2).unwrap();
+    let cropped = ranking.iter().find(|score| score.index == 1).unwrap();
+    assert!(full.capacity > cropped.capacity);
+    assert!(full.capacity_usage < cropped.capacity_usage);
+
+    // a payload that fits is actually embeddable into the top ranked vessel
+    let best = &ranking[0];
+    assert!(best.fits);
+    let mut vessel = candidates[best.index].clone();
+    let data: Vec<u8> = (0..payload_length).map(|i| (i % 256) as u8).collect();
+    embed_data(
+        &mut vessel,
+        &mut data.clone().into_iter(),
+        data.len(),
+        min_alpha,
+        [5u8; 32],
+    )?;
+    assert_eq!(extract_data(&vessel, min_alpha, [5u8; 32])?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_circular_bpcs_with_config() -> Result<(), Box<dyn std::error::Error>> {
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let source_image = open(source_image_path)?.to_rgb8();
+    let data: Vec<u8> = (0..3_000u32).map(|i| (i * 7 % 256) as u8).collect();
+
+    // the default policies embed exactly like embed_data
+    let default_config = BpcsConfig::builder().rng_key([9u8; 32]).build()?;
+    let mut default_image = source_image.clone();
+    embed_data_with_config(&mut default_image, &data, &default_config)?;
+    assert_eq!(extract_data(&default_image, 0.3, [9u8; 32])?, data);
+
+    let region = BlockPolicy::Region {
+        x: 0,
+        y: 0,
+        width: source_image.width(),
+        height: source_image.height() / 2,
+    };
+    let config = BpcsConfig::builder()
+        .min_alpha(0.25)
+        .rng_key([10u8; 32])
+        .channels(ChannelPolicy::Only(vec![0, 2]))
+        .blocks(region)
+        .deterministic(true)
+        .verify(true)
+        .build()?;
+
+    let mut stego_image = source_image.clone();
+    embed_data_with_config(&mut stego_image, &data, &config)?;
+    assert_eq!(extract_data_with_config(&stego_image, &config)?, data);
+
+    // only the allowed channels in the allowed region changed
+    for (x, y, pixel) in stego_image.enumerate_pixels() {
+        let original = source_image.get_pixel(x, y);
+        assert_eq!(pixel[1], original[1]);
+        if y >= source_image.height() / 2 {
+            assert_eq!(pixel, original);
+        }
+    }
+
+    // the deterministic layer makes the embedding reproducible
+    let mut second_image = source_image.clone();
+    embed_data_with_config(&mut second_image, &data, &config)?;
+    assert_eq!(stego_image, second_image);
+
+    // a channel that the image doesn't have is rejected, and the image is left unchanged
+    let alpha_channel_config = BpcsConfig::builder()
+        .rng_key([10u8; 32])
+        .channels(ChannelPolicy::Only(vec![3]))
+        .build()?;
+    let mut untouched_image = source_image.clone();
+    assert!(matches!(
+        embed_data_with_config(&mut untouched_image, &data, &alpha_channel_config),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert_eq!(untouched_image, source_image);
+
+    assert!(matches!(
+        BpcsConfig::builder()
+            .min_alpha(0.7)
+            .rng_key([0u8; 32])
+            .build(),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_pixelveil_errors() -> Result<(), Box<dyn std::error::Error>> {
+    use std::error::Error;
+
+    // I/O errors of the image crate are surfaced as I/O errors, and keep the original error as their source
+    let missing_file = open_rgbimage_from_path("tests/assets/missing.png");
+    let Err(PixelveilError::Io(io_error)) = &missing_file else {
+        panic!("expected an I/O error, got {missing_file:?}");
+    };
+    assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+    assert!(missing_file.unwrap_err().source().is_some());
+
+    let float_image = image::DynamicImage::new(8, 8, image::ColorType::Rgb32F);
+    assert!(matches!(
+        export_image_to_png_bytes(&float_image),
+        Err(PixelveilError::UnsupportedPixelFormat(_))
+    ));
+
+    assert!(matches!(
+        subtract_images(&RgbImage::new(8, 8), &RgbImage::new(8, 16)),
+        Err(PixelveilError::DimensionMismatch((8, 8), (8, 16)))
+    ));
+
+    // invalid arguments are returned as errors, and nothing is written
+    let source_image_path = "tests/assets/test_deterministic_plane_selection.png";
+    let source_image = open(source_image_path)?.to_rgb8();
+    let mut untouched_image = source_image.clone();
+    assert!(matches!(
+        embed_slots(&mut untouched_image, &[], 0.3),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        embed_data_tiled(&mut untouched_image, &[1, 2, 3], 12, 0.3, [0u8; 32]),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        read_plane(&source_image, (0, 0, 3, 0), ImageCoding::BinaryCode),
+        Err(PixelveilError::InvalidParameter(_))
+    ));
+    assert_eq!(untouched_image, source_image);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_bpcs_config_serde() -> Result<(), Box<dyn std::error::Error>> {
+    let config = BpcsConfig::builder()
+        .min_alpha(0.4)
+        .rng_key([3u8; 32])
+        .channels(ChannelPolicy::Only(vec![1]))
+        .verify(true)
+        .build()?;
+    let serialized = serde_json::to_string(&config)?;
+    assert_eq!(serde_json::from_str::<BpcsConfig>(&serialized)?, config);
+
+    // missing fields take their defaults, and the values are validated while loading
+    let key = format!("{:?}", [5u8; 32]);
+    let loaded: BpcsConfig = serde_json::from_str(&format!(r#"{{"rng_key": {key}}}"#))?;
+    assert_eq!(loaded, BpcsConfig::builder().rng_key([5u8; 32]).build()?);
+    assert!(
+        serde_json::from_str::<BpcsConfig>(&format!(r#"{{"rng_key": {key}, "min_alpha": 0.9}}"#))
+            .is_err()
+    );
+    assert!(serde_json::from_str::<BpcsConfig>(r#"{"min_alpha": 0.3}"#).is_err());
+
+    Ok(())
+}
@@ -8,13 +8,9 @@ use crate::pyo3_err_prop_utils::open_image_from_bytes;
 
 #[pyfunction]
 fn subtract_pixels(pixel1: (u8, u8, u8), pixel2: (u8, u8, u8)) -> PyResult<(u8, u8, u8)> {
-    let rgb_pixel1 = Rgb::<u8> {
-        0: [pixel1.0, pixel1.1, pixel1.2],
-    };
+    let rgb_pixel1 = Rgb::<u8>([pixel1.0, pixel1.1, pixel1.2]);
 
-    let rgb_pixel2 = Rgb::<u8> {
-        0: [pixel2.0, pixel2.1, pixel2.2],
-    };
+    let rgb_pixel2 = Rgb::<u8>([pixel2.0, pixel2.1, pixel2.2]);
 
     let subtracted = pixelveil::image_steganalysis::subtract_pixels(&rgb_pixel1, &rgb_pixel2);
 
@@ -23,13 +19,9 @@ fn subtract_pixels(pixel1: (u8, u8, u8), pixel2: (u8, u8, u8)) -> PyResult<(u8,
 
 #[pyfunction]
 fn xor_pixels(pixel1: (u8, u8, u8), pixel2: (u8, u8, u8)) -> PyResult<(u8, u8, u8)> {
-    let rgb_pixel1 = Rgb::<u8> {
-        0: [pixel1.0, pixel1.1, pixel1.2],
-    };
+    let rgb_pixel1 = Rgb::<u8>([pixel1.0, pixel1.1, pixel1.2]);
 
-    let rgb_pixel2 = Rgb::<u8> {
-        0: [pixel2.0, pixel2.1, pixel2.2],
-    };
+    let rgb_pixel2 = Rgb::<u8>([pixel2.0, pixel2.1, pixel2.2]);
 
     let xored = pixelveil::image_steganalysis::xor_pixels(&rgb_pixel1, &rgb_pixel2);
 